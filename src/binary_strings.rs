@@ -0,0 +1,116 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Ascii,
+    Utf16,
+}
+
+/// A run of printable characters found in binary data and the offset of its first byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedString {
+    pub offset: usize,
+    pub encoding: Encoding,
+    pub text: String,
+}
+
+impl fmt::Display for ExtractedString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let encoding = match self.encoding {
+            Encoding::Ascii => "ASCII",
+            Encoding::Utf16 => "UTF-16",
+        };
+        write!(f, "{} \"{}\" at {:#X}", encoding, self.text, self.offset)
+    }
+}
+
+fn is_printable(ch: char) -> bool {
+    ch == '\t' || (' '..='~').contains(&ch)
+}
+
+/// Runs of at least `min_length` printable characters, both ASCII and UTF-16LE at either byte
+/// alignment, ordered by offset. UTF-16 runs also take Latin letters beyond ASCII, eg. `ë`.
+pub fn extract(bytes: &[u8], min_length: usize) -> Vec<ExtractedString> {
+    let min_length = min_length.max(1);
+    let mut strings = Vec::new();
+    let mut push = |offset, encoding, text: &mut String, length: &mut usize| {
+        if *length >= min_length {
+            strings.push(ExtractedString {
+                offset,
+                encoding,
+                text: std::mem::take(text),
+            });
+        }
+        text.clear();
+        *length = 0;
+    };
+
+    let (mut text, mut length, mut start) = (String::new(), 0, 0);
+    for (index, &byte) in bytes.iter().enumerate() {
+        if is_printable(byte as char) {
+            if length == 0 {
+                start = index;
+            }
+            text.push(byte as char);
+            length += 1;
+        } else {
+            push(start, Encoding::Ascii, &mut text, &mut length);
+        }
+    }
+    push(start, Encoding::Ascii, &mut text, &mut length);
+
+    for alignment in 0..2 {
+        let units = bytes.get(alignment..).unwrap_or_default().chunks_exact(2);
+        for (index, pair) in units.enumerate() {
+            let offset = alignment + index * 2;
+            let unit = u16::from_le_bytes([pair[0], pair[1]]);
+            match char::from_u32(unit as u32) {
+                Some(ch) if is_printable(ch) || (ch.is_alphabetic() && unit < 0x250) => {
+                    if length == 0 {
+                        start = offset;
+                    }
+                    text.push(ch);
+                    length += 1;
+                }
+                _ => push(start, Encoding::Utf16, &mut text, &mut length),
+            }
+        }
+        push(start, Encoding::Utf16, &mut text, &mut length);
+    }
+    strings.sort_by_key(|string| string.offset);
+    strings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reg_file::to_utf16_bytes;
+
+    #[test]
+    fn extracts_ascii_and_utf16_runs_with_their_offsets() {
+        let mut bytes = vec![0x14, 0x00, 0x1F, 0x80];
+        bytes.extend(b"C:\\Tools\0\x01");
+        bytes.push(0xFF);
+        bytes.extend(to_utf16_bytes("Zoë\\run.lnk"));
+        bytes.extend(b"ab\0");
+        let strings = extract(&bytes, 4);
+        assert_eq!(
+            strings,
+            vec![
+                ExtractedString {
+                    offset: 4,
+                    encoding: Encoding::Ascii,
+                    text: "C:\\Tools".into()
+                },
+                ExtractedString {
+                    offset: 15,
+                    encoding: Encoding::Utf16,
+                    text: "Zoë\\run.lnk".into()
+                },
+            ]
+        );
+        assert_eq!(strings[1].to_string(), "UTF-16 \"Zoë\\run.lnk\" at 0xF");
+        assert_eq!(extract(&bytes, 2).last().unwrap().text, "ab");
+        assert!(extract(&bytes, 12).is_empty());
+    }
+}
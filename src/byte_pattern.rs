@@ -0,0 +1,128 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Element {
+    Byte(u8),
+    /// `??`, exactly one byte of any value.
+    Any,
+    /// `[min-max]`, between `min` and `max` bytes of any value.
+    Gap(usize, usize),
+}
+
+/// A sequence of hex bytes, `??` wildcards and `[n]` or `[min-max]` gaps, eg.
+/// `4D 5A ?? ?? [2-4] 50 45`. Bytes can also be written without spaces, `4D5A`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BytePattern {
+    elements: Vec<Element>,
+}
+
+fn parse_count(text: &str, token: &str) -> Result<usize, String> {
+    text.parse().map_err(|_| {
+        format!(
+            "\"{}\" is not a valid gap, expected [n] or [min-max]",
+            token
+        )
+    })
+}
+
+impl BytePattern {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut elements = Vec::new();
+        for token in text.split_whitespace() {
+            if token == "??" {
+                elements.push(Element::Any);
+            } else if let Some(gap) = token.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+                let (min, max) = match gap.split_once('-') {
+                    Some((min, max)) => (parse_count(min, token)?, parse_count(max, token)?),
+                    None => {
+                        let count = parse_count(gap, token)?;
+                        (count, count)
+                    }
+                };
+                if min > max {
+                    return Err(format!("\"{}\" has its bounds the wrong way round", token));
+                }
+                elements.push(Element::Gap(min, max));
+            } else {
+                if token.len() % 2 != 0 || !token.chars().all(|ch| ch.is_ascii_hexdigit()) {
+                    return Err(format!("\"{}\" is not a hex byte", token));
+                }
+                for index in (0..token.len()).step_by(2) {
+                    let byte = u8::from_str_radix(&token[index..index + 2], 16).unwrap();
+                    elements.push(Element::Byte(byte));
+                }
+            }
+        }
+        if elements.is_empty() {
+            return Err("Byte pattern is empty".into());
+        }
+        Ok(Self { elements })
+    }
+
+    /// Offset of the first match in `bytes`.
+    pub fn find(&self, bytes: &[u8]) -> Option<usize> {
+        (0..=bytes.len()).find(|&start| matches_at(&self.elements, &bytes[start..]))
+    }
+}
+
+fn matches_at(elements: &[Element], bytes: &[u8]) -> bool {
+    let (element, rest) = match elements.split_first() {
+        Some(split) => split,
+        None => return true,
+    };
+    match *element {
+        Element::Byte(byte) => bytes.first() == Some(&byte) && matches_at(rest, &bytes[1..]),
+        Element::Any => !bytes.is_empty() && matches_at(rest, &bytes[1..]),
+        Element::Gap(min, max) => {
+            (min..=max.min(bytes.len())).any(|skipped| matches_at(rest, &bytes[skipped..]))
+        }
+    }
+}
+
+impl fmt::Display for BytePattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let elements = self
+            .elements
+            .iter()
+            .map(|element| match element {
+                Element::Byte(byte) => format!("{:02X}", byte),
+                Element::Any => "??".to_string(),
+                Element::Gap(min, max) if min == max => format!("[{}]", min),
+                Element::Gap(min, max) => format!("[{}-{}]", min, max),
+            })
+            .collect::<Vec<String>>();
+        write!(f, "{}", elements.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_bytes_with_wildcards_and_gaps() {
+        let pattern = BytePattern::parse("4D 5A ?? ?? [2-4] 50 45").unwrap();
+        let mut bytes = vec![0x00, 0x11, 0x4D, 0x5A, 0x90, 0x00, 0xAA, 0xBB, 0xCC];
+        assert_eq!(pattern.find(&bytes), None);
+        bytes.extend([0x50, 0x45, 0x00]);
+        assert_eq!(pattern.find(&bytes), Some(2));
+        bytes.insert(8, 0xDD);
+        bytes.insert(8, 0xDD);
+        assert_eq!(pattern.find(&bytes), None, "gap is now 5 bytes");
+        assert_eq!(
+            BytePattern::parse("4d5a [3]").unwrap().find(&[0x4D, 0x5A]),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_patterns() {
+        for pattern in ["", "4G", "4D5", "[2-x]", "[4-2]", "?"] {
+            assert!(BytePattern::parse(pattern).is_err(), "{}", pattern);
+        }
+        assert_eq!(
+            BytePattern::parse("4d5a ?? [2] [1-3]").unwrap().to_string(),
+            "4D 5A ?? [2] [1-3]"
+        );
+    }
+}
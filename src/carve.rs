@@ -0,0 +1,469 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    hive::{
+        base_block_checksum, read_u32, BaseBlock, Hive, KeyNode, BASE_BLOCK_SIZE, KEY_HIVE_ENTRY,
+    },
+    hive_recovery::ORPHANED_KEYS,
+    memory_source::MemorySource,
+    registry_source::{KeyInfo, RegType, RegistryValue},
+};
+
+/// Hives in disk images start on a sector boundary, in memory dumps on a page boundary.
+const SECTOR_SIZE: usize = 512;
+const SCAN_CHUNK_SIZE: usize = 4 << 20;
+const HBIN_HEADER_SIZE: usize = 0x20;
+const HBIN_ALIGNMENT: u32 = 0x1000;
+const NONE: u32 = 0xFFFFFFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CarveKind {
+    /// A base block followed by every hive bin it accounts for.
+    Whole,
+    /// A base block whose hive bins stop early or are broken part way through.
+    Partial,
+    /// Hive bins without a base block, given a new one.
+    Fragment,
+}
+
+impl fmt::Display for CarveKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Whole => write!(f, "whole"),
+            Self::Partial => write!(f, "partial"),
+            Self::Fragment => write!(f, "fragment"),
+        }
+    }
+}
+
+/// A hive rebuilt from a raw file. `offset` is where its base block, or first hive bin for a
+/// fragment, was found and `span` how many bytes of the file it was carved from.
+pub struct CarvedHive {
+    pub offset: u64,
+    pub span: u64,
+    pub kind: CarveKind,
+    pub data: Vec<u8>,
+    pub notes: Vec<String>,
+}
+
+/// Reads up to `len` bytes, fewer at the end of the file.
+fn read_at(file: &mut File, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+    let mut data = Vec::with_capacity(len);
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.by_ref().take(len as u64).read_to_end(&mut data))
+        .map_err(|err| err.to_string())?;
+    Ok(data)
+}
+
+/// The offset and size fields of a hive bin header, if it looks like one.
+fn hbin_header(header: &[u8]) -> Option<(u32, u32)> {
+    if header.get(0..4) != Some(b"hbin") {
+        return None;
+    }
+    let offset = read_u32(header, 0x04)?;
+    let size = read_u32(header, 0x08)?;
+    if offset % HBIN_ALIGNMENT != 0 || size < HBIN_ALIGNMENT || size % HBIN_ALIGNMENT != 0 {
+        return None;
+    }
+    Some((offset, size))
+}
+
+/// Reads hive bins from `offset` for as long as each one follows on from the last, starting at
+/// hive offset `first` or wherever the first bin says it is. Returns the hive offset of the
+/// first bin and the bins.
+fn read_bins(
+    file: &mut File,
+    offset: u64,
+    first: Option<u32>,
+    limit: Option<u32>,
+) -> Result<(u32, Vec<u8>), String> {
+    let mut bins = Vec::new();
+    let mut first = first;
+    loop {
+        let position = offset + bins.len() as u64;
+        let header = read_at(file, position, HBIN_HEADER_SIZE)?;
+        let (bin_offset, size) = match hbin_header(&header) {
+            Some(header) => header,
+            None => break,
+        };
+        let expected = first.map(|first| first + bins.len() as u32);
+        if expected.is_some_and(|expected| expected != bin_offset) {
+            break;
+        }
+        if limit.is_some_and(|limit| bins.len() as u64 + size as u64 > limit as u64) {
+            break;
+        }
+        let bin = read_at(file, position, size as usize)?;
+        if bin.len() != size as usize {
+            break;
+        }
+        first.get_or_insert(bin_offset);
+        bins.extend(bin);
+    }
+    Ok((first.unwrap_or(0), bins))
+}
+
+/// The first allocated key cell flagged as a hive root.
+fn find_root_cell(hive: &Hive) -> Option<u32> {
+    hive.cells()
+        .into_iter()
+        .filter(|(_, size)| *size < 0)
+        .filter_map(|(offset, _)| hive.key_node(offset).ok())
+        .find(|key| key.flags & KEY_HIVE_ENTRY != 0)
+        .map(|key| key.offset)
+}
+
+fn has_keys(hive: &Hive) -> bool {
+    hive.cells()
+        .into_iter()
+        .any(|(offset, size)| size < 0 && hive.key_node(offset).is_ok())
+}
+
+/// A base block for bins that lost theirs. Bins that started past the first are put back at
+/// their own offset behind a single empty bin, so the cell offsets inside them still line up.
+fn rebuild(first: u32, bins: Vec<u8>) -> Result<Hive, String> {
+    let mut data = vec![0; BASE_BLOCK_SIZE];
+    data[0..4].copy_from_slice(b"regf");
+    for (offset, field) in [
+        (0x04, 1),
+        (0x08, 1),
+        (0x14, 1),
+        (0x18, 5),
+        (0x20, 1),
+        (0x24, NONE),
+        (0x28, first + bins.len() as u32),
+        (0x2C, 1),
+    ] {
+        data[offset..offset + 4].copy_from_slice(&field.to_le_bytes());
+    }
+    if first > 0 {
+        let mut padding = vec![0; first as usize];
+        padding[0..4].copy_from_slice(b"hbin");
+        padding[0x08..0x0C].copy_from_slice(&first.to_le_bytes());
+        padding[0x20..0x24].copy_from_slice(&(first - 0x20).to_le_bytes());
+        data.extend(padding);
+    }
+    data.extend(bins);
+    let hive = Hive::from_bytes(data.to_owned())?;
+    if let Some(root_cell) = find_root_cell(&hive) {
+        data[0x24..0x28].copy_from_slice(&root_cell.to_le_bytes());
+    }
+    let checksum = base_block_checksum(&data);
+    data[0x1FC..0x200].copy_from_slice(&checksum.to_le_bytes());
+    Hive::from_bytes(data)
+}
+
+/// Carves the hive whose base block, or first hive bin, is at `offset`.
+fn carve_from(file: &mut File, offset: u64) -> Result<CarvedHive, String> {
+    let signature = read_at(file, offset, 4)?;
+    if signature == b"hbin" {
+        let (first, bins) = read_bins(file, offset, None, None)?;
+        if bins.is_empty() {
+            return Err(format!("No hive bin at 0x{:X}", offset));
+        }
+        let span = bins.len() as u64;
+        let hive = rebuild(first, bins)?;
+        if !has_keys(&hive) {
+            return Err(format!("Hive bins at 0x{:X} hold no keys", offset));
+        }
+        let mut notes = vec![format!(
+            "{} bytes of hive bins starting at hive offset 0x{:X}",
+            span, first
+        )];
+        if hive.root_key().is_err() {
+            notes.push("Root key not found, keys are listed by the path they still have".into());
+        }
+        return Ok(CarvedHive {
+            offset,
+            span,
+            kind: CarveKind::Fragment,
+            data: hive.into_bytes(),
+            notes,
+        });
+    }
+
+    let base_block = read_at(file, offset, BASE_BLOCK_SIZE)?;
+    let parsed = BaseBlock::parse(&base_block)?;
+    if parsed.major_version != 1
+        || parsed.hive_bins_size == 0
+        || parsed.hive_bins_size % HBIN_ALIGNMENT != 0
+    {
+        return Err(format!("No valid base block at 0x{:X}", offset));
+    }
+    let mut notes = Vec::new();
+    if base_block_checksum(&base_block) != parsed.checksum {
+        notes.push("Base block checksum does not match".into());
+    }
+    let (_, bins) = read_bins(
+        file,
+        offset + BASE_BLOCK_SIZE as u64,
+        Some(0),
+        Some(parsed.hive_bins_size),
+    )?;
+    let kind = if bins.len() == parsed.hive_bins_size as usize {
+        CarveKind::Whole
+    } else {
+        notes.push(format!(
+            "{} of {} bytes of hive bins found",
+            bins.len(),
+            parsed.hive_bins_size
+        ));
+        CarveKind::Partial
+    };
+    let span = (BASE_BLOCK_SIZE + bins.len()) as u64;
+    let mut data = base_block;
+    data.extend(bins);
+    Ok(CarvedHive {
+        offset,
+        span,
+        kind,
+        data,
+        notes,
+    })
+}
+
+/// Carves the hive found at `offset` by `carve`.
+pub fn carve_at(path: &Path, offset: u64) -> Result<CarvedHive, String> {
+    let mut file = File::open(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    carve_from(&mut file, offset).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Scans every sector of a raw disk image, memory dump or any other file for regf base blocks and
+/// hbin signatures. Bins that follow a base block are carved with it, the rest are grouped into
+/// fragments of consecutive bins.
+pub fn carve(path: &Path) -> Result<Vec<CarvedHive>, String> {
+    let error = |err: String| format!("{}: {}", path.display(), err);
+    let mut file = File::open(path).map_err(|err| error(err.to_string()))?;
+    let mut carved = Vec::new();
+    let mut covered_until = 0;
+    let mut position = 0;
+    loop {
+        let chunk = read_at(&mut file, position, SCAN_CHUNK_SIZE).map_err(error)?;
+        if chunk.is_empty() {
+            break;
+        }
+        for sector in (0..chunk.len()).step_by(SECTOR_SIZE) {
+            let offset = position + sector as u64;
+            if offset < covered_until {
+                continue;
+            }
+            if !matches!(chunk.get(sector..sector + 4), Some(b"regf") | Some(b"hbin")) {
+                continue;
+            }
+            if let Ok(hive) = carve_from(&mut file, offset) {
+                covered_until = offset + hive.span;
+                carved.push(hive);
+            }
+        }
+        position += chunk.len() as u64;
+    }
+    Ok(carved)
+}
+
+/// Where a carved hive is mounted, the file it came from and the offset it was found at.
+pub fn root_name(path: &Path, carved: &CarvedHive) -> String {
+    let file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    match carved.kind {
+        CarveKind::Whole => format!("{} @ 0x{:X}", file_name, carved.offset),
+        kind => format!("{} @ 0x{:X} ({})", file_name, carved.offset, kind),
+    }
+}
+
+/// Writes the rebuilt hive to `directory`, named after the file and offset it was carved from.
+pub fn write(path: &Path, carved: &CarvedHive, directory: &Path) -> Result<PathBuf, String> {
+    let file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let output = directory.join(format!("{}_0x{:X}.hiv", file_name, carved.offset));
+    fs::create_dir_all(directory)
+        .and_then(|_| fs::write(&output, &carved.data))
+        .map_err(|err| format!("{}: {}", output.display(), err))?;
+    Ok(output)
+}
+
+/// Path of the key as far up as the fragment holds it, under `ORPHANED_KEYS` when its parents
+/// were lost.
+fn fragment_path(hive: &Hive, key: &KeyNode) -> String {
+    let mut components = Vec::new();
+    let mut node = key.to_owned();
+    loop {
+        if node.flags & KEY_HIVE_ENTRY != 0 {
+            break;
+        }
+        components.push(node.name.to_owned());
+        node = match hive.key_node(node.parent) {
+            Ok(parent) if components.len() < 512 => parent,
+            _ => {
+                components.push(ORPHANED_KEYS.to_string());
+                break;
+            }
+        };
+    }
+    components.reverse();
+    components.join("\\")
+}
+
+/// Loads every key of a hive without a usable root into `source`, returns how many.
+pub fn load_fragment(hive: &Hive, root: &str, source: &mut MemorySource) -> usize {
+    let mut keys = 0;
+    for (offset, size) in hive.cells() {
+        if size >= 0 {
+            continue;
+        }
+        let key = match hive.key_node(offset) {
+            Ok(key) => key,
+            Err(_) => continue,
+        };
+        let path = fragment_path(hive, &key);
+        source.set_key_info(
+            root,
+            &path,
+            KeyInfo {
+                last_write_time: key.last_write_time,
+                class_name: hive.class_name(&key).ok().flatten(),
+                ..Default::default()
+            },
+        );
+        for value_offset in hive.value_offsets(&key).unwrap_or_default() {
+            let value = match hive.value_node(value_offset) {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            if let Ok(bytes) = hive.value_data(&value) {
+                let vtype = RegType::from_u32(value.data_type).unwrap_or(RegType::REG_NONE);
+                source.set_value(root, &path, &value.name, RegistryValue { vtype, bytes });
+            }
+        }
+        keys += 1;
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        registry_source::RegistrySource,
+        test_hive::{build, sz, temp_dir, TestHive, TestKey},
+    };
+
+    fn test_hive(name: &str) -> TestHive {
+        build(
+            &TestKey::new("ROOT").key(TestKey::new("Software").key(TestKey::new(name).value(
+                "Path",
+                1,
+                sz("C:\\Temp\\evil.exe"),
+            ))),
+        )
+    }
+
+    /// Junk with a whole hive, a fragment without a base block and a hive cut short.
+    fn image() -> (Vec<u8>, Vec<u64>) {
+        let junk = |len: usize| (0..len).map(|index| (index * 7) as u8).collect::<Vec<u8>>();
+        let mut image = junk(0x2200);
+        let mut offsets = vec![image.len() as u64];
+        image.extend(test_hive("Whole").bytes);
+        image.extend(junk(0x400));
+        offsets.push(image.len() as u64);
+        image.extend(&test_hive("Fragment").bytes[BASE_BLOCK_SIZE..]);
+        image.extend(junk(0x600));
+        offsets.push(image.len() as u64);
+        let mut partial = test_hive("Partial");
+        let hive_bins_size = read_u32(&partial.bytes, 0x28).unwrap() + 0x1000;
+        partial.bytes[0x28..0x2C].copy_from_slice(&hive_bins_size.to_le_bytes());
+        partial.update_checksum();
+        image.extend(partial.bytes);
+        image.extend(junk(0x1000));
+        (image, offsets)
+    }
+
+    fn carve_image(name: &str, image: &[u8]) -> (PathBuf, Vec<CarvedHive>) {
+        let directory = temp_dir(name);
+        let path = directory.join("disk.dd");
+        fs::write(&path, image).unwrap();
+        let carved = carve(&path).unwrap();
+        (path, carved)
+    }
+
+    #[test]
+    fn carves_whole_partial_and_fragmented_hives() {
+        let (image, offsets) = image();
+        let (path, carved) = carve_image("carve", &image);
+        assert_eq!(
+            carved
+                .iter()
+                .map(|hive| (hive.offset, hive.kind))
+                .collect::<Vec<(u64, CarveKind)>>(),
+            vec![
+                (offsets[0], CarveKind::Whole),
+                (offsets[1], CarveKind::Fragment),
+                (offsets[2], CarveKind::Partial),
+            ]
+        );
+        for (hive, name) in carved.iter().zip(["Whole", "Fragment", "Partial"]) {
+            let hive = Hive::from_bytes(hive.data.to_owned()).unwrap();
+            let key = hive.open_path(&format!("Software\\{}", name)).unwrap();
+            assert_eq!(hive.value_offsets(&key).unwrap().len(), 1);
+        }
+        assert_eq!(root_name(&path, &carved[0]), "disk.dd @ 0x2200");
+        assert!(root_name(&path, &carved[2]).ends_with("(partial)"));
+        let again = carve_at(&path, offsets[1]).unwrap();
+        assert_eq!(again.data, carved[1].data);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn writes_carved_hives_that_carve_again() {
+        let (image, offsets) = image();
+        let (path, carved) = carve_image("carve_write", &image);
+        let directory = path.parent().unwrap().join("out");
+        let written = write(&path, &carved[1], &directory).unwrap();
+        assert_eq!(
+            written.file_name().unwrap().to_string_lossy(),
+            format!("disk.dd_0x{:X}.hiv", offsets[1])
+        );
+        let again = carve_at(&written, 0).unwrap();
+        assert_eq!(again.kind, CarveKind::Whole);
+        assert_eq!(again.data, carved[1].data);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn lists_keys_of_fragments_without_a_root() {
+        let mut fragment = test_hive("Lost");
+        let root = fragment.offset("") as usize + BASE_BLOCK_SIZE + 4;
+        fragment.bytes[root + 2..root + 4].copy_from_slice(&0x20u16.to_le_bytes());
+        let (path, carved) = carve_image("carve_rootless", &fragment.bytes[BASE_BLOCK_SIZE..]);
+        assert_eq!(carved.len(), 1);
+        let hive = Hive::from_bytes(carved[0].data.to_owned()).unwrap();
+        assert!(hive.root_key().is_err());
+        let mut source = MemorySource::default();
+        assert_eq!(load_fragment(&hive, "X", &mut source), 3);
+        let lost = source
+            .open_key("X", "(Orphaned keys)\\ROOT\\Software\\Lost")
+            .unwrap();
+        assert_eq!(source.enum_values(&lost).len(), 1);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn finds_nothing_in_junk() {
+        let mut junk = vec![0xAB; 0x3000];
+        junk[0x200..0x204].copy_from_slice(b"regf");
+        junk[0x400..0x404].copy_from_slice(b"hbin");
+        let (path, carved) = carve_image("carve_junk", &junk);
+        assert!(carved.is_empty());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
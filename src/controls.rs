@@ -2,7 +2,7 @@ use crate::{
     search_editor::SearchEditor, static_selection::StaticSelection, Focus, EVENT_POLL_TIMEOUT,
 };
 use crossterm::event::Event as CEvent;
-use crossterm::event::{self, KeyCode, KeyEventKind};
+use crossterm::event::{self, KeyCode, KeyEventKind, KeyModifiers};
 use parking_lot::RwLock;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -30,28 +30,30 @@ pub fn controls(
                                     SearchEditor::new_add(),
                                 ))))
                             }
-                            KeyCode::Char('e') => {
-                                if static_menu_selection.pane_selected.load(Ordering::SeqCst) == 1 {
-                                    let (search_terms_is_empty, selected_search_term_value) = {
-                                        let search_term_tracker_lock =
-                                            static_menu_selection.search_term_tracker.read();
-                                        (
-                                            search_term_tracker_lock.search_terms.is_empty(),
-                                            search_term_tracker_lock.get_value_at_current_index(),
-                                        )
-                                    };
-                                    if !search_terms_is_empty {
-                                        if let Some(selected_search_term_value) =
-                                            selected_search_term_value
-                                        {
-                                            *focus.write() = Focus::SearchMod(Arc::new(
-                                                RwLock::new(Some(SearchEditor::new_edit(
-                                                    selected_search_term_value,
-                                                ))),
-                                            ))
-                                        } else {
-                                            error!("Search terms pane was selected, search terms was not empty, yet somehow there wasn't a value selected.");
-                                        }
+                            KeyCode::Char('e')
+                                if static_menu_selection.pane_selected.load(Ordering::SeqCst)
+                                    == 1 =>
+                            {
+                                let (search_terms_is_empty, selected_search_term_value) = {
+                                    let search_term_tracker_lock =
+                                        static_menu_selection.search_term_tracker.read();
+                                    (
+                                        search_term_tracker_lock.search_terms.is_empty(),
+                                        search_term_tracker_lock.get_value_at_current_index(),
+                                    )
+                                };
+                                if !search_terms_is_empty {
+                                    if let Some((selected_search_term_value, options)) =
+                                        selected_search_term_value
+                                    {
+                                        *focus.write() = Focus::SearchMod(Arc::new(RwLock::new(
+                                            Some(SearchEditor::new_edit(
+                                                selected_search_term_value,
+                                                options,
+                                            )),
+                                        )))
+                                    } else {
+                                        error!("Search terms pane was selected, search terms was not empty, yet somehow there wasn't a value selected.");
                                     }
                                 }
                             }
@@ -106,11 +108,19 @@ pub fn controls(
                             KeyCode::Backspace => {
                                 search_editor.write().as_mut().unwrap().backspace()
                             }
+                            KeyCode::Char(ch) if key.modifiers.contains(KeyModifiers::ALT) => {
+                                search_editor.write().as_mut().unwrap().toggle_option(ch)
+                            }
                             KeyCode::Char(ch) => {
                                 search_editor.write().as_mut().unwrap().add_char(ch)
                             }
                             KeyCode::Esc => *focus.write() = Focus::Main,
                             KeyCode::Enter => {
+                                if let Some(search_editor) = search_editor.write().as_mut() {
+                                    if !search_editor.validate() {
+                                        continue; //stays open with the error shown
+                                    }
+                                }
                                 let mut focuslock = focus.write(); //this lock must be held until the end of this scope
                                 let mut search_editor_lock = search_editor.write(); //it is imperitive that nothing tries to read this lock after this write cycle, it should be safe
                                 let probably_search_editor = search_editor_lock.take();
@@ -122,11 +132,12 @@ pub fn controls(
                                         continue;
                                     }
                                 };
-                                let (editor_mode, state) = search_editor.resolve();
-                                static_menu_selection
-                                    .search_term_tracker
-                                    .write()
-                                    .update(editor_mode, state);
+                                let (editor_mode, state, options) = search_editor.resolve();
+                                static_menu_selection.search_term_tracker.write().update(
+                                    editor_mode,
+                                    state,
+                                    options,
+                                );
                             }
                             _ => {}
                         },
@@ -150,7 +161,6 @@ pub fn controls(
                     }
                 }
             }
-        } else {
         }
     }
 }
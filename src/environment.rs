@@ -0,0 +1,296 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::{
+    hive::Hive, hive_source::HiveSource, reg_data::RegData, reg_file,
+    registry_source::RegistrySource,
+};
+
+/// Variables that refer to other variables are expanded this many levels deep at most, so a
+/// variable that refers to itself can't loop forever.
+const MAX_DEPTH: usize = 8;
+
+const HIVE_ROOT: &str = "HIVE";
+
+/// Keys of a SOFTWARE hive that Windows derives variables from and the value each variable
+/// takes, the variables of a SYSTEM or NTUSER.DAT hive are every string value of their
+/// Environment key.
+const SOFTWARE_VARIABLES: [(&str, &str, &str); 8] = [
+    (
+        "Microsoft\\Windows NT\\CurrentVersion",
+        "SystemRoot",
+        "SystemRoot",
+    ),
+    (
+        "Microsoft\\Windows NT\\CurrentVersion",
+        "SystemRoot",
+        "windir",
+    ),
+    (
+        "Microsoft\\Windows\\CurrentVersion",
+        "ProgramFilesDir",
+        "ProgramFiles",
+    ),
+    (
+        "Microsoft\\Windows\\CurrentVersion",
+        "ProgramFilesDir (x86)",
+        "ProgramFiles(x86)",
+    ),
+    (
+        "Microsoft\\Windows\\CurrentVersion",
+        "ProgramW6432Dir",
+        "ProgramW6432",
+    ),
+    (
+        "Microsoft\\Windows\\CurrentVersion",
+        "CommonFilesDir",
+        "CommonProgramFiles",
+    ),
+    (
+        "Microsoft\\Windows NT\\CurrentVersion\\ProfileList",
+        "ProgramData",
+        "ProgramData",
+    ),
+    (
+        "Microsoft\\Windows NT\\CurrentVersion\\ProfileList",
+        "Public",
+        "PUBLIC",
+    ),
+];
+
+/// Environment variables REG_EXPAND_SZ data is expanded with, names are case insensitive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Environment {
+    variables: HashMap<String, String>,
+}
+
+impl Environment {
+    /// Reads a regf hive, SYSTEM, SOFTWARE or NTUSER.DAT, or else a file of `NAME=VALUE` lines
+    /// such as the output of `set`. Lines starting with `#` are skipped.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes =
+            fs::read(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        let mut environment = Self::default();
+        if bytes.starts_with(b"regf") {
+            let hive = Hive::open(path)?;
+            let mut hive_source = HiveSource::default();
+            let root = hive_source.mount(HIVE_ROOT.to_string(), hive);
+            environment.add_from_source(&hive_source, &root);
+        } else {
+            environment.add_from_text(&reg_file::decode_text(&bytes));
+        }
+        Ok(environment)
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.variables
+            .insert(name.to_uppercase(), value.to_string());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.variables
+            .get(&name.to_uppercase())
+            .map(|value| value.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.variables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty()
+    }
+
+    /// Variables from another profile replace ones of the same name.
+    pub fn extend(&mut self, other: Environment) {
+        self.variables.extend(other.variables);
+    }
+
+    pub fn add_from_text(&mut self, text: &str) {
+        for line in text.lines().map(|line| line.trim()) {
+            if line.starts_with('#') {
+                continue;
+            }
+            let line = line.strip_prefix("set ").unwrap_or(line);
+            if let Some((name, value)) = line.split_once('=') {
+                if !name.is_empty() {
+                    self.set(name.trim(), value);
+                }
+            }
+        }
+    }
+
+    /// Variables stored in the hive mounted at `root`, whichever of a SYSTEM, SOFTWARE or
+    /// NTUSER.DAT hive it is.
+    pub fn add_from_source<S: RegistrySource>(&mut self, source: &S, root: &str) {
+        let values_of = |key_path: &str| match source.open_key(root, key_path) {
+            Ok(key) => source
+                .enum_values(&key)
+                .into_iter()
+                .filter_map(|value_result| value_result.ok())
+                .map(|(name, value)| (name, RegData::from_value(&value)))
+                .collect::<Vec<(String, RegData)>>(),
+            Err(_) => Vec::new(),
+        };
+        let string_values_of = |key_path: &str| {
+            values_of(key_path)
+                .into_iter()
+                .filter_map(|(name, data)| match data {
+                    RegData::Sz(text) | RegData::ExpandSz(text) => Some((name, text)),
+                    _ => None,
+                })
+                .collect::<Vec<(String, String)>>()
+        };
+
+        let current_control_set = values_of("Select")
+            .into_iter()
+            .find_map(|(name, data)| match data {
+                RegData::Dword(current) if name.eq_ignore_ascii_case("Current") => Some(current),
+                _ => None,
+            })
+            .unwrap_or(1);
+        let system_environment = format!(
+            "ControlSet{:03}\\Control\\Session Manager\\Environment",
+            current_control_set
+        );
+        for key_path in [system_environment.as_str(), "Environment"] {
+            for (name, value) in string_values_of(key_path) {
+                self.set(&name, &value);
+            }
+        }
+        for (key_path, value_name, variable) in SOFTWARE_VARIABLES {
+            if let Some((_, value)) = string_values_of(key_path)
+                .into_iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(value_name))
+            {
+                self.set(variable, &value);
+            }
+        }
+        if self.get("SystemDrive").is_none() {
+            if let Some(system_drive) = self.get("SystemRoot").and_then(|root| root.get(..2)) {
+                let system_drive = system_drive.to_string();
+                self.set("SystemDrive", &system_drive);
+            }
+        }
+    }
+
+    /// Replaces each `%NAME%` with its value, variables that aren't set are left as they are.
+    pub fn expand(&self, text: &str) -> String {
+        self.expand_to_depth(text, MAX_DEPTH)
+    }
+
+    fn expand_to_depth(&self, text: &str, depth: usize) -> String {
+        let mut expanded = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('%') {
+            expanded.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            match after.find('%') {
+                Some(end) => match self.get(&after[..end]) {
+                    Some(value) if depth > 0 => {
+                        expanded.push_str(&self.expand_to_depth(value, depth - 1));
+                        rest = &after[end + 1..];
+                    }
+                    _ => {
+                        //the closing % may open the next variable
+                        expanded.push('%');
+                        expanded.push_str(&after[..end]);
+                        rest = &after[end..];
+                    }
+                },
+                None => {
+                    expanded.push_str(&rest[start..]);
+                    rest = "";
+                }
+            }
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        memory_source::MemorySource,
+        reg_file::to_utf16_bytes,
+        registry_source::{RegType, RegistryValue},
+    };
+
+    fn sz(vtype: RegType, text: &str) -> RegistryValue {
+        RegistryValue {
+            vtype,
+            bytes: to_utf16_bytes(text),
+        }
+    }
+
+    #[test]
+    fn expands_nested_and_leaves_unknown_variables() {
+        let mut environment = Environment::default();
+        environment.add_from_text(
+            "# exported with set\r\nSystemRoot=C:\\Windows\r\nset TEMP=%USERPROFILE%\\AppData\\Local\\Temp\r\nUSERPROFILE=C:\\Users\\bob\r\nLOOP=%LOOP%x\r\n",
+        );
+        assert_eq!(environment.len(), 4);
+        assert_eq!(
+            environment.expand("%systemroot%\\System32\\%Unknown%%temp%\\a.exe"),
+            "C:\\Windows\\System32\\%Unknown%C:\\Users\\bob\\AppData\\Local\\Temp\\a.exe"
+        );
+        assert_eq!(environment.expand("100% %"), "100% %");
+        assert_eq!(environment.expand("%LOOP%"), "%LOOP%xxxxxxxx");
+    }
+
+    #[test]
+    fn derives_variables_from_offline_hives() {
+        let mut source = MemorySource::default();
+        source.set_value(
+            "SYSTEM",
+            "Select",
+            "Current",
+            RegistryValue {
+                vtype: RegType::REG_DWORD,
+                bytes: 2u32.to_le_bytes().to_vec(),
+            },
+        );
+        for (control_set, windir) in [
+            ("ControlSet001", "D:\\Old"),
+            ("ControlSet002", "%SystemRoot%"),
+        ] {
+            source.set_value(
+                "SYSTEM",
+                &format!("{}\\Control\\Session Manager\\Environment", control_set),
+                "windir",
+                sz(RegType::REG_EXPAND_SZ, windir),
+            );
+        }
+        source.set_value(
+            "SOFTWARE",
+            "Microsoft\\Windows NT\\CurrentVersion",
+            "SystemRoot",
+            sz(RegType::REG_SZ, "E:\\WINDOWS"),
+        );
+        let mut environment = Environment::default();
+        environment.add_from_source(&source, "SYSTEM");
+        assert_eq!(environment.get("WinDir"), Some("%SystemRoot%"));
+        environment.add_from_source(&source, "SOFTWARE");
+        assert_eq!(
+            environment.expand("%SystemDrive%|%windir%"),
+            "E:|E:\\WINDOWS"
+        );
+
+        let directory = crate::test_hive::temp_dir("environment_load");
+        let path = directory.join("env.txt");
+        fs::write(
+            &path,
+            [
+                &[0xFF, 0xFE][..],
+                &to_utf16_bytes("APPDATA=C:\\Roaming\r\n"),
+            ]
+            .concat(),
+        )
+        .unwrap();
+        assert_eq!(
+            Environment::load(&path).unwrap().get("appdata"),
+            Some("C:\\Roaming")
+        );
+    }
+}
@@ -0,0 +1,811 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use crate::transaction_log::{find_logs, replay, LogReplay, TransactionLog};
+
+pub const BASE_BLOCK_SIZE: usize = 0x1000;
+const BIG_DATA_SEGMENT_SIZE: usize = 16344;
+
+pub const KEY_HIVE_ENTRY: u16 = 0x0004;
+pub const KEY_COMP_NAME: u16 = 0x0020;
+const VALUE_COMP_NAME: u16 = 0x0001;
+
+pub fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(read_u32(data, offset)? as u64 | (read_u32(data, offset + 4)? as u64) << 32)
+}
+
+/// Key and value names are either Latin-1 ("compressed") or UTF-16LE.
+pub fn decode_name(bytes: &[u8], compressed: bool) -> String {
+    if compressed {
+        bytes.iter().map(|&byte| byte as char).collect()
+    } else {
+        let units = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect::<Vec<u16>>();
+        String::from_utf16_lossy(&units)
+    }
+}
+
+/// XOR of the first 508 bytes of the base block as u32s, 0 and 0xFFFFFFFF are substituted.
+pub fn base_block_checksum(base_block: &[u8]) -> u32 {
+    let checksum = (0..0x1FC).step_by(4).fold(0u32, |checksum, offset| {
+        checksum ^ read_u32(base_block, offset).unwrap_or(0)
+    });
+    match checksum {
+        0 => 1,
+        0xFFFFFFFF => 0xFFFFFFFE,
+        checksum => checksum,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BaseBlock {
+    pub primary_sequence: u32,
+    pub secondary_sequence: u32,
+    pub last_written: u64,
+    pub major_version: u32,
+    pub minor_version: u32,
+    pub root_cell: u32,
+    pub hive_bins_size: u32,
+    pub file_name: String,
+    pub checksum: u32,
+}
+
+impl BaseBlock {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < BASE_BLOCK_SIZE {
+            return Err(format!(
+                "File is too small to be a hive ({} bytes)",
+                data.len()
+            ));
+        }
+        if &data[0..4] != b"regf" {
+            return Err("Missing regf signature".into());
+        }
+        let field = |offset| read_u32(data, offset).unwrap_or(0);
+        Ok(Self {
+            primary_sequence: field(0x04),
+            secondary_sequence: field(0x08),
+            last_written: read_u64(data, 0x0C).unwrap_or(0),
+            major_version: field(0x14),
+            minor_version: field(0x18),
+            root_cell: field(0x24),
+            hive_bins_size: field(0x28),
+            file_name: decode_name(&data[0x30..0x70], false)
+                .trim_end_matches('\0')
+                .to_string(),
+            checksum: field(0x1FC),
+        })
+    }
+
+    /// A write was interrupted, the newest data is in the transaction logs.
+    pub fn is_dirty(&self) -> bool {
+        self.primary_sequence != self.secondary_sequence
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyNode {
+    pub offset: u32,
+    pub flags: u16,
+    pub name: String,
+    pub last_write_time: u64,
+    pub parent: u32,
+    pub subkey_count: u32,
+    pub subkey_list: u32,
+    pub value_count: u32,
+    pub value_list: u32,
+    pub security: u32,
+    pub class_name: u32,
+    pub class_name_length: u16,
+    pub max_value_data_size: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ValueNode {
+    pub offset: u32,
+    pub name: String,
+    pub data_size: u32,
+    pub data_offset: u32,
+    pub data_type: u32,
+}
+
+impl ValueNode {
+    /// Data of 4 bytes or less is stored in the offset field itself.
+    pub fn is_resident(&self) -> bool {
+        self.data_size & 0x80000000 != 0
+    }
+
+    pub fn data_len(&self) -> usize {
+        (self.data_size & 0x7FFFFFFF) as usize
+    }
+}
+
+/// Part of a hive that couldn't be read, `offset` is relative to the first hbin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellProblem {
+    pub offset: u32,
+    pub message: String,
+}
+
+impl CellProblem {
+    pub fn new(offset: u32, message: String) -> Self {
+        Self { offset, message }
+    }
+}
+
+/// A regf hive file held in memory. Cell offsets are relative to the first hbin.
+pub struct Hive {
+    data: Vec<u8>,
+    pub base_block: BaseBlock,
+    pub log_replay: Option<LogReplay>,
+    log_only_keys: HashSet<u32>,
+}
+
+impl Hive {
+    /// Loads a hive, replaying its .LOG1/.LOG2 files first if it's dirty.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let hive = Self::from_bytes(data)?;
+        if !hive.base_block.is_dirty() {
+            return Ok(hive);
+        }
+        let mut warnings = Vec::new();
+        let logs = find_logs(path)
+            .iter()
+            .filter_map(|log_path| match TransactionLog::open(log_path) {
+                Ok(log) => Some(log),
+                Err(err) => {
+                    warnings.push(err);
+                    None
+                }
+            })
+            .collect::<Vec<TransactionLog>>();
+        let mut hive = hive.replay_logs(logs)?;
+        if let Some(log_replay) = hive.log_replay.as_mut() {
+            log_replay.warnings.extend(warnings);
+        }
+        Ok(hive)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, String> {
+        let base_block = BaseBlock::parse(&data)?;
+        Ok(Self {
+            data,
+            base_block,
+            log_replay: None,
+            log_only_keys: HashSet::new(),
+        })
+    }
+
+    /// The hive as it would be written to disk.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Applies the logs and records keys that don't exist in the hive without them.
+    pub fn replay_logs(self, logs: Vec<TransactionLog>) -> Result<Self, String> {
+        let mut data = self.data.to_owned();
+        let log_replay = replay(&mut data, &self.base_block, logs);
+        let mut replayed = Self::from_bytes(data)?;
+        for (offset, size) in replayed.cells() {
+            if size >= 0
+                || !log_replay
+                    .dirty_ranges
+                    .iter()
+                    .any(|(start, end)| (*start..*end).contains(&offset))
+            {
+                continue;
+            }
+            let key = match replayed.key_node(offset) {
+                Ok(key) => key,
+                Err(_) => continue,
+            };
+            if let Ok(path) = replayed.key_path(&key) {
+                if self.open_path(&path).is_err() {
+                    replayed.log_only_keys.insert(offset);
+                }
+            }
+        }
+        replayed.log_replay = Some(log_replay);
+        Ok(replayed)
+    }
+
+    pub fn is_log_only(&self, key: &KeyNode) -> bool {
+        self.log_only_keys.contains(&key.offset)
+    }
+
+    pub fn log_only_key_paths(&self) -> Vec<String> {
+        let mut paths = self
+            .log_only_keys
+            .iter()
+            .filter_map(|offset| self.key_node(*offset).ok())
+            .filter_map(|key| self.key_path(&key).ok())
+            .collect::<Vec<String>>();
+        paths.sort();
+        paths
+    }
+
+    /// Every cell in every hbin as (offset, size), a negative size means the cell is allocated.
+    pub fn cells(&self) -> Vec<(u32, i32)> {
+        self.scan_cells().0
+    }
+
+    /// Every cell that could be walked to, along with the bins and cells that had to be skipped.
+    /// A cell with a bad size ends its bin, a bad bin header is skipped up to the next bin header
+    /// within `hive_bins_size`. Past that the walk ends at the first thing that isn't a bin.
+    pub fn scan_cells(&self) -> (Vec<(u32, i32)>, Vec<CellProblem>) {
+        let mut cells = Vec::new();
+        let mut problems = Vec::new();
+        let bins_end = BASE_BLOCK_SIZE + self.base_block.hive_bins_size as usize;
+        let mut hbin = BASE_BLOCK_SIZE;
+        while hbin + 0x20 <= self.data.len() {
+            let hbin_size = read_u32(&self.data, hbin + 8).unwrap_or(0) as usize;
+            if self.data.get(hbin..hbin + 4) != Some(b"hbin")
+                || hbin_size < 0x1000
+                || !hbin_size.is_multiple_of(0x1000)
+                || hbin + hbin_size > self.data.len()
+            {
+                if hbin >= bins_end {
+                    break;
+                }
+                problems.push(CellProblem::new(
+                    (hbin - BASE_BLOCK_SIZE) as u32,
+                    "Invalid hive bin header".into(),
+                ));
+                match (hbin + 0x1000..bins_end.min(self.data.len()))
+                    .step_by(0x1000)
+                    .find(|next| self.data.get(*next..*next + 4) == Some(b"hbin"))
+                {
+                    Some(next) => {
+                        hbin = next;
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+            let mut cell = hbin + 0x20;
+            while cell + 4 <= hbin + hbin_size {
+                let size = read_u32(&self.data, cell).unwrap_or(0) as i32;
+                let len = size.unsigned_abs() as usize;
+                if len < 8 || !len.is_multiple_of(8) || cell + len > hbin + hbin_size {
+                    problems.push(CellProblem::new(
+                        (cell - BASE_BLOCK_SIZE) as u32,
+                        format!(
+                            "Cell has an invalid size ({}), rest of its bin skipped",
+                            size
+                        ),
+                    ));
+                    break;
+                }
+                cells.push(((cell - BASE_BLOCK_SIZE) as u32, size));
+                cell += len;
+            }
+            hbin += hbin_size;
+        }
+        if self.data.len() < bins_end {
+            problems.push(CellProblem::new(
+                (self.data.len().max(BASE_BLOCK_SIZE) - BASE_BLOCK_SIZE) as u32,
+                format!(
+                    "Hive bins end {} bytes short of the size in the base block",
+                    bins_end - self.data.len()
+                ),
+            ));
+        }
+        (cells, problems)
+    }
+
+    /// The base block checksum matches the one stored in it.
+    pub fn checksum_is_valid(&self) -> bool {
+        base_block_checksum(&self.data) == self.base_block.checksum
+    }
+
+    pub fn root_key(&self) -> Result<KeyNode, String> {
+        self.key_node(self.base_block.root_cell)
+    }
+
+    /// Returns the cell data without its size field.
+    pub fn cell(&self, offset: u32) -> Result<&[u8], String> {
+        self.cell_with(offset, false)
+    }
+
+    /// Like `cell` but also accepts unallocated cells, for recovering deleted data.
+    pub fn raw_cell(&self, offset: u32) -> Result<&[u8], String> {
+        self.cell_with(offset, true)
+    }
+
+    fn cell_with(&self, offset: u32, allow_free: bool) -> Result<&[u8], String> {
+        let start = BASE_BLOCK_SIZE + offset as usize;
+        let size = match read_u32(&self.data, start) {
+            Some(size) => size as i32,
+            None => return Err(format!("Cell 0x{:X} is out of bounds", offset)),
+        };
+        if size >= 0 && !allow_free {
+            return Err(format!("Cell 0x{:X} is not allocated", offset));
+        }
+        let end = start + size.unsigned_abs() as usize;
+        if size.unsigned_abs() < 8 || end > self.data.len() {
+            return Err(format!(
+                "Cell 0x{:X} has an invalid size ({})",
+                offset, size
+            ));
+        }
+        Ok(&self.data[start + 4..end])
+    }
+
+    pub fn key_node(&self, offset: u32) -> Result<KeyNode, String> {
+        Self::parse_key_node(offset, self.cell(offset)?)
+    }
+
+    pub fn deleted_key_node(&self, offset: u32) -> Result<KeyNode, String> {
+        Self::parse_key_node(offset, self.raw_cell(offset)?)
+    }
+
+    fn parse_key_node(offset: u32, cell: &[u8]) -> Result<KeyNode, String> {
+        if cell.len() < 0x4C || &cell[0..2] != b"nk" {
+            return Err(format!("Cell 0x{:X} is not a key node", offset));
+        }
+        let field = |offset| read_u32(cell, offset).unwrap_or(0);
+        let flags = read_u16(cell, 0x02).unwrap_or(0);
+        let name_length = read_u16(cell, 0x48).unwrap_or(0) as usize;
+        let name = match cell.get(0x4C..0x4C + name_length) {
+            Some(name) => decode_name(name, flags & KEY_COMP_NAME != 0),
+            None => return Err(format!("Key node 0x{:X} name is truncated", offset)),
+        };
+        Ok(KeyNode {
+            offset,
+            flags,
+            name,
+            last_write_time: read_u64(cell, 0x04).unwrap_or(0),
+            parent: field(0x10),
+            subkey_count: field(0x14),
+            subkey_list: field(0x1C),
+            value_count: field(0x24),
+            value_list: field(0x28),
+            security: field(0x2C),
+            class_name: field(0x30),
+            class_name_length: read_u16(cell, 0x4A).unwrap_or(0),
+            max_value_data_size: field(0x40),
+        })
+    }
+
+    pub fn value_node(&self, offset: u32) -> Result<ValueNode, String> {
+        Self::parse_value_node(offset, self.cell(offset)?)
+    }
+
+    pub fn deleted_value_node(&self, offset: u32) -> Result<ValueNode, String> {
+        Self::parse_value_node(offset, self.raw_cell(offset)?)
+    }
+
+    fn parse_value_node(offset: u32, cell: &[u8]) -> Result<ValueNode, String> {
+        if cell.len() < 0x14 || &cell[0..2] != b"vk" {
+            return Err(format!("Cell 0x{:X} is not a value node", offset));
+        }
+        let name_length = read_u16(cell, 0x02).unwrap_or(0) as usize;
+        let flags = read_u16(cell, 0x10).unwrap_or(0);
+        let name = match cell.get(0x14..0x14 + name_length) {
+            Some(name) => decode_name(name, flags & VALUE_COMP_NAME != 0),
+            None => return Err(format!("Value node 0x{:X} name is truncated", offset)),
+        };
+        Ok(ValueNode {
+            offset,
+            name,
+            data_size: read_u32(cell, 0x04).unwrap_or(0),
+            data_offset: read_u32(cell, 0x08).unwrap_or(0),
+            data_type: read_u32(cell, 0x0C).unwrap_or(0),
+        })
+    }
+
+    pub fn class_name(&self, key: &KeyNode) -> Result<Option<String>, String> {
+        if key.class_name == 0xFFFFFFFF || key.class_name_length == 0 {
+            return Ok(None);
+        }
+        let cell = self.cell(key.class_name)?;
+        match cell.get(..key.class_name_length as usize) {
+            Some(bytes) => Ok(Some(decode_name(bytes, false))),
+            None => Err(format!("Class name of key 0x{:X} is truncated", key.offset)),
+        }
+    }
+
+    /// The self-relative security descriptor in the key's sk cell, which keys with the same
+    /// permissions share.
+    pub fn security_descriptor(&self, key: &KeyNode) -> Result<Option<Vec<u8>>, String> {
+        if key.security == 0xFFFFFFFF {
+            return Ok(None);
+        }
+        let cell = self.cell(key.security)?;
+        if cell.len() < 0x14 || &cell[0..2] != b"sk" {
+            return Err(format!("Cell 0x{:X} is not a security cell", key.security));
+        }
+        let size = read_u32(cell, 0x10).unwrap_or(0) as usize;
+        match cell.get(0x14..0x14 + size) {
+            Some(descriptor) => Ok(Some(descriptor.to_vec())),
+            None => Err(format!(
+                "Security descriptor of key 0x{:X} is truncated",
+                key.offset
+            )),
+        }
+    }
+
+    /// Flattens li/lf/lh/ri lists into key node offsets, in stored (sorted) order.
+    /// Parts of an ri list that can't be read are skipped, use `subkeys_checked` to see them.
+    pub fn subkey_offsets(&self, key: &KeyNode) -> Result<Vec<u32>, String> {
+        let (offsets, problems) = self.subkeys_checked(key);
+        match problems.into_iter().next() {
+            Some(problem) if offsets.is_empty() => Err(problem.message),
+            _ => Ok(offsets),
+        }
+    }
+
+    /// Every subkey offset that could be read, along with the lists that couldn't.
+    pub fn subkeys_checked(&self, key: &KeyNode) -> (Vec<u32>, Vec<CellProblem>) {
+        let mut offsets = Vec::new();
+        let mut problems = Vec::new();
+        if key.subkey_count != 0 && key.subkey_list != 0xFFFFFFFF {
+            self.collect_subkey_list(key.subkey_list, &mut offsets, &mut problems, 0);
+        }
+        (offsets, problems)
+    }
+
+    fn collect_subkey_list(
+        &self,
+        list_offset: u32,
+        offsets: &mut Vec<u32>,
+        problems: &mut Vec<CellProblem>,
+        depth: usize,
+    ) {
+        let cell = match self.cell(list_offset) {
+            Ok(cell) => cell,
+            Err(err) => {
+                problems.push(CellProblem::new(list_offset, err));
+                return;
+            }
+        };
+        let count = read_u16(cell, 0x02).unwrap_or(0) as usize;
+        let (stride, nested) = match cell.get(0..2) {
+            Some(b"li") => (4, false),
+            Some(b"lf") | Some(b"lh") => (8, false),
+            Some(b"ri") if depth == 0 => (4, true),
+            _ => {
+                problems.push(CellProblem::new(
+                    list_offset,
+                    format!("Cell 0x{:X} is not a subkey list", list_offset),
+                ));
+                return;
+            }
+        };
+        for index in 0..count {
+            let offset = match read_u32(cell, 0x04 + index * stride) {
+                Some(offset) => offset,
+                None => {
+                    problems.push(CellProblem::new(
+                        list_offset,
+                        format!("Subkey list 0x{:X} is truncated", list_offset),
+                    ));
+                    return;
+                }
+            };
+            if nested {
+                self.collect_subkey_list(offset, offsets, problems, depth + 1);
+            } else {
+                offsets.push(offset);
+            }
+        }
+    }
+
+    pub fn value_offsets(&self, key: &KeyNode) -> Result<Vec<u32>, String> {
+        self.value_offsets_with(key, false)
+    }
+
+    pub fn deleted_value_offsets(&self, key: &KeyNode) -> Result<Vec<u32>, String> {
+        self.value_offsets_with(key, true)
+    }
+
+    fn value_offsets_with(&self, key: &KeyNode, allow_free: bool) -> Result<Vec<u32>, String> {
+        let (offsets, problem) = self.value_list(key, allow_free);
+        match problem {
+            Some(problem) => Err(problem.message),
+            None => Ok(offsets),
+        }
+    }
+
+    /// Every value offset that could be read, along with why the rest of the list couldn't.
+    pub fn values_checked(&self, key: &KeyNode) -> (Vec<u32>, Option<CellProblem>) {
+        self.value_list(key, false)
+    }
+
+    fn value_list(&self, key: &KeyNode, allow_free: bool) -> (Vec<u32>, Option<CellProblem>) {
+        if key.value_count == 0 || key.value_list == 0xFFFFFFFF {
+            return (Vec::new(), None);
+        }
+        let cell = match self.cell_with(key.value_list, allow_free) {
+            Ok(cell) => cell,
+            Err(err) => return (Vec::new(), Some(CellProblem::new(key.value_list, err))),
+        };
+        let offsets = (0..key.value_count as usize)
+            .map_while(|index| read_u32(cell, index * 4))
+            .collect::<Vec<u32>>();
+        let problem = match offsets.len() == key.value_count as usize {
+            true => None,
+            false => Some(CellProblem::new(
+                key.value_list,
+                format!("Value list 0x{:X} is truncated", key.value_list),
+            )),
+        };
+        (offsets, problem)
+    }
+
+    pub fn value_data(&self, value: &ValueNode) -> Result<Vec<u8>, String> {
+        self.value_data_with(value, false)
+    }
+
+    pub fn deleted_value_data(&self, value: &ValueNode) -> Result<Vec<u8>, String> {
+        self.value_data_with(value, true)
+    }
+
+    fn value_data_with(&self, value: &ValueNode, allow_free: bool) -> Result<Vec<u8>, String> {
+        let len = value.data_len();
+        if value.is_resident() {
+            return Ok(value.data_offset.to_le_bytes()[..len.min(4)].to_vec());
+        }
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let cell = self.cell_with(value.data_offset, allow_free)?;
+        if len > BIG_DATA_SEGMENT_SIZE
+            && self.base_block.minor_version >= 4
+            && cell.get(0..2) == Some(b"db")
+        {
+            return self.big_data(value.data_offset, cell, len, allow_free);
+        }
+        match cell.get(..len) {
+            Some(bytes) => Ok(bytes.to_vec()),
+            None => Err(format!("Value data of 0x{:X} is truncated", value.offset)),
+        }
+    }
+
+    fn big_data(
+        &self,
+        offset: u32,
+        cell: &[u8],
+        len: usize,
+        allow_free: bool,
+    ) -> Result<Vec<u8>, String> {
+        let segments = read_u16(cell, 0x02).unwrap_or(0) as usize;
+        let segment_list = read_u32(cell, 0x04).unwrap_or(0xFFFFFFFF);
+        let list = self.cell_with(segment_list, allow_free)?;
+        let mut data = Vec::with_capacity(len);
+        for index in 0..segments {
+            let segment_offset = match read_u32(list, index * 4) {
+                Some(segment_offset) => segment_offset,
+                None => return Err(format!("Big data list of 0x{:X} is truncated", offset)),
+            };
+            let segment = self.cell_with(segment_offset, allow_free)?;
+            let remaining = len - data.len();
+            data.extend_from_slice(
+                &segment[..segment.len().min(BIG_DATA_SEGMENT_SIZE).min(remaining)],
+            );
+            if data.len() == len {
+                break;
+            }
+        }
+        if data.len() != len {
+            return Err(format!("Big data of 0x{:X} is truncated", offset));
+        }
+        Ok(data)
+    }
+
+    /// Lists are sorted by uppercased name so a binary search is tried first, falling back to a
+    /// linear scan for hives that weren't written in order.
+    pub fn find_subkey(&self, key: &KeyNode, name: &str) -> Result<Option<KeyNode>, String> {
+        let offsets = self.subkey_offsets(key)?;
+        let target = name.to_uppercase();
+        let (mut low, mut high) = (0, offsets.len());
+        while low < high {
+            let middle = (low + high) / 2;
+            let node = match self.key_node(offsets[middle]) {
+                Ok(node) => node,
+                Err(_) => break,
+            };
+            match node.name.to_uppercase().cmp(&target) {
+                std::cmp::Ordering::Equal => return Ok(Some(node)),
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+            }
+        }
+        for offset in offsets {
+            if let Ok(node) = self.key_node(offset) {
+                if node.name.to_uppercase() == target {
+                    return Ok(Some(node));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Path of the key below the hive root, following parent links.
+    pub fn key_path(&self, key: &KeyNode) -> Result<String, String> {
+        let mut components = Vec::new();
+        let mut node = key.to_owned();
+        while node.flags & KEY_HIVE_ENTRY == 0 {
+            if components.len() > 512 {
+                return Err(format!("Key 0x{:X} has a parent loop", key.offset));
+            }
+            let parent = self.key_node(node.parent)?;
+            components.push(node.name);
+            node = parent;
+        }
+        components.reverse();
+        Ok(components.join("\\"))
+    }
+
+    pub fn open_path(&self, path: &str) -> Result<KeyNode, String> {
+        let mut key = self.root_key()?;
+        for component in path.split('\\').filter(|component| !component.is_empty()) {
+            key = match self.find_subkey(&key, component)? {
+                Some(subkey) => subkey,
+                None => return Err("The system cannot find the file specified.".into()),
+            };
+        }
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_hive::{build, build_with_version, sz, TestKey, DEFAULT_TIME};
+
+    fn tree() -> TestKey {
+        let mut alpha = TestKey::new("Alpha");
+        for index in 0..30 {
+            alpha = alpha.key(TestKey::new(&index.to_string()));
+        }
+        TestKey::new("ROOT")
+            .key(
+                TestKey::new("Software")
+                    .key(
+                        TestKey::new("Run")
+                            .value("Evil", 1, sz("C:\\Temp\\evil.exe"))
+                            .value("Näme", 2, sz("%SystemRoot%\\x.dll")),
+                    )
+                    .key(
+                        TestKey::new("Zeta")
+                            .class("MyClass")
+                            .value("", 4, 4096u32.to_le_bytes().to_vec())
+                            .value("Big", 3, (0..=255u8).cycle().take(25600).collect()),
+                    ),
+            )
+            .key(alpha)
+    }
+
+    fn hive() -> Hive {
+        Hive::from_bytes(build(&tree()).bytes).unwrap()
+    }
+
+    fn values(hive: &Hive, key: &KeyNode) -> Vec<(String, u32, Vec<u8>)> {
+        hive.value_offsets(key)
+            .unwrap()
+            .into_iter()
+            .map(|offset| {
+                let value = hive.value_node(offset).unwrap();
+                let data = hive.value_data(&value).unwrap();
+                (value.name, value.data_type, data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_the_base_block() {
+        let hive = hive();
+        assert_eq!(hive.base_block.file_name, "test");
+        assert_eq!(hive.base_block.minor_version, 5);
+        assert!(!hive.base_block.is_dirty());
+        assert_eq!(hive.root_key().unwrap().name, "ROOT");
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_hives() {
+        assert!(Hive::from_bytes(vec![0; 100]).is_err());
+        assert!(Hive::from_bytes(vec![0; BASE_BLOCK_SIZE]).is_err());
+    }
+
+    #[test]
+    fn opens_paths_case_insensitively() {
+        let hive = hive();
+        let key = hive.open_path("SOFTWARE\\run").unwrap();
+        assert_eq!(key.name, "Run");
+        assert_eq!(hive.key_path(&key).unwrap(), "Software\\Run");
+        assert_eq!(key.last_write_time, DEFAULT_TIME);
+        assert!(hive.open_path("Software\\Missing").is_err());
+        assert_eq!(hive.open_path("").unwrap().name, "ROOT");
+    }
+
+    #[test]
+    fn finds_every_subkey_of_a_large_list() {
+        let hive = hive();
+        let alpha = hive.open_path("Alpha").unwrap();
+        assert_eq!(alpha.subkey_count, 30);
+        assert_eq!(hive.subkey_offsets(&alpha).unwrap().len(), 30);
+        for index in 0..30 {
+            let name = index.to_string();
+            assert_eq!(hive.find_subkey(&alpha, &name).unwrap().unwrap().name, name);
+        }
+        assert!(hive.find_subkey(&alpha, "30").unwrap().is_none());
+    }
+
+    #[test]
+    fn reads_names_and_data() {
+        let hive = hive();
+        let run = hive.open_path("Software\\Run").unwrap();
+        assert_eq!(
+            values(&hive, &run),
+            vec![
+                ("Evil".to_string(), 1, sz("C:\\Temp\\evil.exe")),
+                ("Näme".to_string(), 2, sz("%SystemRoot%\\x.dll")),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_resident_and_big_data() {
+        for minor_version in [3, 5] {
+            let hive = Hive::from_bytes(build_with_version(&tree(), minor_version).bytes).unwrap();
+            let zeta = hive.open_path("Software\\Zeta").unwrap();
+            let values = values(&hive, &zeta);
+            assert_eq!(values[0], (String::new(), 4, vec![0, 0x10, 0, 0]));
+            assert_eq!(values[1].2.len(), 25600);
+            assert!(values[1]
+                .2
+                .iter()
+                .enumerate()
+                .all(|(index, byte)| *byte == index as u8));
+            assert_eq!(zeta.max_value_data_size, 25600);
+        }
+    }
+
+    #[test]
+    fn reads_class_names() {
+        let hive = hive();
+        let zeta = hive.open_path("Software\\Zeta").unwrap();
+        assert_eq!(hive.class_name(&zeta).unwrap().as_deref(), Some("MyClass"));
+        let run = hive.open_path("Software\\Run").unwrap();
+        assert_eq!(hive.class_name(&run).unwrap(), None);
+    }
+
+    #[test]
+    fn reports_cells_that_are_not_what_they_should_be() {
+        let hive = hive();
+        let run = hive.open_path("Software\\Run").unwrap();
+        assert!(hive.value_node(run.offset).is_err());
+        assert!(hive.key_node(run.value_list).is_err());
+        assert!(hive.key_node(0x7FFFFFF0).is_err());
+    }
+
+    #[test]
+    fn lists_every_cell() {
+        let hive = hive();
+        let cells = hive.cells();
+        assert!(cells
+            .iter()
+            .any(|(offset, size)| *offset == hive.base_block.root_cell && *size < 0));
+        //the padding at the end of the hbin is a free cell
+        assert!(cells.last().unwrap().1 > 0);
+    }
+
+    #[test]
+    fn decodes_names() {
+        assert_eq!(decode_name(b"caf\xE9", true), "café");
+        assert_eq!(decode_name(&[0x3A, 0x04, 0x30, 0x04], false), "ка");
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt,
+};
+
+use crate::hive::{CellProblem, Hive, KeyNode};
+
+/// A cell that couldn't be read and the key it belongs to, if the walk got that far.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadCell {
+    pub offset: u32,
+    pub key_path: Option<String>,
+    pub problem: String,
+}
+
+/// What checking a whole hive found. Keys and values are counted as recovered when they could
+/// be read despite problems elsewhere and unrecovered when they couldn't.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    pub checksum_valid: bool,
+    pub bad_cells: Vec<BadCell>,
+    pub keys_recovered: usize,
+    pub keys_unrecovered: usize,
+    pub values_recovered: usize,
+    pub values_unrecovered: usize,
+}
+
+impl IntegrityReport {
+    pub fn is_clean(&self) -> bool {
+        self.checksum_valid && self.bad_cells.is_empty()
+    }
+
+    /// Keys with a subkey, value or list that couldn't be read, sorted.
+    pub fn affected_key_paths(&self) -> Vec<String> {
+        self.bad_cells
+            .iter()
+            .filter_map(|bad_cell| bad_cell.key_path.to_owned())
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect()
+    }
+
+    fn bad_cell(&mut self, problem: CellProblem, key_path: Option<&str>) {
+        self.bad_cells.push(BadCell {
+            offset: problem.offset,
+            key_path: key_path.map(|key_path| key_path.to_string()),
+            problem: problem.message,
+        });
+    }
+}
+
+impl fmt::Display for IntegrityReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.checksum_valid {
+            write!(f, "bad base block checksum, ")?;
+        }
+        write!(
+            f,
+            "{} bad cells in {} keys, {} of {} keys and {} of {} values read",
+            self.bad_cells.len(),
+            self.affected_key_paths().len(),
+            self.keys_recovered,
+            self.keys_recovered + self.keys_unrecovered,
+            self.values_recovered,
+            self.values_recovered + self.values_unrecovered,
+        )
+    }
+}
+
+fn child_path(path: &str, name: &str) -> String {
+    match path.is_empty() {
+        true => name.to_string(),
+        false => format!("{}\\{}", path, name),
+    }
+}
+
+/// Validates the base block checksum and every hive bin and cell, then walks the key tree from
+/// the root reading every subkey, value and class name, skipping whatever can't be read.
+pub fn check(hive: &Hive) -> IntegrityReport {
+    let mut report = IntegrityReport {
+        checksum_valid: hive.checksum_is_valid(),
+        ..Default::default()
+    };
+    for problem in hive.scan_cells().1 {
+        report.bad_cell(problem, None);
+    }
+
+    let root = match hive.root_key() {
+        Ok(root) => root,
+        Err(err) => {
+            report.keys_unrecovered += 1;
+            report.bad_cell(CellProblem::new(hive.base_block.root_cell, err), None);
+            return report;
+        }
+    };
+    let mut visited = HashSet::new();
+    let mut stack: Vec<(String, KeyNode)> = vec![(String::new(), root)];
+    while let Some((path, key)) = stack.pop() {
+        if !visited.insert(key.offset) {
+            report.bad_cell(
+                CellProblem::new(key.offset, "Key is listed more than once".into()),
+                Some(&path),
+            );
+            continue;
+        }
+        report.keys_recovered += 1;
+        if let Err(err) = hive.class_name(&key) {
+            report.bad_cell(CellProblem::new(key.class_name, err), Some(&path));
+        }
+
+        let (value_offsets, problem) = hive.values_checked(&key);
+        if let Some(problem) = problem {
+            report.values_unrecovered += key.value_count as usize - value_offsets.len();
+            report.bad_cell(problem, Some(&path));
+        }
+        for offset in value_offsets {
+            match hive
+                .value_node(offset)
+                .and_then(|value| hive.value_data(&value))
+            {
+                Ok(_) => report.values_recovered += 1,
+                Err(err) => {
+                    report.values_unrecovered += 1;
+                    report.bad_cell(CellProblem::new(offset, err), Some(&path));
+                }
+            }
+        }
+
+        let (subkey_offsets, problems) = hive.subkeys_checked(&key);
+        if !problems.is_empty() {
+            report.keys_unrecovered +=
+                (key.subkey_count as usize).saturating_sub(subkey_offsets.len());
+        }
+        for problem in problems {
+            report.bad_cell(problem, Some(&path));
+        }
+        for offset in subkey_offsets {
+            match hive.key_node(offset) {
+                Ok(subkey) => stack.push((child_path(&path, &subkey.name), subkey)),
+                Err(err) => {
+                    report.keys_unrecovered += 1;
+                    report.bad_cell(CellProblem::new(offset, err), Some(&path));
+                }
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hive::BASE_BLOCK_SIZE,
+        test_hive::{build, sz, TestHive, TestKey},
+    };
+
+    fn test_hive() -> TestHive {
+        build(
+            &TestKey::new("ROOT").key(
+                TestKey::new("Software")
+                    .key(
+                        TestKey::new("Run")
+                            .value("Evil", 1, sz("C:\\Temp\\evil.exe"))
+                            .value("Other", 1, sz("C:\\Temp\\other.exe")),
+                    )
+                    .key(TestKey::new("Broken").key(TestKey::new("Lost")))
+                    .key(TestKey::new("Kept")),
+            ),
+        )
+    }
+
+    /// Overwrites the signature of the cell at `offset`.
+    fn corrupt(hive: &mut TestHive, offset: u32) {
+        let start = BASE_BLOCK_SIZE + offset as usize + 4;
+        hive.bytes[start..start + 2].copy_from_slice(b"XX");
+    }
+
+    fn value_offset(hive: &Hive, path: &str, index: usize) -> u32 {
+        let key = hive.open_path(path).unwrap();
+        hive.value_offsets(&key).unwrap()[index]
+    }
+
+    #[test]
+    fn a_sound_hive_is_clean() {
+        let report = check(&Hive::from_bytes(test_hive().bytes).unwrap());
+        assert!(report.is_clean(), "{:?}", report);
+        assert_eq!((report.keys_recovered, report.keys_unrecovered), (6, 0));
+        assert_eq!((report.values_recovered, report.values_unrecovered), (2, 0));
+    }
+
+    #[test]
+    fn skips_bad_cells_and_reports_where_they_were() {
+        let mut test_hive = test_hive();
+        let lost = test_hive.offset("Software\\Broken\\Lost");
+        corrupt(&mut test_hive, lost);
+        let value = value_offset(
+            &Hive::from_bytes(test_hive.bytes.to_owned()).unwrap(),
+            "Software\\Run",
+            0,
+        );
+        corrupt(&mut test_hive, value);
+        test_hive.bytes[0x30] = b'x'; //breaks the checksum
+        let hive = Hive::from_bytes(test_hive.bytes).unwrap();
+        let report = check(&hive);
+        assert!(!report.checksum_valid);
+        assert_eq!((report.keys_recovered, report.keys_unrecovered), (5, 1));
+        assert_eq!((report.values_recovered, report.values_unrecovered), (1, 1));
+        assert_eq!(
+            report.affected_key_paths(),
+            vec!["Software\\Broken", "Software\\Run"]
+        );
+        let offsets = report
+            .bad_cells
+            .iter()
+            .map(|bad_cell| bad_cell.offset)
+            .collect::<BTreeSet<u32>>();
+        assert_eq!(offsets, BTreeSet::from([lost, value]));
+        assert!(report
+            .to_string()
+            .starts_with("bad base block checksum, 2 bad cells"));
+    }
+
+    #[test]
+    fn walks_cells_past_a_bad_cell_size_and_a_truncated_hive() {
+        let mut test_hive = test_hive();
+        let kept = test_hive.offset("Software\\Kept");
+        let start = BASE_BLOCK_SIZE + kept as usize;
+        test_hive.bytes[start..start + 4].copy_from_slice(&(-3i32).to_le_bytes());
+        let hive_bins_size = test_hive.bytes.len() as u32;
+        test_hive.bytes[0x28..0x2C].copy_from_slice(&hive_bins_size.to_le_bytes());
+        test_hive.update_checksum();
+        let hive = Hive::from_bytes(test_hive.bytes).unwrap();
+        let report = check(&hive);
+        assert!(report.checksum_valid);
+        assert!(report
+            .bad_cells
+            .iter()
+            .any(|bad_cell| bad_cell.offset == kept
+                && bad_cell.key_path.as_deref() == Some("Software")));
+        assert!(report
+            .bad_cells
+            .iter()
+            .any(|bad_cell| bad_cell.problem.contains("short of the size")));
+        assert_eq!(report.keys_unrecovered, 1);
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    hive::{Hive, KeyNode, ValueNode},
+    memory_source::MemorySource,
+    registry_source::{KeyInfo, RegType, RegistryValue},
+};
+
+/// Key that deleted keys are placed under when their parent can't be found.
+pub const ORPHANED_KEYS: &str = "(Orphaned keys)";
+/// Key that deleted values are placed under when no deleted key references them.
+pub const ORPHANED_VALUES: &str = "(Orphaned values)";
+/// Added to the hive's root name to get the root recovered entries are loaded under.
+pub const DELETED_ROOT_SUFFIX: &str = " (deleted)";
+
+pub struct DeletedKey {
+    pub offset: u32,
+    pub path: String,
+    pub last_write_time: u64,
+    pub values: Vec<(String, RegistryValue)>,
+}
+
+#[derive(Default)]
+pub struct DeletedEntries {
+    pub keys: Vec<DeletedKey>,
+    pub orphaned_values: Vec<(String, RegistryValue)>,
+}
+
+fn deleted_value(hive: &Hive, value: &ValueNode) -> Option<(String, RegistryValue)> {
+    let bytes = hive.deleted_value_data(value).ok()?;
+    Some((
+        value.name.to_owned(),
+        RegistryValue {
+            vtype: RegType::from_u32(value.data_type).unwrap_or(RegType::REG_NONE),
+            bytes,
+        },
+    ))
+}
+
+fn deleted_path(
+    hive: &Hive,
+    key: &KeyNode,
+    deleted_keys: &BTreeMap<u32, KeyNode>,
+    depth: usize,
+) -> String {
+    if let Ok(parent) = hive.key_node(key.parent) {
+        if let Ok(parent_path) = hive.key_path(&parent) {
+            return if parent_path.is_empty() {
+                key.name.to_owned()
+            } else {
+                format!("{}\\{}", parent_path, key.name)
+            };
+        }
+    }
+    match deleted_keys.get(&key.parent) {
+        Some(parent) if depth < 512 && parent.offset != key.offset => format!(
+            "{}\\{}",
+            deleted_path(hive, parent, deleted_keys, depth + 1),
+            key.name
+        ),
+        _ => format!("{}\\{}", ORPHANED_KEYS, key.name),
+    }
+}
+
+/// Carves nk and vk records out of unallocated cells, including records left inside cells that
+/// were coalesced after being freed. Values are attached to the deleted key whose value list still
+/// references them, the rest are returned as orphans.
+pub fn recover_deleted(hive: &Hive) -> DeletedEntries {
+    let mut deleted_keys = BTreeMap::new();
+    let mut deleted_values = BTreeMap::new();
+    for (offset, size) in hive.cells() {
+        if size <= 0 {
+            continue;
+        }
+        let end = offset + size as u32;
+        for position in (offset..end).step_by(8) {
+            let cell = match hive.raw_cell(position) {
+                Ok(cell) if position as usize + cell.len() + 4 <= end as usize => cell,
+                _ => continue,
+            };
+            match cell.get(0..2) {
+                Some(b"nk") => {
+                    if let Ok(key) = hive.deleted_key_node(position) {
+                        deleted_keys.insert(position, key);
+                    }
+                }
+                Some(b"vk") => {
+                    if let Ok(value) = hive.deleted_value_node(position) {
+                        deleted_values.insert(position, value);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut entries = DeletedEntries::default();
+    for key in deleted_keys.values() {
+        let mut values = Vec::new();
+        for value_offset in hive.deleted_value_offsets(key).unwrap_or_default() {
+            if let Some(value) = deleted_values.remove(&value_offset) {
+                if let Some(value) = deleted_value(hive, &value) {
+                    values.push(value);
+                }
+            }
+        }
+        entries.keys.push(DeletedKey {
+            offset: key.offset,
+            path: deleted_path(hive, key, &deleted_keys, 0),
+            last_write_time: key.last_write_time,
+            values,
+        });
+    }
+    entries.orphaned_values = deleted_values
+        .values()
+        .filter_map(|value| deleted_value(hive, value))
+        .collect();
+    entries
+}
+
+/// Adds recovered entries under `root` followed by `DELETED_ROOT_SUFFIX`, annotated so they stand
+/// out from live results. The live parents of deleted keys are recreated under that root too,
+/// rather than passing for the live keys they were copied from.
+pub fn load_deleted(entries: DeletedEntries, root: &str, source: &mut MemorySource) {
+    let root = &format!("{}{}", root, DELETED_ROOT_SUFFIX);
+    for key in entries.keys {
+        source.set_key_info(
+            root,
+            &key.path,
+            KeyInfo {
+                last_write_time: key.last_write_time,
+                ..Default::default()
+            },
+        );
+        source.set_annotation(root, &key.path, "deleted");
+        for (name, value) in key.values {
+            source.set_value(root, &key.path, &name, value);
+        }
+    }
+    if !entries.orphaned_values.is_empty() {
+        source.set_annotation(root, ORPHANED_VALUES, "deleted");
+        for (name, value) in entries.orphaned_values {
+            source.set_value(root, ORPHANED_VALUES, &name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        registry_source::RegistrySource,
+        test_hive::{build, sz, TestKey},
+    };
+
+    fn hive() -> Hive {
+        let mut hive = build(
+            &TestKey::new("ROOT").key(
+                TestKey::new("Software")
+                    .key(TestKey::new("Live").value("kept", 1, sz("kept")))
+                    .key(
+                        TestKey::new("Gone")
+                            .time(133_000_000_000_000_000)
+                            .value("ImagePath", 2, sz("C:\\Temp\\evil.exe"))
+                            .value("Start", 4, 2u32.to_le_bytes().to_vec())
+                            .key(TestKey::new("Child")),
+                    ),
+            ),
+        );
+        hive.delete_key("Software\\Gone\\Child");
+        hive.delete_key("Software\\Gone");
+        Hive::from_bytes(hive.bytes).unwrap()
+    }
+
+    #[test]
+    fn recovers_deleted_keys_and_their_values() {
+        let hive = hive();
+        assert!(hive.open_path("Software\\Gone").is_err());
+        let entries = recover_deleted(&hive);
+        let mut paths = entries
+            .keys
+            .iter()
+            .map(|key| key.path.as_str())
+            .collect::<Vec<&str>>();
+        paths.sort();
+        assert_eq!(paths, vec!["Software\\Gone", "Software\\Gone\\Child"]);
+        let gone = entries
+            .keys
+            .iter()
+            .find(|key| key.path == "Software\\Gone")
+            .unwrap();
+        assert_eq!(gone.last_write_time, 133_000_000_000_000_000);
+        assert_eq!(
+            gone.values,
+            vec![
+                (
+                    "ImagePath".to_string(),
+                    RegistryValue {
+                        vtype: RegType::REG_EXPAND_SZ,
+                        bytes: sz("C:\\Temp\\evil.exe"),
+                    }
+                ),
+                (
+                    "Start".to_string(),
+                    RegistryValue {
+                        vtype: RegType::REG_DWORD,
+                        bytes: vec![2, 0, 0, 0],
+                    }
+                ),
+            ]
+        );
+        assert!(entries.orphaned_values.is_empty());
+    }
+
+    #[test]
+    fn finds_nothing_in_a_hive_without_deletions() {
+        let hive = Hive::from_bytes(build(&TestKey::new("ROOT").key(TestKey::new("A"))).bytes);
+        let entries = recover_deleted(&hive.unwrap());
+        assert!(entries.keys.is_empty());
+        assert!(entries.orphaned_values.is_empty());
+    }
+
+    #[test]
+    fn loads_recovered_entries_under_their_own_root() {
+        let mut entries = recover_deleted(&hive());
+        entries.orphaned_values.push((
+            "lost".to_string(),
+            RegistryValue {
+                vtype: RegType::REG_SZ,
+                bytes: sz("lost"),
+            },
+        ));
+        let mut source = MemorySource::default();
+        load_deleted(entries, "HKEY_LOCAL_MACHINE\\SOFTWARE", &mut source);
+        let root = "HKEY_LOCAL_MACHINE\\SOFTWARE (deleted)";
+        assert_eq!(source.roots(), vec![root]);
+        let gone = source.open_key(root, "Software\\Gone").unwrap();
+        assert_eq!(source.annotation(&gone).as_deref(), Some("deleted"));
+        assert_eq!(
+            source.key_info(&gone).unwrap().last_write_time,
+            133_000_000_000_000_000
+        );
+        assert_eq!(source.enum_values(&gone).len(), 2);
+        let orphaned = source.open_key(root, ORPHANED_VALUES).unwrap();
+        assert_eq!(source.enum_values(&orphaned).len(), 1);
+    }
+}
@@ -0,0 +1,268 @@
+use std::path::Path;
+
+use crate::{
+    hive::{Hive, KeyNode},
+    registry_source::{KeyInfo, RegType, RegistrySource, RegistryValue, Subkeys},
+};
+
+/// Where a hive is normally loaded on a live system, based on its file name.
+pub fn default_root_name(path: &Path) -> String {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match file_name.to_uppercase().as_str() {
+        "SYSTEM" | "SOFTWARE" | "SAM" | "SECURITY" | "HARDWARE" | "BCD" | "COMPONENTS"
+        | "DRIVERS" => format!("HKEY_LOCAL_MACHINE\\{}", file_name.to_uppercase()),
+        "DEFAULT" => "HKEY_USERS\\.DEFAULT".into(),
+        "NTUSER.DAT" => "HKEY_CURRENT_USER".into(),
+        "USRCLASS.DAT" => "HKEY_CURRENT_USER\\Software\\Classes".into(),
+        _ => file_name,
+    }
+}
+
+/// One or more offline hive files, each mounted under its own root name.
+#[derive(Default)]
+pub struct HiveSource {
+    hives: Vec<(String, Hive)>,
+}
+
+impl HiveSource {
+    /// `root_name`, followed by " (2)", " (3)" and so on when another hive is already mounted
+    /// there, so hives with the same file name from different places are all searched.
+    pub fn unique_root_name(&self, root_name: &str) -> String {
+        let taken = |name: &str| {
+            self.hives
+                .iter()
+                .any(|(mounted, _)| mounted.eq_ignore_ascii_case(name))
+        };
+        if !taken(root_name) {
+            return root_name.to_string();
+        }
+        (2..)
+            .map(|number| format!("{} ({})", root_name, number))
+            .find(|name| !taken(name))
+            .unwrap()
+    }
+
+    /// Mounts under `unique_root_name(root_name)` and returns the name it was given.
+    pub fn mount(&mut self, root_name: String, hive: Hive) -> String {
+        let root_name = self.unique_root_name(&root_name);
+        self.hives.push((root_name.to_owned(), hive));
+        root_name
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hives.is_empty()
+    }
+
+    fn hive(&self, root: &str) -> Result<(usize, &Hive), String> {
+        self.hives
+            .iter()
+            .enumerate()
+            .find(|(_, (root_name, _))| root_name.eq_ignore_ascii_case(root))
+            .map(|(index, (_, hive))| (index, hive))
+            .ok_or_else(|| format!("No hive is mounted at \"{}\"", root))
+    }
+}
+
+impl RegistrySource for HiveSource {
+    type Key = (usize, KeyNode);
+
+    fn roots(&self) -> Vec<String> {
+        self.hives
+            .iter()
+            .map(|(root_name, _)| root_name.to_owned())
+            .collect()
+    }
+
+    fn open_key(&self, root: &str, path: &str) -> Result<(usize, KeyNode), String> {
+        let (index, hive) = self.hive(root)?;
+        Ok((index, hive.open_path(path)?))
+    }
+
+    fn enum_keys(&self, key: &(usize, KeyNode)) -> Vec<Result<String, String>> {
+        self.enum_subkeys(key)
+            .into_iter()
+            .map(|subkey| subkey.map(|(name, _)| name))
+            .collect()
+    }
+
+    /// Subkeys are handed out by their cell, so walking a key with many subkeys doesn't rebuild
+    /// its subkey list to look each one up by name. Parts of the list that can't be read are
+    /// errors alongside the subkeys that can.
+    fn enum_subkeys(&self, (index, key): &(usize, KeyNode)) -> Subkeys<(usize, KeyNode)> {
+        let hive = &self.hives[*index].1;
+        let (offsets, problems) = hive.subkeys_checked(key);
+        offsets
+            .into_iter()
+            .map(|offset| {
+                hive.key_node(offset)
+                    .map(|subkey| (subkey.name.to_owned(), Some((*index, subkey))))
+            })
+            .chain(problems.into_iter().map(|problem| Err(problem.message)))
+            .collect()
+    }
+
+    fn enum_values(
+        &self,
+        (index, key): &(usize, KeyNode),
+    ) -> Vec<Result<(String, RegistryValue), String>> {
+        let hive = &self.hives[*index].1;
+        let (offsets, problem) = hive.values_checked(key);
+        offsets
+            .into_iter()
+            .map(|offset| {
+                let value = hive.value_node(offset)?;
+                let bytes = hive.value_data(&value)?;
+                Ok((
+                    value.name,
+                    RegistryValue {
+                        vtype: RegType::from_u32(value.data_type).unwrap_or(RegType::REG_NONE),
+                        bytes,
+                    },
+                ))
+            })
+            .chain(problem.map(|problem| Err(problem.message)))
+            .collect()
+    }
+
+    fn key_info(&self, (index, key): &(usize, KeyNode)) -> Result<KeyInfo, String> {
+        let hive = &self.hives[*index].1;
+        Ok(KeyInfo {
+            last_write_time: key.last_write_time,
+            class_name: hive.class_name(key)?,
+            subkey_count: key.subkey_count,
+            value_count: key.value_count,
+            max_value_len: key.max_value_data_size,
+        })
+    }
+
+    fn security_descriptor(
+        &self,
+        (index, key): &(usize, KeyNode),
+    ) -> Result<Option<Vec<u8>>, String> {
+        self.hives[*index].1.security_descriptor(key)
+    }
+
+    fn annotation(&self, (index, key): &(usize, KeyNode)) -> Option<String> {
+        if self.hives[*index].1.is_log_only(key) {
+            Some("transaction log".into())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_hive::{build, sz, TestKey};
+
+    fn hive(data: &str) -> Hive {
+        let root = TestKey::new("ROOT").key(TestKey::new("Run").value("a", 1, sz(data)));
+        Hive::from_bytes(build(&root).bytes).unwrap()
+    }
+
+    #[test]
+    fn default_root_names() {
+        for (path, root) in [
+            ("hives/SYSTEM", "HKEY_LOCAL_MACHINE\\SYSTEM"),
+            ("software", "HKEY_LOCAL_MACHINE\\SOFTWARE"),
+            ("NTUSER.DAT", "HKEY_CURRENT_USER"),
+            ("UsrClass.dat", "HKEY_CURRENT_USER\\Software\\Classes"),
+            ("DEFAULT", "HKEY_USERS\\.DEFAULT"),
+            ("other.hiv", "other.hiv"),
+        ] {
+            assert_eq!(default_root_name(Path::new(path)), root);
+        }
+    }
+
+    #[test]
+    fn mounts_hives_with_the_same_root_separately() {
+        let mut hive_source = HiveSource::default();
+        assert_eq!(
+            hive_source.mount("HKEY_CURRENT_USER".into(), hive("first")),
+            "HKEY_CURRENT_USER"
+        );
+        assert_eq!(
+            hive_source.mount("hkey_current_user".into(), hive("second")),
+            "hkey_current_user (2)"
+        );
+        assert_eq!(
+            hive_source.mount("HKEY_CURRENT_USER".into(), hive("third")),
+            "HKEY_CURRENT_USER (3)"
+        );
+        let data = hive_source
+            .roots()
+            .iter()
+            .map(|root| {
+                let key = hive_source.open_key(root, "Run").unwrap();
+                let values = hive_source.enum_values(&key);
+                let (_, value) = values[0].as_ref().unwrap();
+                value.bytes.to_owned()
+            })
+            .collect::<Vec<Vec<u8>>>();
+        assert_eq!(data, vec![sz("first"), sz("second"), sz("third")]);
+    }
+
+    #[test]
+    fn walks_keys_and_reports_unknown_roots() {
+        let mut hive_source = HiveSource::default();
+        hive_source.mount("X".into(), hive("data"));
+        let root = hive_source.open_key("x", "").unwrap();
+        let subkeys = hive_source.enum_keys(&root);
+        assert_eq!(subkeys.len(), 1);
+        assert_eq!(subkeys[0].as_deref(), Ok("Run"));
+        let run = hive_source.open_key("X", "Run").unwrap();
+        let key_info = hive_source.key_info(&run).unwrap();
+        assert_eq!(key_info.value_count, 1);
+        assert_eq!(hive_source.annotation(&run), None);
+        assert!(hive_source.open_key("Y", "").is_err());
+    }
+
+    #[test]
+    fn hands_out_subkeys_by_their_cell() {
+        let mut alpha = TestKey::new("Alpha");
+        for index in 0..20 {
+            alpha = alpha.key(TestKey::new(&format!("Sub{:02}", index)).value("n", 1, sz("x")));
+        }
+        let mut hive_source = HiveSource::default();
+        hive_source.mount(
+            "X".into(),
+            Hive::from_bytes(build(&TestKey::new("ROOT").key(alpha)).bytes).unwrap(),
+        );
+        let alpha = hive_source.open_key("X", "Alpha").unwrap();
+        let subkeys = hive_source.enum_subkeys(&alpha);
+        assert_eq!(subkeys.len(), 20);
+        for subkey in subkeys {
+            let (name, subkey) = subkey.unwrap();
+            let (index, subkey) = subkey.unwrap();
+            let (opened_index, opened) = hive_source
+                .open_key("X", &format!("Alpha\\{}", name))
+                .unwrap();
+            assert_eq!((index, subkey.offset), (opened_index, opened.offset));
+            assert_eq!(hive_source.enum_values(&(index, subkey)).len(), 1);
+        }
+    }
+
+    #[test]
+    fn keeps_readable_subkeys_next_to_bad_ones() {
+        let mut test_hive = build(
+            &TestKey::new("ROOT")
+                .key(TestKey::new("A"))
+                .key(TestKey::new("B"))
+                .key(TestKey::new("C")),
+        );
+        let start = crate::hive::BASE_BLOCK_SIZE + test_hive.offset("B") as usize + 4;
+        test_hive.bytes[start..start + 2].copy_from_slice(b"XX");
+        let mut hive_source = HiveSource::default();
+        hive_source.mount("X".into(), Hive::from_bytes(test_hive.bytes).unwrap());
+        let root = hive_source.open_key("X", "").unwrap();
+        let subkeys = hive_source.enum_keys(&root);
+        assert_eq!(subkeys.len(), 3);
+        assert_eq!(subkeys[0].as_deref(), Ok("A"));
+        assert!(subkeys[1].is_err());
+        assert_eq!(subkeys[2].as_deref(), Ok("C"));
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const CONFIG_HIVES: [(&str, &str); 5] = [
+    ("SYSTEM", "HKEY_LOCAL_MACHINE\\SYSTEM"),
+    ("SOFTWARE", "HKEY_LOCAL_MACHINE\\SOFTWARE"),
+    ("SAM", "HKEY_LOCAL_MACHINE\\SAM"),
+    ("SECURITY", "HKEY_LOCAL_MACHINE\\SECURITY"),
+    ("DEFAULT", "HKEY_USERS\\.DEFAULT"),
+];
+
+/// A hive file found in an image and the root it would be loaded under on that system.
+#[derive(Debug, Clone)]
+pub struct DiscoveredHive {
+    pub path: PathBuf,
+    pub root: String,
+}
+
+/// Finds `name` in `directory` ignoring case, mounted NTFS volumes aren't always case insensitive.
+fn find_child(directory: &Path, name: &str) -> Option<PathBuf> {
+    let exact = directory.join(name);
+    if exact.exists() {
+        return Some(exact);
+    }
+    fs::read_dir(directory)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .eq_ignore_ascii_case(name)
+        })
+        .map(|entry| entry.path())
+}
+
+fn find_path(directory: &Path, components: &[&str]) -> Option<PathBuf> {
+    components
+        .iter()
+        .try_fold(directory.to_path_buf(), |path, component| {
+            find_child(&path, component)
+        })
+}
+
+/// Empty files are skipped, RegBack only holds placeholders on Windows 10 1803 and later.
+fn hive_file(path: Option<PathBuf>) -> Option<PathBuf> {
+    let path = path?;
+    match fs::metadata(&path) {
+        Ok(metadata) if metadata.is_file() && metadata.len() > 0 => Some(path),
+        _ => None,
+    }
+}
+
+/// Finds the system hives, their RegBack copies and every user's NTUSER.DAT and UsrClass.dat
+/// under the root of a mounted Windows volume.
+pub fn discover(image_root: &Path) -> Vec<DiscoveredHive> {
+    let mut hives = Vec::new();
+    if let Some(config) = find_path(image_root, &["Windows", "System32", "config"]) {
+        for (file_name, root) in CONFIG_HIVES {
+            if let Some(path) = hive_file(find_child(&config, file_name)) {
+                hives.push(DiscoveredHive {
+                    path,
+                    root: root.to_string(),
+                });
+            }
+        }
+        if let Some(reg_back) = find_child(&config, "RegBack") {
+            for (file_name, root) in CONFIG_HIVES {
+                if let Some(path) = hive_file(find_child(&reg_back, file_name)) {
+                    hives.push(DiscoveredHive {
+                        path,
+                        root: format!("{} (RegBack)", root),
+                    });
+                }
+            }
+        }
+    }
+
+    let users = find_child(image_root, "Users")
+        .and_then(|users| fs::read_dir(users).ok())
+        .map(|entries| {
+            let mut users = entries
+                .filter_map(|entry| entry.ok())
+                //"Default User" and "All Users" are junctions to other profiles
+                .filter(|entry| {
+                    entry
+                        .file_type()
+                        .map(|file_type| file_type.is_dir() && !file_type.is_symlink())
+                        .unwrap_or(false)
+                })
+                .map(|entry| entry.path())
+                .collect::<Vec<PathBuf>>();
+            users.sort();
+            users
+        })
+        .unwrap_or_default();
+    for profile in users {
+        let user = profile
+            .file_name()
+            .map(|user| user.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if let Some(path) = hive_file(find_child(&profile, "NTUSER.DAT")) {
+            hives.push(DiscoveredHive {
+                path,
+                root: format!("HKEY_USERS\\{}", user),
+            });
+        }
+        if let Some(path) = hive_file(find_path(
+            &profile,
+            &["AppData", "Local", "Microsoft", "Windows", "UsrClass.dat"],
+        )) {
+            hives.push(DiscoveredHive {
+                path,
+                root: format!("HKEY_USERS\\{}_Classes", user),
+            });
+        }
+    }
+    hives
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_hive::temp_dir;
+
+    #[test]
+    fn finds_system_and_user_hives_ignoring_case() {
+        let image = temp_dir("image");
+        let config = image.join("WINDOWS").join("system32").join("Config");
+        fs::create_dir_all(config.join("RegBack")).unwrap();
+        fs::write(config.join("SYSTEM"), b"regf").unwrap();
+        fs::write(config.join("software"), b"regf").unwrap();
+        fs::write(config.join("RegBack").join("SYSTEM"), b"regf").unwrap();
+        fs::write(config.join("RegBack").join("SOFTWARE"), b"").unwrap();
+        let alice = image.join("Users").join("alice");
+        let classes = alice.join("AppData/Local/Microsoft/Windows");
+        fs::create_dir_all(&classes).unwrap();
+        fs::write(alice.join("ntuser.dat"), b"regf").unwrap();
+        fs::write(classes.join("UsrClass.dat"), b"regf").unwrap();
+        fs::create_dir_all(image.join("Users").join("Public")).unwrap();
+
+        let roots = discover(&image)
+            .into_iter()
+            .map(|hive| hive.root)
+            .collect::<Vec<String>>();
+        assert_eq!(
+            roots,
+            vec![
+                "HKEY_LOCAL_MACHINE\\SYSTEM",
+                "HKEY_LOCAL_MACHINE\\SOFTWARE",
+                "HKEY_LOCAL_MACHINE\\SYSTEM (RegBack)",
+                "HKEY_USERS\\alice",
+                "HKEY_USERS\\alice_Classes",
+            ]
+        );
+        fs::remove_dir_all(image).unwrap();
+    }
+
+    #[test]
+    fn finds_nothing_outside_a_windows_volume() {
+        let image = temp_dir("not_an_image");
+        assert!(discover(&image).is_empty());
+        fs::remove_dir_all(image).unwrap();
+    }
+}
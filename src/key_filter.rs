@@ -0,0 +1,116 @@
+use crate::{
+    number_predicate::NumberPredicate,
+    registry_source::KeyInfo,
+    timestamp::{self, DateRange},
+};
+
+/// Key metadata as shown after a key's path in the results, eg. ` [modified 2024-01-31
+/// 10:36:07 UTC, class "Shell", 3 subkeys, 2 values, max data 520 bytes]`. Sources that don't
+/// record a last write time, like .reg files, leave it out.
+pub fn describe(info: &KeyInfo) -> String {
+    let mut parts = Vec::new();
+    if info.last_write_time != 0 {
+        parts.push(format!(
+            "modified {}",
+            timestamp::format_filetime(info.last_write_time)
+        ));
+    }
+    if let Some(class_name) = info.class_name.as_ref() {
+        parts.push(format!("class \"{}\"", class_name));
+    }
+    parts.push(format!("{} subkeys", info.subkey_count));
+    parts.push(format!("{} values", info.value_count));
+    parts.push(format!("max data {} bytes", info.max_value_len));
+    format!(" [{}]", parts.join(", "))
+}
+
+/// Conditions on key metadata every result's key has to meet, on top of matching a term. Keys
+/// that are left out are still walked for subkeys that meet them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyFilter {
+    pub modified: Option<DateRange>,
+    /// Found in the class name case insensitively, stored lowercased. Keys without one never
+    /// match.
+    pub class_name: Option<String>,
+    pub subkeys: Option<NumberPredicate>,
+    pub values: Option<NumberPredicate>,
+    pub max_data: Option<NumberPredicate>,
+}
+
+impl KeyFilter {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn matches(&self, info: &KeyInfo) -> bool {
+        let number_matches = |predicate: &Option<NumberPredicate>, number: u32| {
+            predicate
+                .as_ref()
+                .is_none_or(|predicate| predicate.matches(number as u64))
+        };
+        self.modified
+            .as_ref()
+            .is_none_or(|range| info.last_write_time != 0 && range.contains(info.last_write_time))
+            && self.class_name.as_ref().is_none_or(|term| {
+                info.class_name
+                    .as_ref()
+                    .is_some_and(|class_name| class_name.to_lowercase().contains(term))
+            })
+            && number_matches(&self.subkeys, info.subkey_count)
+            && number_matches(&self.values, info.value_count)
+            && number_matches(&self.max_data, info.max_value_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_and_describes_key_metadata() {
+        let info = KeyInfo {
+            last_write_time: 133_511_709_670_000_000,
+            class_name: Some("Shell".into()),
+            subkey_count: 3,
+            value_count: 2,
+            max_value_len: 520,
+        };
+        assert_eq!(
+            describe(&info),
+            " [modified 2024-01-31 10:36:07 UTC, class \"Shell\", 3 subkeys, 2 values, max data 520 bytes]"
+        );
+        assert_eq!(
+            describe(&KeyInfo::default()),
+            " [0 subkeys, 0 values, max data 0 bytes]"
+        );
+
+        let filter = KeyFilter {
+            modified: Some(DateRange::parse("2024-01-31..2024-01-31").unwrap()),
+            class_name: Some("shell".into()),
+            subkeys: Some(NumberPredicate::parse(">= 1").unwrap()),
+            ..Default::default()
+        };
+        assert!(KeyFilter::default().is_empty() && !filter.is_empty());
+        assert!(filter.matches(&info));
+        for info in [
+            KeyInfo {
+                last_write_time: 133_536_765_670_000_000,
+                ..info.to_owned()
+            },
+            KeyInfo {
+                class_name: None,
+                ..info.to_owned()
+            },
+            KeyInfo {
+                subkey_count: 0,
+                ..info.to_owned()
+            },
+            KeyInfo {
+                last_write_time: 0,
+                ..info.to_owned()
+            },
+        ] {
+            assert!(!filter.matches(&info), "{:?}", info);
+        }
+    }
+}
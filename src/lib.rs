@@ -6,14 +6,44 @@ use std::{
 use parking_lot::RwLock;
 use ratatui::style::Color;
 use search_editor::SearchEditor;
-use winreg::{enums::RegType, RegValue};
 
+pub mod binary_strings;
+pub mod byte_pattern;
+pub mod carve;
 pub mod controls;
+pub mod environment;
+pub mod hive;
+pub mod hive_integrity;
+pub mod hive_recovery;
+pub mod hive_source;
+pub mod image_discovery;
+pub mod key_filter;
+pub mod lint;
+pub mod memory_source;
+pub mod number_predicate;
+pub mod offline_source;
+pub mod path_glob;
+pub mod policy_file;
+pub mod query;
+pub mod reg_data;
+pub mod reg_file;
+pub mod registry_source;
 pub mod renderer;
+pub mod resource_list;
 pub mod root;
 pub mod search_editor;
+pub mod search_term;
 pub mod search_term_tracker;
+pub mod security_descriptor;
+pub mod snapshot;
 pub mod static_selection;
+pub mod suppression;
+#[cfg(test)]
+pub mod test_hive;
+pub mod timestamp;
+pub mod transaction_log;
+#[cfg(windows)]
+pub mod winreg_source;
 pub mod worker_manager;
 pub mod worker_runtime;
 
@@ -23,6 +53,8 @@ pub const SELECTION_COLOUR: Color = Color::Cyan;
 
 pub static KEY_COUNT: AtomicUsize = AtomicUsize::new(0);
 pub static VALUE_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// Results left out by exclusion terms or the suppression list this run.
+pub static SUPPRESSED_COUNT: AtomicUsize = AtomicUsize::new(0);
 
 const REGEDIT_OUTPUT_FOR_BLANK_NAMES: bool = true;
 
@@ -39,48 +71,3 @@ pub enum Focus {
     Help,
     ConfirmClose,
 }
-
-pub fn alt_reg_value_to_string(reg_value: RegValue) -> String {
-    match reg_value.vtype {
-        RegType::REG_SZ | RegType::REG_EXPAND_SZ => {
-            String::from_utf8_lossy(&reg_value.bytes).to_string()
-        }
-        RegType::REG_BINARY => {
-            format!("BIN_LENGTH: {}", reg_value.bytes.len())
-        }
-        RegType::REG_DWORD => {
-            let u32 = match reg_value.bytes.try_into() {
-                Ok(t) => t,
-                Err(_err) => return "Invalid REG_DWORD".into(),
-            };
-            u32::from_le_bytes(u32).to_string()
-        }
-        RegType::REG_DWORD_BIG_ENDIAN => {
-            let u32 = match reg_value.bytes.try_into() {
-                Ok(t) => t,
-                Err(_err) => return "Invalid REG_DWORD_BIG_ENDIAN".into(),
-            };
-            u32::from_be_bytes(u32).to_string()
-        }
-        RegType::REG_QWORD => {
-            let u64 = match reg_value.bytes.try_into() {
-                Ok(t) => t,
-                Err(_err) => return "Invalid REG_DWORD".into(),
-            };
-            u64::from_le_bytes(u64).to_string()
-        }
-        RegType::REG_MULTI_SZ | RegType::REG_RESOURCE_LIST => {
-            // Split at null bytes and join
-            reg_value
-                .bytes
-                .split(|&b| b == 0)
-                .filter_map(|s| std::str::from_utf8(s).ok())
-                .collect::<Vec<&str>>()
-                .join(", ")
-        }
-        RegType::REG_LINK
-        | RegType::REG_FULL_RESOURCE_DESCRIPTOR
-        | RegType::REG_RESOURCE_REQUIREMENTS_LIST => reg_value.to_string(),
-        RegType::REG_NONE => "None".into(),
-    }
-}
@@ -0,0 +1,360 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+    reg_data::decode_utf16,
+    registry_source::{RegType, RegistrySource, RegistryValue},
+    resource_list,
+};
+
+/// Kinds of value data that doesn't fit its declared type, in the order they're listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    WrongSize,
+    OddLength,
+    MissingTerminator,
+    MissingDoubleTerminator,
+    DataAfterTerminator,
+    MalformedStructure,
+    DanglingLink,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::WrongSize => "Wrong size",
+            Self::OddLength => "Odd length string",
+            Self::MissingTerminator => "Missing NUL terminator",
+            Self::MissingDoubleTerminator => "Missing double NUL terminator",
+            Self::DataAfterTerminator => "Data after terminator",
+            Self::MalformedStructure => "Malformed structure",
+            Self::DanglingLink => "Dangling link",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Anomaly {
+    pub category: Category,
+    pub detail: String,
+}
+
+impl Anomaly {
+    fn new(category: Category, detail: String) -> Self {
+        Self { category, detail }
+    }
+}
+
+fn units(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+/// Bytes after `end` that aren't all zero, the padding some writers leave is ignored.
+fn trailing_data(units: &[u16], end: usize) -> Option<usize> {
+    let trailing = &units[end..];
+    trailing
+        .iter()
+        .any(|unit| *unit != 0)
+        .then_some(trailing.len() * 2)
+}
+
+/// Everything wrong with the size and layout of a value's data for its type. REG_LINK targets
+/// are checked against the registry with `dangling_link`.
+pub fn check(value: &RegistryValue) -> Vec<Anomaly> {
+    let bytes = &value.bytes;
+    let mut anomalies = Vec::new();
+    let expected_size = match value.vtype {
+        RegType::REG_DWORD | RegType::REG_DWORD_BIG_ENDIAN => Some(4),
+        RegType::REG_QWORD => Some(8),
+        _ => None,
+    };
+    if let Some(expected_size) = expected_size {
+        if bytes.len() != expected_size {
+            anomalies.push(Anomaly::new(
+                Category::WrongSize,
+                format!("{} bytes, expected {}", bytes.len(), expected_size),
+            ));
+        }
+    }
+    if matches!(
+        value.vtype,
+        RegType::REG_SZ | RegType::REG_EXPAND_SZ | RegType::REG_MULTI_SZ | RegType::REG_LINK
+    ) && !bytes.len().is_multiple_of(2)
+    {
+        anomalies.push(Anomaly::new(
+            Category::OddLength,
+            format!(
+                "{} bytes, not a whole number of UTF-16 characters",
+                bytes.len()
+            ),
+        ));
+    }
+    let units = units(bytes);
+    match value.vtype {
+        //empty data is how an empty string is often written, not a missing terminator
+        RegType::REG_SZ | RegType::REG_EXPAND_SZ if !units.is_empty() => {
+            match units.iter().position(|unit| *unit == 0) {
+                Some(end) => {
+                    if let Some(length) = trailing_data(&units, end + 1) {
+                        anomalies.push(Anomaly::new(
+                            Category::DataAfterTerminator,
+                            format!("{} bytes after the NUL terminator", length),
+                        ));
+                    }
+                }
+                None => anomalies.push(Anomaly::new(
+                    Category::MissingTerminator,
+                    format!("{} characters without a NUL", units.len()),
+                )),
+            }
+        }
+        RegType::REG_MULTI_SZ if !units.is_empty() => {
+            //the list ends with an empty string, which is a NUL straight after another or at 0
+            let end = (0..units.len())
+                .find(|index| units[*index] == 0 && (*index == 0 || units[index - 1] == 0));
+            match end {
+                Some(end) => {
+                    if let Some(length) = trailing_data(&units, end + 1) {
+                        anomalies.push(Anomaly::new(
+                            Category::DataAfterTerminator,
+                            format!("{} bytes after the double NUL terminator", length),
+                        ));
+                    }
+                }
+                None => anomalies.push(Anomaly::new(
+                    Category::MissingDoubleTerminator,
+                    format!(
+                        "{} characters without an empty string to end the list",
+                        units.len()
+                    ),
+                )),
+            }
+        }
+        RegType::REG_RESOURCE_LIST
+        | RegType::REG_FULL_RESOURCE_DESCRIPTOR
+        | RegType::REG_RESOURCE_REQUIREMENTS_LIST => {
+            let parsed = match value.vtype {
+                RegType::REG_RESOURCE_LIST => resource_list::parse_resource_list(bytes).map(drop),
+                RegType::REG_FULL_RESOURCE_DESCRIPTOR => {
+                    resource_list::parse_full_resource_descriptor(bytes).map(drop)
+                }
+                _ => resource_list::parse_requirements_list(bytes).map(drop),
+            };
+            if let Err(err) = parsed {
+                anomalies.push(Anomaly::new(Category::MalformedStructure, err));
+            }
+        }
+        _ => {}
+    }
+    anomalies
+}
+
+/// Where a REG_LINK target like `\Registry\Machine\System\ControlSet001` is in the roots
+/// searched, eg. `HKEY_LOCAL_MACHINE\System\ControlSet001`.
+pub fn registry_path(target: &str) -> Option<String> {
+    [
+        ("\\Registry\\Machine", "HKEY_LOCAL_MACHINE"),
+        ("\\Registry\\User", "HKEY_USERS"),
+    ]
+    .iter()
+    .find_map(|(prefix, root)| {
+        let rest = target.get(..prefix.len())?;
+        if !rest.eq_ignore_ascii_case(prefix) {
+            return None;
+        }
+        match &target[prefix.len()..] {
+            "" => Some(root.to_string()),
+            rest if rest.starts_with('\\') => Some(format!("{}{}", root, rest)),
+            _ => None,
+        }
+    })
+}
+
+/// Whether the key `path` exists, `None` when it's under none of the roots loaded so there's no
+/// telling.
+pub fn key_exists<S: RegistrySource>(source: &S, path: &str) -> Option<bool> {
+    let root = source
+        .roots()
+        .into_iter()
+        .filter(|root| {
+            path.get(..root.len())
+                .is_some_and(|start| start.eq_ignore_ascii_case(root))
+                && matches!(path[root.len()..].chars().next(), None | Some('\\'))
+        })
+        .max_by_key(|root| root.len())?;
+    let rest = path[root.len()..].trim_start_matches('\\');
+    Some(source.open_key(&root, rest).is_ok())
+}
+
+/// The anomaly of a REG_LINK whose target isn't a registry path or is a key that doesn't exist.
+pub fn dangling_link<S: RegistrySource>(source: &S, value: &RegistryValue) -> Option<Anomaly> {
+    if value.vtype != RegType::REG_LINK {
+        return None;
+    }
+    let target = decode_utf16(&value.bytes);
+    let detail = match registry_path(&target) {
+        _ if target.is_empty() => "empty target".to_string(),
+        Some(path) => match key_exists(source, &path) {
+            Some(false) => format!("\"{}\" doesn't exist", target),
+            _ => return None,
+        },
+        None => format!("\"{}\" isn't a registry path", target),
+    };
+    Some(Anomaly::new(Category::DanglingLink, detail))
+}
+
+/// Anomalies found across a run, each listed as the value's full path and type followed by what
+/// is wrong with it. Values can be added from any number of worker threads.
+#[derive(Debug, Default)]
+pub struct LintReport {
+    findings: Mutex<BTreeMap<Category, BTreeSet<String>>>,
+}
+
+impl LintReport {
+    pub fn add(&self, value_path: &str, vtype: RegType, anomaly: Anomaly) {
+        self.findings
+            .lock()
+            .entry(anomaly.category)
+            .or_default()
+            .insert(format!("{} ({}): {}", value_path, vtype, anomaly.detail));
+    }
+
+    pub fn len(&self) -> usize {
+        self.findings.lock().values().map(|found| found.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn findings(&self) -> BTreeMap<Category, BTreeSet<String>> {
+        self.findings.lock().to_owned()
+    }
+}
+
+impl fmt::Display for LintReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (category, found) in self.findings.lock().iter() {
+            writeln!(f, "{} ({})", category, found.len())?;
+            for finding in found {
+                writeln!(f, "    {}", finding)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{memory_source::MemorySource, reg_file::to_utf16_bytes};
+
+    fn categories(vtype: RegType, bytes: Vec<u8>) -> Vec<Category> {
+        check(&RegistryValue { vtype, bytes })
+            .into_iter()
+            .map(|anomaly| anomaly.category)
+            .collect()
+    }
+
+    #[test]
+    fn finds_data_that_does_not_fit_its_type() {
+        assert_eq!(
+            categories(RegType::REG_DWORD, vec![1, 0, 0]),
+            vec![Category::WrongSize]
+        );
+        assert_eq!(
+            categories(RegType::REG_QWORD, vec![0; 4]),
+            vec![Category::WrongSize]
+        );
+        assert_eq!(
+            categories(RegType::REG_SZ, b"ab\0".to_vec()),
+            vec![Category::OddLength, Category::MissingTerminator]
+        );
+        assert_eq!(
+            categories(RegType::REG_SZ, to_utf16_bytes("a\0\0\0hidden")),
+            vec![Category::DataAfterTerminator]
+        );
+        assert_eq!(
+            categories(RegType::REG_MULTI_SZ, to_utf16_bytes("one\0two")),
+            vec![Category::MissingDoubleTerminator]
+        );
+        assert_eq!(
+            categories(RegType::REG_RESOURCE_LIST, vec![1, 0, 0, 0, 1]),
+            vec![Category::MalformedStructure]
+        );
+        for (vtype, bytes) in [
+            (RegType::REG_DWORD, vec![0; 4]),
+            (RegType::REG_SZ, to_utf16_bytes("a\0")),
+            (RegType::REG_SZ, Vec::new()),
+            (RegType::REG_EXPAND_SZ, to_utf16_bytes("%TEMP%\0\0")),
+            (RegType::REG_MULTI_SZ, to_utf16_bytes("one\0two\0\0")),
+            (RegType::REG_MULTI_SZ, to_utf16_bytes("\0")),
+            (RegType::REG_BINARY, vec![1, 2, 3]),
+        ] {
+            assert!(categories(vtype, bytes.clone()).is_empty(), "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn finds_links_pointing_nowhere() {
+        let mut source = MemorySource::default();
+        source.set_value(
+            "HKEY_LOCAL_MACHINE",
+            "SYSTEM\\ControlSet001\\Services",
+            "",
+            RegistryValue {
+                vtype: RegType::REG_SZ,
+                bytes: Vec::new(),
+            },
+        );
+        let link = |target: &str| RegistryValue {
+            vtype: RegType::REG_LINK,
+            bytes: to_utf16_bytes(target),
+        };
+        assert_eq!(
+            registry_path("\\REGISTRY\\MACHINE\\System"),
+            Some("HKEY_LOCAL_MACHINE\\System".into())
+        );
+        assert_eq!(registry_path("\\Registry\\Machinery"), None);
+        assert!(
+            dangling_link(&source, &link("\\Registry\\Machine\\System\\ControlSet001")).is_none()
+        );
+        //no hive is loaded under HKEY_USERS, so there's no telling
+        assert!(dangling_link(&source, &link("\\Registry\\User\\S-1-5-18")).is_none());
+        for target in [
+            "\\Registry\\Machine\\System\\ControlSet002",
+            "C:\\Windows",
+            "",
+        ] {
+            assert_eq!(
+                dangling_link(&source, &link(target)).map(|anomaly| anomaly.category),
+                Some(Category::DanglingLink),
+                "{}",
+                target
+            );
+        }
+
+        let report = LintReport::default();
+        report.add(
+            "HKEY_LOCAL_MACHINE\\SYSTEM\\Select\\Current",
+            RegType::REG_DWORD,
+            check(&RegistryValue {
+                vtype: RegType::REG_DWORD,
+                bytes: vec![1, 0, 0],
+            })
+            .remove(0),
+        );
+        assert_eq!(
+            report.to_string(),
+            "Wrong size (1)\n    HKEY_LOCAL_MACHINE\\SYSTEM\\Select\\Current (REG_DWORD): 3 bytes, expected 4\n"
+        );
+    }
+}
@@ -1,12 +1,20 @@
 use directories::BaseDirs;
 use parking_lot::RwLock;
 use registry_playground::{
-    controls::controls, renderer::renderer_wrappers_wrapper, static_selection::StaticSelection,
-    worker_runtime::worker_runtime, Focus,
+    controls::controls,
+    offline_source::{self, SelectedSources},
+    renderer::renderer_wrappers_wrapper,
+    static_selection::StaticSelection,
+    worker_runtime::{self, worker_runtime},
+    Focus, VALUE_COUNT,
 };
 use std::{
     error::Error,
-    sync::{atomic::AtomicBool, Arc},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     thread,
 };
 use tokio::{sync::mpsc, task::JoinHandle};
@@ -15,11 +23,12 @@ use tracing_subscriber::{filter::LevelFilter, layer::SubscriberExt, registry::Re
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    let matches = offline_source::command().get_matches();
     let base_directories = BaseDirs::new().expect("Base directories not found");
     let log_path = base_directories
         .config_dir()
         .join("windows_registry_search/logs/");
-    let file = tracing_appender::rolling::daily(log_path, format!("log"));
+    let file = tracing_appender::rolling::daily(log_path, "log");
     let (file_writer, _guard) = tracing_appender::non_blocking(file);
     let level_filter = LevelFilter::from_level(Level::DEBUG);
     let logfile_layer = tracing_subscriber::fmt::layer()
@@ -29,10 +38,51 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let subscriber = Registry::default().with(logfile_layer);
     tracing::subscriber::set_global_default(subscriber).unwrap();
 
+    if let Some(capture_path) = matches.get_one::<String>("capture") {
+        let capture_path = Path::new(capture_path);
+        let (offline_sources, setup_errors) = offline_source::from_matches(&matches);
+        let (summary, errors) = worker_runtime::capture(offline_sources, capture_path).await?;
+        for error in setup_errors.into_iter().chain(errors) {
+            eprintln!("{}", error);
+        }
+        println!(
+            "Captured {} keys and {} values to {}",
+            summary.keys,
+            summary.values,
+            capture_path.display()
+        );
+        return Ok(());
+    }
+
+    if matches.get_flag("lint") {
+        let (offline_sources, setup_errors) = offline_source::from_matches(&matches);
+        let (report, errors) = worker_runtime::lint(offline_sources).await?;
+        for error in setup_errors.into_iter().chain(errors) {
+            eprintln!("{}", error);
+        }
+        print!("{}", report);
+        println!(
+            "Checked {} values, {} anomalies",
+            VALUE_COUNT.load(Ordering::SeqCst),
+            report.len()
+        );
+        return Ok(());
+    }
+
     let (tx, rx) = mpsc::channel::<()>(1);
 
     let focus: Arc<RwLock<Focus>> = Arc::new(RwLock::new(Focus::Main));
     let static_menu_selection: Arc<StaticSelection> = Arc::new(StaticSelection::default());
+    let (offline_sources, mut setup_errors) = offline_source::from_matches(&matches);
+    *static_menu_selection.selected_sources.write() = SelectedSources::new(offline_sources);
+    let (search_options, option_errors) = offline_source::search_options(&matches);
+    *static_menu_selection.search_options.write() = search_options;
+    setup_errors.extend(option_errors);
+    static_menu_selection
+        .run_errors
+        .lock()
+        .extend(setup_errors.iter().cloned());
+    *static_menu_selection.setup_errors.lock() = setup_errors;
     let stop: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
     let static_menu_selection_ = static_menu_selection.to_owned();
     let focus_ = focus.to_owned();
@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+
+use crate::registry_source::{KeyInfo, RegistrySource, RegistryValue};
+
+struct MemoryKey {
+    name: String,
+    subkeys: BTreeMap<String, usize>, //lowercase name -> node index
+    values: Vec<(String, RegistryValue)>,
+    info: KeyInfo,
+    annotation: Option<String>,
+}
+
+impl MemoryKey {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            subkeys: BTreeMap::new(),
+            values: Vec::new(),
+            info: KeyInfo::default(),
+            annotation: None,
+        }
+    }
+}
+
+/// In-memory registry tree, key and value names are matched case insensitively like the real registry.
+/// Deleted keys are unlinked from their parent and left in the arena.
+#[derive(Default)]
+pub struct MemorySource {
+    roots: BTreeMap<String, usize>,
+    nodes: Vec<MemoryKey>,
+    label: Option<String>,
+}
+
+/// Whether two value names match, folding case the same way as key names.
+fn same_name(left: &str, right: &str) -> bool {
+    left.to_lowercase() == right.to_lowercase()
+}
+
+impl MemorySource {
+    fn find(&self, root: &str, path: &str) -> Option<usize> {
+        let mut index = *self.roots.get(&root.to_lowercase())?;
+        for component in path.split('\\').filter(|component| !component.is_empty()) {
+            index = *self.nodes[index].subkeys.get(&component.to_lowercase())?;
+        }
+        Some(index)
+    }
+
+    /// Creates the key and any missing parents, returns the node index.
+    fn create(&mut self, root: &str, path: &str) -> usize {
+        let mut index = match self.roots.get(&root.to_lowercase()) {
+            Some(index) => *index,
+            None => {
+                self.nodes.push(MemoryKey::new(root.to_string()));
+                let index = self.nodes.len() - 1;
+                self.roots.insert(root.to_lowercase(), index);
+                index
+            }
+        };
+        for component in path.split('\\').filter(|component| !component.is_empty()) {
+            let lowercase = component.to_lowercase();
+            index = match self.nodes[index].subkeys.get(&lowercase) {
+                Some(child) => *child,
+                None => {
+                    self.nodes.push(MemoryKey::new(component.to_string()));
+                    let child = self.nodes.len() - 1;
+                    self.nodes[index].subkeys.insert(lowercase, child);
+                    self.nodes[index].info.subkey_count += 1;
+                    child
+                }
+            };
+        }
+        index
+    }
+
+    pub fn insert_key(&mut self, root: &str, path: &str) {
+        let _ = self.create(root, path);
+    }
+
+    pub fn set_key_info(&mut self, root: &str, path: &str, info: KeyInfo) {
+        let index = self.create(root, path);
+        let node = &mut self.nodes[index];
+        node.info = KeyInfo {
+            subkey_count: node.subkeys.len() as u32,
+            value_count: node.values.len() as u32,
+            max_value_len: node.info.max_value_len,
+            ..info
+        };
+    }
+
+    /// Note shown alongside results from this key, eg. where it was recovered from.
+    pub fn set_annotation(&mut self, root: &str, path: &str, annotation: &str) {
+        let index = self.create(root, path);
+        self.nodes[index].annotation = Some(annotation.to_string());
+    }
+
+    /// Note shown alongside results from every key, eg. the file they were loaded from.
+    pub fn set_label(&mut self, label: &str) {
+        self.label = Some(label.to_string());
+    }
+
+    pub fn set_value(&mut self, root: &str, path: &str, name: &str, value: RegistryValue) {
+        let index = self.create(root, path);
+        let node = &mut self.nodes[index];
+        node.info.max_value_len = node.info.max_value_len.max(value.bytes.len() as u32);
+        match node
+            .values
+            .iter_mut()
+            .find(|(existing, _)| same_name(existing, name))
+        {
+            Some((_, existing)) => *existing = value,
+            None => {
+                node.values.push((name.to_string(), value));
+                node.info.value_count += 1;
+            }
+        }
+    }
+
+    /// Returns false if the key didn't exist.
+    pub fn delete_key(&mut self, root: &str, path: &str) -> bool {
+        let (parent_path, name) = match path.rsplit_once('\\') {
+            Some((parent_path, name)) => (parent_path, name),
+            None => ("", path),
+        };
+        if name.is_empty() {
+            return self.roots.remove(&root.to_lowercase()).is_some();
+        }
+        let parent = match self.find(root, parent_path) {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let node = &mut self.nodes[parent];
+        if node.subkeys.remove(&name.to_lowercase()).is_some() {
+            node.info.subkey_count -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns false if the value didn't exist.
+    pub fn delete_value(&mut self, root: &str, path: &str, name: &str) -> bool {
+        let index = match self.find(root, path) {
+            Some(index) => index,
+            None => return false,
+        };
+        let node = &mut self.nodes[index];
+        let len = node.values.len();
+        node.values
+            .retain(|(existing, _)| !same_name(existing, name));
+        node.info.value_count = node.values.len() as u32;
+        node.values.len() != len
+    }
+}
+
+impl RegistrySource for MemorySource {
+    type Key = usize;
+
+    fn roots(&self) -> Vec<String> {
+        self.roots
+            .values()
+            .map(|index| self.nodes[*index].name.to_owned())
+            .collect()
+    }
+
+    fn open_key(&self, root: &str, path: &str) -> Result<usize, String> {
+        self.find(root, path)
+            .ok_or_else(|| "The system cannot find the file specified.".to_string())
+    }
+
+    fn enum_keys(&self, key: &usize) -> Vec<Result<String, String>> {
+        self.nodes[*key]
+            .subkeys
+            .values()
+            .map(|index| Ok(self.nodes[*index].name.to_owned()))
+            .collect()
+    }
+
+    fn enum_values(&self, key: &usize) -> Vec<Result<(String, RegistryValue), String>> {
+        self.nodes[*key]
+            .values
+            .iter()
+            .map(|(name, value)| Ok((name.to_owned(), value.to_owned())))
+            .collect()
+    }
+
+    fn key_info(&self, key: &usize) -> Result<KeyInfo, String> {
+        Ok(self.nodes[*key].info.to_owned())
+    }
+
+    fn annotation(&self, key: &usize) -> Option<String> {
+        self.nodes[*key].annotation.to_owned()
+    }
+
+    fn label(&self) -> Option<String> {
+        self.label.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_every_key() {
+        let mut source = MemorySource::default();
+        source.insert_key("HKEY_CURRENT_USER", "Software\\Run");
+        source.set_annotation("HKEY_CURRENT_USER", "Software\\Gone", "deleted");
+        assert_eq!(source.label(), None);
+        source.set_label("export.reg");
+        assert_eq!(source.roots(), vec!["HKEY_CURRENT_USER"]);
+        assert_eq!(source.label().as_deref(), Some("export.reg"));
+        let run = source
+            .open_key("HKEY_CURRENT_USER", "Software\\Run")
+            .unwrap();
+        assert_eq!(source.annotation(&run), None);
+        let gone = source
+            .open_key("HKEY_CURRENT_USER", "Software\\Gone")
+            .unwrap();
+        assert_eq!(source.annotation(&gone).as_deref(), Some("deleted"));
+    }
+
+    #[test]
+    fn keeps_counts_up_to_date() {
+        let mut source = MemorySource::default();
+        let value = RegistryValue {
+            vtype: crate::registry_source::RegType::REG_BINARY,
+            bytes: vec![0; 8],
+        };
+        source.set_value("X", "A", "v", value.to_owned());
+        source.set_value("X", "A", "V", value.to_owned());
+        source.set_value("X", "A", "Ärger", value.to_owned());
+        source.set_value("X", "A", "ärger", value);
+        source.insert_key("X", "A\\B");
+        let info = source
+            .key_info(&source.open_key("X", "A").unwrap())
+            .unwrap();
+        assert_eq!(
+            (info.subkey_count, info.value_count, info.max_value_len),
+            (1, 2, 8)
+        );
+        assert!(source.delete_key("X", "A\\B"));
+        assert!(!source.delete_key("X", "A\\B"));
+        assert!(source.delete_value("X", "A", "v"));
+        assert!(source.delete_value("X", "A", "ÄRGER"));
+        let info = source
+            .key_info(&source.open_key("X", "A").unwrap())
+            .unwrap();
+        assert_eq!((info.subkey_count, info.value_count), (0, 0));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::reg_data::RegData;
+
+/// A test on the number held by a REG_DWORD, REG_DWORD_BIG_ENDIAN or REG_QWORD value, eg.
+/// `= 0x1`, `>= 4096`, `in 1..=3` or `& 0x4`. Numbers are decimal or `0x` hex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NumberPredicate {
+    Equal(u64),
+    NotEqual(u64),
+    Less(u64),
+    LessOrEqual(u64),
+    Greater(u64),
+    GreaterOrEqual(u64),
+    /// Inclusive at both ends, `in 1..3` is stored as 1 to 2.
+    InRange(u64, u64),
+    /// Any of the bits in the mask are set.
+    AnyBits(u64),
+}
+
+const OPERATORS: [&str; 8] = ["!=", ">=", "<=", "==", "=", "<", ">", "&"];
+
+pub fn parse_number(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("\"{}\" is not a number", text))
+}
+
+impl NumberPredicate {
+    /// Whether `term` is written as a numeric test rather than text to look for. An operator
+    /// only makes one when a number follows it, so `<html` and `&amp` stay text.
+    pub fn is_predicate(term: &str) -> bool {
+        let term = term.trim_start();
+        OPERATORS
+            .iter()
+            .find(|operator| term.starts_with(**operator))
+            .is_some_and(|operator| parse_number(&term[operator.len()..]).is_ok())
+            || term
+                .strip_prefix("in ")
+                .is_some_and(|range| range.contains(".."))
+    }
+
+    pub fn parse(term: &str) -> Result<Self, String> {
+        let term = term.trim();
+        if let Some(range) = term.strip_prefix("in ") {
+            let (start, end) = range
+                .split_once("..")
+                .ok_or_else(|| format!("\"{}\" is not a range, expected start..end", range))?;
+            let start = parse_number(start)?;
+            let end = match end.strip_prefix('=') {
+                Some(end) => parse_number(end)?,
+                None => parse_number(end)?
+                    .checked_sub(1)
+                    .ok_or_else(|| format!("\"{}\" is an empty range", range))?,
+            };
+            if start > end {
+                return Err(format!("\"{}\" is an empty range", range));
+            }
+            return Ok(Self::InRange(start, end));
+        }
+        let operator = OPERATORS
+            .iter()
+            .find(|operator| term.starts_with(**operator))
+            .ok_or_else(|| format!("\"{}\" is not a numeric test", term))?;
+        let number = parse_number(&term[operator.len()..])?;
+        Ok(match *operator {
+            "!=" => Self::NotEqual(number),
+            ">=" => Self::GreaterOrEqual(number),
+            "<=" => Self::LessOrEqual(number),
+            "<" => Self::Less(number),
+            ">" => Self::Greater(number),
+            "&" => Self::AnyBits(number),
+            _ => Self::Equal(number),
+        })
+    }
+
+    pub fn matches(&self, number: u64) -> bool {
+        match *self {
+            Self::Equal(other) => number == other,
+            Self::NotEqual(other) => number != other,
+            Self::Less(other) => number < other,
+            Self::LessOrEqual(other) => number <= other,
+            Self::Greater(other) => number > other,
+            Self::GreaterOrEqual(other) => number >= other,
+            Self::InRange(start, end) => (start..=end).contains(&number),
+            Self::AnyBits(mask) => number & mask != 0,
+        }
+    }
+
+    /// Values that don't hold a number never match.
+    pub fn matches_data(&self, data: &RegData) -> bool {
+        match *data {
+            RegData::Dword(number) | RegData::DwordBigEndian(number) => self.matches(number as u64),
+            RegData::Qword(number) => self.matches(number),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_term::SearchTerm;
+
+    #[test]
+    fn parses_comparisons_ranges_and_masks() {
+        for (term, predicate) in [
+            ("= 0x1", NumberPredicate::Equal(1)),
+            ("==16", NumberPredicate::Equal(16)),
+            (">= 4096", NumberPredicate::GreaterOrEqual(4096)),
+            ("!= 0", NumberPredicate::NotEqual(0)),
+            ("< 0XFF", NumberPredicate::Less(255)),
+            ("in 1..=3", NumberPredicate::InRange(1, 3)),
+            ("in 0x10..0x20", NumberPredicate::InRange(16, 31)),
+            ("& 0x4", NumberPredicate::AnyBits(4)),
+        ] {
+            assert!(NumberPredicate::is_predicate(term), "{}", term);
+            assert_eq!(NumberPredicate::parse(term).unwrap(), predicate, "{}", term);
+        }
+        for term in ["= ", ">= 12abc", "in 3..1", "in 1..0", "& -1"] {
+            assert!(NumberPredicate::parse(term).is_err(), "{}", term);
+        }
+        assert!(!NumberPredicate::is_predicate("in the registry"));
+        assert!(!NumberPredicate::is_predicate("0x10"));
+        for term in ["<html", "&amp", "=C:\\", ">= 12abc", "== "] {
+            assert!(!NumberPredicate::is_predicate(term), "{}", term);
+            assert_eq!(
+                SearchTerm::parse(term),
+                Ok(SearchTerm::Text(term.to_lowercase())),
+                "{}",
+                term
+            );
+        }
+    }
+
+    #[test]
+    fn tests_typed_numbers_only() {
+        let flags = NumberPredicate::parse("& 0x4").unwrap();
+        assert!(flags.matches_data(&RegData::Dword(0x6)));
+        assert!(!flags.matches_data(&RegData::Dword(0x3)));
+        assert!(NumberPredicate::parse(">= 4096")
+            .unwrap()
+            .matches_data(&RegData::Qword(u64::MAX)));
+        assert!(!NumberPredicate::parse("= 1")
+            .unwrap()
+            .matches_data(&RegData::Sz("1".into())));
+    }
+}
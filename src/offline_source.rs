@@ -0,0 +1,393 @@
+use std::{fmt, path::PathBuf};
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use tracing::{error, info, warn};
+
+use crate::{
+    carve,
+    environment::Environment,
+    hive_source, image_discovery,
+    key_filter::KeyFilter,
+    number_predicate::NumberPredicate,
+    policy_file,
+    suppression::{self, SuppressionList},
+    timestamp::DateRange,
+    worker_manager::SearchOptions,
+};
+
+/// A file given on the command line to be searched in place of the live registry.
+#[derive(Debug, Clone)]
+pub enum OfflineSource {
+    Hive {
+        path: PathBuf,
+        root: String,
+        recover_deleted: bool,
+    },
+    RegFile {
+        path: PathBuf,
+    },
+    PolicyFile {
+        path: PathBuf,
+        root: String,
+    },
+    Snapshot {
+        path: PathBuf,
+    },
+    /// A hive carved out of a raw file, `offset` is where `carve` found it.
+    Carved {
+        path: PathBuf,
+        offset: u64,
+        root: String,
+        recover_deleted: bool,
+    },
+}
+
+impl fmt::Display for OfflineSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let file_name = |path: &PathBuf| {
+            path.file_name()
+                .map(|file_name| file_name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        };
+        match self {
+            Self::Hive { root, .. } | Self::Carved { root, .. } => write!(f, "{}", root),
+            Self::RegFile { path } | Self::Snapshot { path } => {
+                write!(f, "{}", file_name(path))
+            }
+            Self::PolicyFile { path, root } => write!(f, "{} ({})", root, file_name(path)),
+        }
+    }
+}
+
+/// Offline sources listed in the root selection pane, each can be toggled like a live root.
+#[derive(Default)]
+pub struct SelectedSources {
+    sources: Vec<(OfflineSource, bool)>,
+}
+
+impl SelectedSources {
+    pub fn new(sources: Vec<OfflineSource>) -> Self {
+        Self {
+            sources: sources.into_iter().map(|source| (source, true)).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn export_sources(&self) -> Vec<OfflineSource> {
+        self.sources
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(source, _)| source.to_owned())
+            .collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(OfflineSource, bool)> {
+        self.sources.iter()
+    }
+
+    pub fn toggle(&mut self, index: usize) {
+        if let Some((_, enabled)) = self.sources.get_mut(index) {
+            *enabled = !*enabled;
+        }
+    }
+}
+
+/// Splits `PATH=ROOT` into the path and an optional root override.
+fn path_and_root(value: &str) -> (PathBuf, Option<String>) {
+    match value.rsplit_once('=') {
+        Some((path, root)) if !root.is_empty() => (PathBuf::from(path), Some(root.to_string())),
+        _ => (PathBuf::from(value), None),
+    }
+}
+
+pub fn command() -> Command {
+    Command::new("windows_registry_search")
+        .about("Multi-threaded Windows Registry Search Tool")
+        .arg(
+            Arg::new("hive")
+                .long("hive")
+                .value_name("PATH[=ROOT]")
+                .action(ArgAction::Append)
+                .help("Search an offline regf hive, mounted under ROOT or a root based on its file name"),
+        )
+        .arg(
+            Arg::new("reg-file")
+                .long("reg-file")
+                .value_name("PATH")
+                .action(ArgAction::Append)
+                .help("Search a regedit export (.reg), REGEDIT5 or REGEDIT4"),
+        )
+        .arg(
+            Arg::new("policy-file")
+                .long("policy-file")
+                .value_name("PATH[=ROOT]")
+                .action(ArgAction::Append)
+                .help("Search a Group Policy Registry.pol, mounted under ROOT or HKLM/HKCU for Machine/User folders"),
+        )
+        .arg(
+            Arg::new("image")
+                .long("image")
+                .value_name("PATH")
+                .action(ArgAction::Append)
+                .help("Search every system and user hive found under the root of a mounted Windows volume"),
+        )
+        .arg(
+            Arg::new("carve")
+                .long("carve")
+                .value_name("PATH")
+                .action(ArgAction::Append)
+                .help("Search every hive, whole or partial, carved out of a raw disk image, memory dump or other file"),
+        )
+        .arg(
+            Arg::new("carve-to")
+                .long("carve-to")
+                .value_name("DIRECTORY")
+                .help("Write the hives found by --carve to DIRECTORY, named after the file and offset they came from"),
+        )
+        .arg(
+            Arg::new("snapshot")
+                .long("snapshot")
+                .value_name("PATH")
+                .action(ArgAction::Append)
+                .help("Search a snapshot written by --capture"),
+        )
+        .arg(
+            Arg::new("recover-deleted")
+                .long("recover-deleted")
+                .action(ArgAction::SetTrue)
+                .help("Also search keys and values recovered from free space in --hive, --image and --carve hives"),
+        )
+        .arg(
+            Arg::new("strings")
+                .long("strings")
+                .value_name("MIN_LENGTH")
+                .num_args(0..=1)
+                .default_missing_value("4")
+                .value_parser(clap::value_parser!(usize))
+                .help("Also match terms against ASCII and UTF-16 strings of at least MIN_LENGTH (4) characters pulled out of binary values"),
+        )
+        .arg(
+            Arg::new("environment")
+                .long("environment")
+                .value_name("PATH")
+                .action(ArgAction::Append)
+                .help("Also match REG_EXPAND_SZ data expanded with the variables in PATH, a file of NAME=VALUE lines or a SYSTEM, SOFTWARE or NTUSER.DAT hive, later files override earlier ones"),
+        )
+        .arg(
+            Arg::new("modified")
+                .long("modified")
+                .value_name("START..END")
+                .value_parser(DateRange::parse)
+                .help("Only list keys, and values of keys, last written between START and END, eg. 2024-01-01..2024-01-31 or \"2024-01-31 09:00..\", in UTC"),
+        )
+        .arg(
+            Arg::new("class")
+                .long("class")
+                .value_name("TEXT")
+                .help("Only list keys, and values of keys, whose class name contains TEXT"),
+        )
+        .arg(
+            Arg::new("subkeys")
+                .long("subkeys")
+                .value_name("TEST")
+                .value_parser(NumberPredicate::parse)
+                .help("Only list keys, and values of keys, whose number of subkeys passes TEST, eg. \">= 10\" or \"= 0\""),
+        )
+        .arg(
+            Arg::new("values")
+                .long("values")
+                .value_name("TEST")
+                .value_parser(NumberPredicate::parse)
+                .help("Only list keys, and values of keys, whose number of values passes TEST"),
+        )
+        .arg(
+            Arg::new("max-data")
+                .long("max-data")
+                .value_name("TEST")
+                .value_parser(NumberPredicate::parse)
+                .help("Only list keys, and values of keys, whose largest value data in bytes passes TEST, eg. \"> 4096\""),
+        )
+        .arg(
+            Arg::new("suppressions")
+                .long("suppressions")
+                .value_name("PATH")
+                .help("Leave results from the path prefixes and value terms listed in PATH out of every run, instead of the suppressions.txt in the config directory"),
+        )
+        .arg(
+            Arg::new("capture")
+                .long("capture")
+                .value_name("PATH")
+                .help("Walk the given sources, or the live registry without any, once and save every key and value to a snapshot at PATH instead of opening the search"),
+        )
+        .arg(
+            Arg::new("lint")
+                .long("lint")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("capture")
+                .help("Walk the given sources, or the live registry without any, and list the values whose data doesn't fit their type by what's wrong with it instead of opening the search"),
+        )
+}
+
+/// The sources along with the errors from carving them out of their files.
+pub fn from_matches(matches: &ArgMatches) -> (Vec<OfflineSource>, Vec<String>) {
+    let mut offline_sources = Vec::new();
+    let mut errors = Vec::new();
+    let recover_deleted = matches.get_flag("recover-deleted");
+    if let Some(values) = matches.get_many::<String>("hive") {
+        for value in values {
+            let (path, root) = path_and_root(value);
+            offline_sources.push(OfflineSource::Hive {
+                root: root.unwrap_or_else(|| hive_source::default_root_name(&path)),
+                path,
+                recover_deleted,
+            });
+        }
+    }
+    if let Some(values) = matches.get_many::<String>("image") {
+        for value in values {
+            let hives = image_discovery::discover(&PathBuf::from(value));
+            if hives.is_empty() {
+                warn!("{}: no hives found", value);
+            }
+            for hive in hives {
+                info!("{}: found {}", hive.path.display(), hive.root);
+                offline_sources.push(OfflineSource::Hive {
+                    path: hive.path,
+                    root: hive.root,
+                    recover_deleted,
+                });
+            }
+        }
+    }
+    if let Some(values) = matches.get_many::<String>("carve") {
+        let carve_to = matches.get_one::<String>("carve-to").map(PathBuf::from);
+        for value in values {
+            let path = PathBuf::from(value);
+            let carved = match carve::carve(&path) {
+                Ok(carved) => carved,
+                Err(err) => {
+                    error!("{}", err);
+                    errors.push(format!("Failed to carve: {}", err));
+                    continue;
+                }
+            };
+            if carved.is_empty() {
+                warn!("{}: no hives found", value);
+            }
+            for hive in carved {
+                let root = carve::root_name(&path, &hive);
+                info!(
+                    "{}: carved {} hive at 0x{:X}",
+                    path.display(),
+                    hive.kind,
+                    hive.offset
+                );
+                for note in hive.notes.iter() {
+                    info!("{}: 0x{:X}: {}", path.display(), hive.offset, note);
+                }
+                //written hives are searched from the copy, which starts with its base block
+                let (path, offset) = match carve_to.as_ref() {
+                    Some(directory) => match carve::write(&path, &hive, directory) {
+                        Ok(written) => (written, 0),
+                        Err(err) => {
+                            error!("{}", err);
+                            errors.push(format!("Failed to write carved hive: {}", err));
+                            (path.to_owned(), hive.offset)
+                        }
+                    },
+                    None => (path.to_owned(), hive.offset),
+                };
+                offline_sources.push(OfflineSource::Carved {
+                    path,
+                    offset,
+                    root,
+                    recover_deleted,
+                });
+            }
+        }
+    }
+    if let Some(values) = matches.get_many::<String>("reg-file") {
+        for value in values {
+            offline_sources.push(OfflineSource::RegFile {
+                path: PathBuf::from(value),
+            });
+        }
+    }
+    if let Some(values) = matches.get_many::<String>("policy-file") {
+        for value in values {
+            let (path, root) = path_and_root(value);
+            offline_sources.push(OfflineSource::PolicyFile {
+                root: root.unwrap_or_else(|| policy_file::default_root_name(&path)),
+                path,
+            });
+        }
+    }
+    if let Some(values) = matches.get_many::<String>("snapshot") {
+        for value in values {
+            offline_sources.push(OfflineSource::Snapshot {
+                path: PathBuf::from(value),
+            });
+        }
+    }
+    (offline_sources, errors)
+}
+
+/// The options along with the files they name that couldn't be loaded.
+pub fn search_options(matches: &ArgMatches) -> (SearchOptions, Vec<String>) {
+    let mut errors = Vec::new();
+    let environment = matches.get_many::<String>("environment").map(|values| {
+        let mut environment = Environment::default();
+        for value in values {
+            match Environment::load(&PathBuf::from(value)) {
+                Ok(profile) => {
+                    info!("{}: {} environment variables", value, profile.len());
+                    environment.extend(profile);
+                }
+                Err(err) => {
+                    error!("{}", err);
+                    errors.push(format!("Failed to load environment: {}", err));
+                }
+            }
+        }
+        environment
+    });
+    let key_filter = KeyFilter {
+        modified: matches.get_one::<DateRange>("modified").cloned(),
+        class_name: matches
+            .get_one::<String>("class")
+            .map(|class_name| class_name.to_lowercase()),
+        subkeys: matches.get_one::<NumberPredicate>("subkeys").cloned(),
+        values: matches.get_one::<NumberPredicate>("values").cloned(),
+        max_data: matches.get_one::<NumberPredicate>("max-data").cloned(),
+    };
+    let suppressions = matches
+        .get_one::<String>("suppressions")
+        .map(PathBuf::from)
+        .or_else(suppression::default_path)
+        .and_then(|path| match SuppressionList::load(&path) {
+            Ok(suppressions) => {
+                info!("{}: {} suppressions", path.display(), suppressions.len());
+                Some(suppressions)
+            }
+            Err(err) => {
+                error!("{}", err);
+                errors.push(format!("Failed to load suppressions: {}", err));
+                None
+            }
+        })
+        .unwrap_or_default();
+    let options = SearchOptions {
+        string_min_length: matches.get_one::<usize>("strings").copied(),
+        environment,
+        key_filter,
+        suppressions,
+    };
+    (options, errors)
+}
@@ -0,0 +1,243 @@
+/// Prefix that turns a term into a glob matched against full key and value paths.
+pub const PATH_PREFIX: &str = "path:";
+
+/// Root names as they're often abbreviated, a glob component that is one of these stands for
+/// the full name.
+const ROOT_ABBREVIATIONS: [(&str, &str); 5] = [
+    ("HKLM", "HKEY_LOCAL_MACHINE"),
+    ("HKCU", "HKEY_CURRENT_USER"),
+    ("HKU", "HKEY_USERS"),
+    ("HKCR", "HKEY_CLASSES_ROOT"),
+    ("HKCC", "HKEY_CURRENT_CONFIG"),
+];
+
+/// The full root name when `component` abbreviates one, otherwise `component` itself.
+pub fn expand_root(component: &str) -> &str {
+    ROOT_ABBREVIATIONS
+        .iter()
+        .find(|(abbreviation, _)| abbreviation.eq_ignore_ascii_case(component))
+        .map_or(component, |(_, root)| root)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// Stored lowercased.
+    Char(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnyChars,
+    /// `[a-z_]`, or `[!a-z_]` when negated. Ranges are stored lowercased.
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Token {
+    fn matches(&self, ch: char) -> bool {
+        match self {
+            Self::Char(expected) => *expected == ch,
+            Self::AnyChar => true,
+            Self::AnyChars => true,
+            Self::Class { negated, ranges } => {
+                ranges
+                    .iter()
+                    .any(|(start, end)| (*start..=*end).contains(&ch))
+                    != *negated
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// `**`, any number of components including none.
+    AnyDepth,
+    Component(Vec<Token>),
+}
+
+fn parse_component(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().flat_map(char::to_lowercase).peekable();
+    while let Some(ch) = chars.next() {
+        tokens.push(match ch {
+            '?' => Token::AnyChar,
+            '*' => Token::AnyChars,
+            '[' => {
+                let negated = chars.next_if(|ch| *ch == '!' || *ch == '^').is_some();
+                let mut ranges = Vec::new();
+                loop {
+                    let start = match chars.next() {
+                        Some(']') if !ranges.is_empty() => break,
+                        Some(start) => start,
+                        None => return Err(format!("\"{}\" has an unclosed [", text)),
+                    };
+                    let end = match chars.next_if_eq(&'-') {
+                        Some(_) => match chars.next_if(|ch| *ch != ']') {
+                            Some(end) => end,
+                            None => {
+                                ranges.push(('-', '-'));
+                                start
+                            }
+                        },
+                        None => start,
+                    };
+                    ranges.push((start, end));
+                }
+                Token::Class { negated, ranges }
+            }
+            ch => Token::Char(ch),
+        });
+    }
+    Ok(tokens)
+}
+
+/// Matches one component, going back to just after the last `*` seen and letting it take one
+/// more character whenever the tokens after it fail.
+fn component_matches(tokens: &[Token], text: &[char]) -> bool {
+    let (mut token, mut position) = (0, 0);
+    let mut last_star = None;
+    while position < text.len() {
+        match tokens.get(token) {
+            Some(Token::AnyChars) => {
+                last_star = Some((token, position));
+                token += 1;
+            }
+            Some(expected) if expected.matches(text[position]) => {
+                token += 1;
+                position += 1;
+            }
+            _ => match last_star {
+                Some((star, star_position)) => {
+                    last_star = Some((star, star_position + 1));
+                    token = star + 1;
+                    position = star_position + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[token..]
+        .iter()
+        .all(|token| *token == Token::AnyChars)
+}
+
+/// A path with `*` and `?` wildcards, `[...]` classes and `**` for any number of keys, eg.
+/// `HKLM\Software\*\CurrentVersion\Run`, matched case insensitively component by component
+/// against `ROOT\path` for keys and `ROOT\path\name` for values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathGlob {
+    segments: Vec<Segment>,
+}
+
+impl PathGlob {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let segments = text
+            .split('\\')
+            .filter(|component| !component.is_empty())
+            .map(|component| match expand_root(component) {
+                "**" => Ok(Segment::AnyDepth),
+                component => parse_component(component).map(Segment::Component),
+            })
+            .collect::<Result<Vec<Segment>, String>>()?;
+        if segments.is_empty() {
+            return Err("Path pattern is empty".into());
+        }
+        Ok(Self { segments })
+    }
+
+    /// Adds the segments `**` lets the ones in `states` skip to.
+    fn closure(&self, mut states: Vec<usize>) -> Vec<usize> {
+        let mut index = 0;
+        while index < states.len() {
+            let state = states[index];
+            if self.segments.get(state) == Some(&Segment::AnyDepth)
+                && !states.contains(&(state + 1))
+            {
+                states.push(state + 1);
+            }
+            index += 1;
+        }
+        states
+    }
+
+    /// Segments the next component could be matched against after `path`, past the end when
+    /// `path` matches in full. Empty when nothing starting with `path` can match.
+    fn states(&self, path: &str) -> Vec<usize> {
+        let mut states = self.closure(vec![0]);
+        for component in path.split('\\').filter(|component| !component.is_empty()) {
+            let component = component.to_lowercase().chars().collect::<Vec<char>>();
+            let mut next = Vec::new();
+            for state in states {
+                let advanced = match self.segments.get(state) {
+                    Some(Segment::AnyDepth) => Some(state),
+                    Some(Segment::Component(tokens)) if component_matches(tokens, &component) => {
+                        Some(state + 1)
+                    }
+                    _ => None,
+                };
+                if let Some(advanced) = advanced.filter(|advanced| !next.contains(advanced)) {
+                    next.push(advanced);
+                }
+            }
+            states = self.closure(next);
+            if states.is_empty() {
+                break;
+            }
+        }
+        states
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        self.states(path).contains(&self.segments.len())
+    }
+
+    /// Whether `path`, or a key or value below it, could match.
+    pub fn could_match(&self, path: &str) -> bool {
+        !self.states(path).is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_components_classes_and_any_depth() {
+        let run = PathGlob::parse("HKLM\\Software\\*\\CurrentVersion\\Run").unwrap();
+        assert!(run.matches("hkey_local_machine\\SOFTWARE\\WOW6432Node\\CurrentVersion\\Run"));
+        assert!(
+            !run.matches("HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run")
+        );
+        assert!(run.could_match("HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft"));
+        assert!(!run.could_match("HKEY_LOCAL_MACHINE\\SYSTEM"));
+        assert!(!run.could_match("HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows"));
+
+        let deep = PathGlob::parse("HKLM\\**\\Run?\\[a-c!]*").unwrap();
+        for path in [
+            "HKEY_LOCAL_MACHINE\\RunX\\alpha",
+            "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Run2\\Cmd",
+            "HKEY_LOCAL_MACHINE\\A\\B\\Run1\\!",
+        ] {
+            assert!(deep.matches(path), "{}", path);
+        }
+        assert!(!deep.matches("HKEY_LOCAL_MACHINE\\Run\\alpha"));
+        assert!(!deep.matches("HKEY_LOCAL_MACHINE\\RunOnce\\Cmd"));
+        assert!(!deep.matches("HKEY_LOCAL_MACHINE\\RunX\\delta"));
+        assert!(deep.could_match("HKEY_LOCAL_MACHINE\\Anything\\At\\All"));
+        assert!(!deep.could_match("HKEY_USERS"));
+
+        let negated = PathGlob::parse("HKU\\[!.]*").unwrap();
+        assert!(negated.matches("HKEY_USERS\\S-1-5-18"));
+        assert!(!negated.matches("HKEY_USERS\\.DEFAULT"));
+        let stars = PathGlob::parse("HKCU\\*a*b?*").unwrap();
+        assert!(stars.matches("HKEY_CURRENT_USER\\xxaxxbyz"));
+        assert!(stars.matches("HKEY_CURRENT_USER\\abc"));
+        assert!(!stars.matches("HKEY_CURRENT_USER\\xxaxxb"));
+        assert!(!stars.matches(&format!("HKEY_CURRENT_USER\\{}", "a".repeat(200))));
+        for pattern in ["", "\\\\", "HKLM\\[a-"] {
+            assert!(PathGlob::parse(pattern).is_err(), "{}", pattern);
+        }
+    }
+}
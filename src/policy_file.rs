@@ -0,0 +1,361 @@
+use std::{fmt, fs, path::Path};
+
+use crate::{
+    hive::read_u32,
+    memory_source::MemorySource,
+    reg_data::decode_utf16,
+    reg_file::to_utf16_bytes,
+    registry_source::{RegType, RegistryValue},
+};
+
+const PREG_SIGNATURE: &[u8] = b"PReg";
+const PREG_VERSION: u32 = 1;
+
+/// Special value names in Registry.pol that act on the key instead of setting a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyDirective {
+    DeleteValue(String),       //**del.<name>
+    DeleteAllValues,           //**delvals.
+    DeleteValues(Vec<String>), //**DeleteValues, data is a ; separated list
+    DeleteKeys(Vec<String>),   //**DeleteKeys, data is a ; separated list
+    SecureKey(bool),           //**SecureKey, 1 to restrict the key's ACL to admins and system
+}
+
+impl fmt::Display for PolicyDirective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let quoted = |names: &Vec<String>| {
+            names
+                .iter()
+                .map(|name| format!("\"{}\"", name))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        match self {
+            Self::DeleteValue(name) => write!(f, "Delete value \"{}\"", name),
+            Self::DeleteAllValues => write!(f, "Delete all values"),
+            Self::DeleteValues(names) => write!(f, "Delete values {}", quoted(names)),
+            Self::DeleteKeys(names) => write!(f, "Delete keys {}", quoted(names)),
+            Self::SecureKey(true) => write!(f, "Secure key"),
+            Self::SecureKey(false) => write!(f, "Unsecure key"),
+        }
+    }
+}
+
+/// Where the policy applies, based on the `Machine` or `User` folder the file sits in.
+pub fn default_root_name(path: &Path) -> String {
+    let folder = path
+        .parent()
+        .and_then(|parent| parent.file_name())
+        .map(|folder| folder.to_string_lossy().to_uppercase())
+        .unwrap_or_default();
+    match folder.as_str() {
+        "MACHINE" => "HKEY_LOCAL_MACHINE".into(),
+        "USER" => "HKEY_CURRENT_USER".into(),
+        _ => path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn split_names(data: &[u8]) -> Vec<String> {
+    decode_utf16(data)
+        .split(';')
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_string())
+        .collect()
+}
+
+fn parse_directive(name: &str, data: &[u8]) -> Option<PolicyDirective> {
+    let lowercase = name.to_lowercase();
+    if lowercase == "**delvals." {
+        Some(PolicyDirective::DeleteAllValues)
+    } else if lowercase.starts_with("**del.") {
+        Some(PolicyDirective::DeleteValue(
+            name.get("**del.".len()..).unwrap_or_default().to_string(),
+        ))
+    } else if lowercase == "**deletevalues" {
+        Some(PolicyDirective::DeleteValues(split_names(data)))
+    } else if lowercase == "**deletekeys" {
+        Some(PolicyDirective::DeleteKeys(split_names(data)))
+    } else if lowercase == "**securekey" {
+        Some(PolicyDirective::SecureKey(
+            read_u32(data, 0).unwrap_or(0) != 0,
+        ))
+    } else {
+        None
+    }
+}
+
+/// Cursor over the UTF-16 record syntax, `[key;value;type;size;data]`.
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn expect(&mut self, ch: char) -> Result<(), String> {
+        match self.data.get(self.offset..self.offset + 2) {
+            Some(unit) if u16::from_le_bytes([unit[0], unit[1]]) == ch as u16 => {
+                self.offset += 2;
+                Ok(())
+            }
+            _ => Err(format!("Expected '{}' at offset 0x{:X}", ch, self.offset)),
+        }
+    }
+
+    /// Reads a NUL terminated string, the terminator is optional before the ';'.
+    fn string(&mut self) -> Result<String, String> {
+        let mut units = Vec::new();
+        loop {
+            let unit = match self.data.get(self.offset..self.offset + 2) {
+                Some(unit) => u16::from_le_bytes([unit[0], unit[1]]),
+                None => return Err("Record is truncated".into()),
+            };
+            if unit == ';' as u16 {
+                break;
+            }
+            self.offset += 2;
+            if unit == 0 {
+                break;
+            }
+            units.push(unit);
+        }
+        Ok(String::from_utf16_lossy(&units))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let value = read_u32(self.data, self.offset).ok_or("Record is truncated")?;
+        self.offset += 4;
+        Ok(value)
+    }
+
+    fn record(&mut self) -> Result<(String, String, u32, Vec<u8>), String> {
+        self.expect('[')?;
+        let key = self.string()?;
+        self.expect(';')?;
+        let name = self.string()?;
+        self.expect(';')?;
+        let vtype = self.u32()?;
+        self.expect(';')?;
+        let size = self.u32()? as usize;
+        self.expect(';')?;
+        let data = self.bytes(size)?.to_vec();
+        self.expect(']')?;
+        Ok((key, name, vtype, data))
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .ok_or("Record data is truncated")?;
+        self.offset += len;
+        Ok(bytes)
+    }
+}
+
+/// Loads a Registry.pol file into `source` under `root`.
+/// Directives are added as REG_SZ values named after the directive, with a description as their
+/// data, whatever type the record had.
+pub fn load(path: &Path, root: &str, source: &mut MemorySource) -> Result<Vec<String>, String> {
+    let bytes = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    parse(&bytes, root, source).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+pub fn parse(data: &[u8], root: &str, source: &mut MemorySource) -> Result<Vec<String>, String> {
+    if data.get(0..4) != Some(PREG_SIGNATURE) {
+        return Err("Missing PReg signature".into());
+    }
+    if read_u32(data, 4) != Some(PREG_VERSION) {
+        return Err(format!(
+            "Unsupported PReg version {}",
+            read_u32(data, 4).unwrap_or(0)
+        ));
+    }
+
+    let mut warnings = Vec::new();
+    let mut reader = Reader { data, offset: 8 };
+    while reader.offset < data.len() {
+        let start = reader.offset;
+        let (key, name, vtype, bytes) = match reader.record() {
+            Ok(record) => record,
+            Err(err) => {
+                //the record lengths can't be trusted past this point
+                warnings.push(format!("Record at offset 0x{:X}: {}", start, err));
+                break;
+            }
+        };
+        let vtype = RegType::from_u32(vtype).unwrap_or(RegType::REG_NONE);
+        if name.is_empty() && bytes.is_empty() && vtype == RegType::REG_NONE {
+            source.insert_key(root, &key);
+        } else if let Some(directive) = parse_directive(&name, &bytes) {
+            source.set_value(
+                root,
+                &key,
+                &name,
+                RegistryValue {
+                    vtype: RegType::REG_SZ,
+                    bytes: to_utf16_bytes(&directive.to_string()),
+                },
+            );
+        } else {
+            source.set_value(root, &key, &name, RegistryValue { vtype, bytes });
+        }
+    }
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry_source::RegistrySource;
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn record(key: &str, name: &str, vtype: u32, data: &[u8]) -> Vec<u8> {
+        let mut record = utf16("[");
+        record.extend(to_utf16_bytes(key));
+        record.extend(utf16(";"));
+        record.extend(to_utf16_bytes(name));
+        record.extend(utf16(";"));
+        record.extend(vtype.to_le_bytes());
+        record.extend(utf16(";"));
+        record.extend((data.len() as u32).to_le_bytes());
+        record.extend(utf16(";"));
+        record.extend(data);
+        record.extend(utf16("]"));
+        record
+    }
+
+    fn policy(records: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = PREG_SIGNATURE.to_vec();
+        data.extend(PREG_VERSION.to_le_bytes());
+        data.extend(records.concat());
+        data
+    }
+
+    fn values(source: &MemorySource, path: &str) -> Vec<(String, RegistryValue)> {
+        let key = source.open_key("HKEY_LOCAL_MACHINE", path).unwrap();
+        source
+            .enum_values(&key)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn loads_keys_values_and_directives() {
+        let data = policy(&[
+            record("Software\\Policies\\Empty", "", 0, &[]),
+            record(
+                "Software\\Policies\\Test",
+                "Enabled",
+                4,
+                &1u32.to_le_bytes(),
+            ),
+            record(
+                "Software\\Policies\\Test",
+                "Path",
+                2,
+                &to_utf16_bytes("%TEMP%"),
+            ),
+            record("Software\\Policies\\Test", "**del.Old", 1, &[]),
+            record("Software\\Policies\\Test", "**delvals.", 1, &[]),
+            record(
+                "Software\\Policies\\Test",
+                "**DeleteValues",
+                1,
+                &to_utf16_bytes("Old;Older;"),
+            ),
+            record(
+                "Software\\Policies\\Test",
+                "**DeleteKeys",
+                1,
+                &to_utf16_bytes("A;B"),
+            ),
+            record(
+                "Software\\Policies\\Test",
+                "**SecureKey",
+                4,
+                &1u32.to_le_bytes(),
+            ),
+        ]);
+        let mut source = MemorySource::default();
+        let warnings = parse(&data, "HKEY_LOCAL_MACHINE", &mut source).unwrap();
+        assert!(warnings.is_empty());
+        assert!(source
+            .open_key("HKEY_LOCAL_MACHINE", "Software\\Policies\\Empty")
+            .is_ok());
+        let values = values(&source, "Software\\Policies\\Test");
+        let strings = values
+            .iter()
+            .skip(2)
+            .map(|(name, value)| (name.as_str(), decode_utf16(&value.bytes)))
+            .collect::<Vec<(&str, String)>>();
+        assert_eq!(
+            values[0].1,
+            RegistryValue {
+                vtype: RegType::REG_DWORD,
+                bytes: vec![1, 0, 0, 0],
+            }
+        );
+        assert_eq!(values[1].1.vtype, RegType::REG_EXPAND_SZ);
+        //**SecureKey is a REG_DWORD record, directives all become descriptions
+        assert!(values
+            .iter()
+            .skip(2)
+            .all(|(_, value)| value.vtype == RegType::REG_SZ));
+        assert_eq!(
+            strings,
+            vec![
+                ("**del.Old", "Delete value \"Old\"".to_string()),
+                ("**delvals.", "Delete all values".to_string()),
+                (
+                    "**DeleteValues",
+                    "Delete values \"Old\", \"Older\"".to_string()
+                ),
+                ("**DeleteKeys", "Delete keys \"A\", \"B\"".to_string()),
+                ("**SecureKey", "Secure key".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_records_before_a_truncated_one() {
+        let mut data = policy(&[record("Software\\A", "x", 4, &2u32.to_le_bytes())]);
+        let mut truncated = record("Software\\B", "y", 3, &[1, 2, 3, 4]);
+        truncated.truncate(truncated.len() - 6);
+        data.extend(truncated);
+        let mut source = MemorySource::default();
+        let warnings = parse(&data, "HKEY_LOCAL_MACHINE", &mut source).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("Record at offset 0x"));
+        assert_eq!(values(&source, "Software\\A").len(), 1);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let mut source = MemorySource::default();
+        assert!(parse(b"REGEDIT4", "X", &mut source).is_err());
+        let mut data = policy(&[]);
+        data[4] = 2;
+        assert_eq!(
+            parse(&data, "X", &mut source),
+            Err("Unsupported PReg version 2".to_string())
+        );
+    }
+
+    #[test]
+    fn default_root_names() {
+        for (path, root) in [
+            ("GroupPolicy/Machine/Registry.pol", "HKEY_LOCAL_MACHINE"),
+            ("GroupPolicy/user/Registry.pol", "HKEY_CURRENT_USER"),
+            ("elsewhere/Registry.pol", "Registry.pol"),
+        ] {
+            assert_eq!(default_root_name(Path::new(path)), root);
+        }
+    }
+}
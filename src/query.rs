@@ -0,0 +1,351 @@
+use std::{iter::Peekable, vec::IntoIter};
+
+use crate::{
+    path_glob::{PathGlob, PATH_PREFIX},
+    registry_source::RegType,
+    search_term::{Pattern, SearchTerm, TermOptions, REGEX_PREFIX},
+};
+
+/// Words that combine terms into a query, only recognised in uppercase so text terms like
+/// `not found` are left alone.
+const OPERATORS: [&str; 3] = ["AND", "OR", "NOT"];
+
+const NAME_PREFIX: &str = "name:";
+const DATA_PREFIX: &str = "data:";
+const TYPE_PREFIX: &str = "type:";
+
+/// Characters that make a `path:` qualifier a glob rather than text found in the path.
+const GLOB_CHARACTERS: [char; 3] = ['*', '?', '['];
+
+fn is_glob(path: &str) -> bool {
+    !path.starts_with(REGEX_PREFIX) && path.contains(GLOB_CHARACTERS)
+}
+
+/// What a qualified term is matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// The full `ROOT\path` of a key, or of the key a value is in.
+    Path,
+    Name,
+    /// Value data decoded as text, and as expanded or extracted when the options ask for it.
+    Data,
+}
+
+/// A term combining others with `AND`, `OR`, `NOT` and parentheses, and restricting them to
+/// one field with `path:`, `name:`, `data:` and `type:`, eg. `path:Services AND
+/// name:ImagePath AND data:temp NOT data:"C:\Windows"`. Terms next to each other without an
+/// operator are AND'ed, and `AND` binds tighter than `OR`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// An unqualified term, matched the way it would be on its own.
+    Term(SearchTerm),
+    /// Text or a `re:` regex in one field, a `SearchTerm::Text` or `SearchTerm::Regex`.
+    Field(Field, SearchTerm),
+    /// `path:` with wildcards, matching the full path of a key, or of the key a value is in.
+    Glob(PathGlob),
+    /// `type:REG_SZ` or `type:sz`, only values have one.
+    Type(RegType),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    /// `raw` is the word as typed, `text` has its quotes removed.
+    Word {
+        raw: String,
+        text: String,
+    },
+}
+
+impl Token {
+    fn is_operator(&self, operator: &str) -> bool {
+        matches!(self, Self::Word { raw, .. } if raw == operator)
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            ch if ch.is_whitespace() => {}
+            ch => {
+                let mut raw = String::new();
+                let mut text = String::new();
+                let mut next = Some(ch);
+                while let Some(ch) = next {
+                    raw.push(ch);
+                    if ch == '"' {
+                        loop {
+                            match chars.next() {
+                                Some('"') => break,
+                                Some(ch) => {
+                                    raw.push(ch);
+                                    text.push(ch);
+                                }
+                                None => return Err("Unclosed \"".into()),
+                            }
+                        }
+                        raw.push('"');
+                    } else {
+                        text.push(ch);
+                    }
+                    next = chars.next_if(|ch| !ch.is_whitespace() && *ch != '(' && *ch != ')');
+                }
+                tokens.push(Token::Word { raw, text });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Text or, with a `re:` prefix, a regex matched against one field.
+fn field_term(value: &str, options: &TermOptions) -> Result<SearchTerm, String> {
+    let options = options.unscoped();
+    match value.strip_prefix(REGEX_PREFIX) {
+        Some(pattern) => Pattern::with_options(pattern, &options).map(SearchTerm::Regex),
+        None if options == TermOptions::default() => Ok(SearchTerm::Text(value.to_lowercase())),
+        None => Pattern::with_options(&regex::escape(value), &options).map(SearchTerm::Regex),
+    }
+}
+
+fn parse_type(name: &str) -> Result<RegType, String> {
+    let upper = name.to_uppercase();
+    let upper = upper.strip_prefix("REG_").unwrap_or(&upper);
+    (0..=11)
+        .filter_map(RegType::from_u32)
+        .find(|vtype| format!("{:?}", vtype).strip_prefix("REG_") == Some(upper))
+        .ok_or_else(|| format!("\"{}\" is not a value type", name))
+}
+
+fn parse_word(raw: &str, text: &str, options: &TermOptions) -> Result<Query, String> {
+    let qualified = |prefix: &str| {
+        raw.starts_with(prefix).then(|| {
+            let value = &text[prefix.len()..];
+            match value.is_empty() {
+                true => Err(format!("{} needs something to match", prefix)),
+                false => Ok(value),
+            }
+        })
+    };
+    if let Some(value) = qualified(PATH_PREFIX) {
+        let value = value?;
+        return match is_glob(value) {
+            true => PathGlob::parse(value).map(Query::Glob),
+            false => field_term(value, options).map(|term| Query::Field(Field::Path, term)),
+        };
+    }
+    if let Some(value) = qualified(NAME_PREFIX) {
+        return field_term(value?, options).map(|term| Query::Field(Field::Name, term));
+    }
+    if let Some(value) = qualified(DATA_PREFIX) {
+        return field_term(value?, options).map(|term| Query::Field(Field::Data, term));
+    }
+    if let Some(value) = qualified(TYPE_PREFIX) {
+        return parse_type(value?).map(Query::Type);
+    }
+    SearchTerm::parse_term(text, options).map(Query::Term)
+}
+
+struct Parser<'a> {
+    tokens: Peekable<IntoIter<Token>>,
+    options: &'a TermOptions,
+}
+
+impl Parser<'_> {
+    fn or(&mut self) -> Result<Query, String> {
+        let mut query = self.and()?;
+        while self
+            .tokens
+            .next_if(|token| token.is_operator("OR"))
+            .is_some()
+        {
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, String> {
+        let mut query = self.not()?;
+        loop {
+            let explicit = self
+                .tokens
+                .next_if(|token| token.is_operator("AND"))
+                .is_some();
+            match self.tokens.peek() {
+                Some(token) if explicit || !(*token == Token::Close || token.is_operator("OR")) => {
+                    query = Query::And(Box::new(query), Box::new(self.not()?));
+                }
+                _ if explicit => return Err("AND needs a term after it".into()),
+                _ => return Ok(query),
+            }
+        }
+    }
+
+    fn not(&mut self) -> Result<Query, String> {
+        if self
+            .tokens
+            .next_if(|token| token.is_operator("NOT"))
+            .is_some()
+        {
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        match self.tokens.next() {
+            Some(Token::Open) => {
+                let query = self.or()?;
+                match self.tokens.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err("Unclosed (".into()),
+                }
+            }
+            Some(Token::Close) => Err("Unexpected )".into()),
+            Some(Token::Word { raw, .. }) if OPERATORS.contains(&raw.as_str()) => {
+                Err(format!("{} needs a term before it", raw))
+            }
+            Some(Token::Word { raw, text }) => parse_word(&raw, &text, self.options),
+            None => Err("Expected a term".into()),
+        }
+    }
+}
+
+impl Query {
+    /// Whether `term` uses an operator or a field qualifier, a `path:` without wildcards
+    /// included, and so is parsed as a query rather than a single term.
+    pub fn is_query(term: &str) -> bool {
+        term.split_whitespace()
+            .map(|word| word.trim_start_matches('('))
+            .any(|word| {
+                OPERATORS.contains(&word)
+                    || [NAME_PREFIX, DATA_PREFIX, TYPE_PREFIX]
+                        .iter()
+                        .any(|prefix| word.starts_with(prefix))
+                    || word
+                        .strip_prefix(PATH_PREFIX)
+                        .is_some_and(|path| !is_glob(path))
+            })
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::parse_with(text, &TermOptions::default())
+    }
+
+    /// Whether every term in the query is matched against the path alone, so it holds for all
+    /// of a key's values or none of them.
+    pub fn only_paths(&self) -> bool {
+        match self {
+            Self::Term(term) => matches!(term, SearchTerm::Path(_)),
+            Self::Field(field, _) => *field == Field::Path,
+            Self::Glob(_) => true,
+            Self::Type(_) => false,
+            Self::And(left, right) | Self::Or(left, right) => {
+                left.only_paths() && right.only_paths()
+            }
+            Self::Not(query) => query.only_paths(),
+        }
+    }
+
+    /// Parses a query whose text and `re:` terms are matched with `options`.
+    pub fn parse_with(text: &str, options: &TermOptions) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?.into_iter().peekable(),
+            options,
+        };
+        let query = parser.or()?;
+        match parser.tokens.next() {
+            Some(Token::Close) => Err("Unexpected )".into()),
+            Some(_) => Err("Expected AND or OR".into()),
+            None => Ok(query),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> SearchTerm {
+        SearchTerm::Text(text.into())
+    }
+
+    #[test]
+    fn parses_operators_qualifiers_and_quotes() {
+        let term = "path:Services AND name:ImagePath AND data:temp NOT data:\"C:\\Program Files\"";
+        assert!(Query::is_query(term));
+        assert_eq!(
+            Query::parse(term).unwrap(),
+            Query::And(
+                Box::new(Query::And(
+                    Box::new(Query::And(
+                        Box::new(Query::Field(Field::Path, text("services"))),
+                        Box::new(Query::Field(Field::Name, text("imagepath"))),
+                    )),
+                    Box::new(Query::Field(Field::Data, text("temp"))),
+                )),
+                Box::new(Query::Not(Box::new(Query::Field(
+                    Field::Data,
+                    text("c:\\program files")
+                )))),
+            )
+        );
+        assert_eq!(
+            Query::parse("(type:dword OR \"Run Once\") hex:4D").unwrap(),
+            Query::And(
+                Box::new(Query::Or(
+                    Box::new(Query::Type(RegType::REG_DWORD)),
+                    Box::new(Query::Term(text("run once"))),
+                )),
+                Box::new(Query::Term(
+                    SearchTerm::parse_term("hex:4D", &TermOptions::default()).unwrap()
+                )),
+            )
+        );
+        assert_eq!(
+            Query::parse("path:HKLM\\**\\Run OR \"AND\"").unwrap(),
+            Query::Or(
+                Box::new(Query::Glob(PathGlob::parse("HKLM\\**\\Run").unwrap())),
+                Box::new(Query::Term(text("and"))),
+            )
+        );
+
+        for term in [
+            "not found",
+            "(Default)",
+            "path:HKLM\\*\\Run",
+            "C:\\Program Files",
+        ] {
+            assert!(!Query::is_query(term), "{}", term);
+        }
+        for (term, only_paths) in [
+            ("path:Run OR path:HKLM\\**\\RunOnce", true),
+            ("NOT (path:Run path:x*)", true),
+            ("path:Run AND name:x", false),
+            ("path:Run OR type:sz", false),
+            ("path:Run NOT run", false),
+        ] {
+            assert_eq!(
+                Query::parse(term).unwrap().only_paths(),
+                only_paths,
+                "{}",
+                term
+            );
+        }
+        for term in [
+            "a AND",
+            "OR b",
+            "(a OR b",
+            "a)",
+            "data:\"unclosed",
+            "name: AND b",
+            "type:text",
+            "NOT",
+        ] {
+            assert!(Query::parse(term).is_err(), "{}", term);
+        }
+    }
+}
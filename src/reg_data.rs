@@ -0,0 +1,199 @@
+use std::fmt;
+
+use crate::{
+    registry_source::{RegType, RegistryValue},
+    resource_list::{self, FullResourceDescriptor, RequirementsList, ResourceList},
+};
+
+/// UTF-16LE up to the first NUL, the terminator and anything after it are dropped. A trailing
+/// odd byte can't be part of a code unit and is ignored.
+pub fn decode_utf16(bytes: &[u8]) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|unit| *unit != 0)
+        .collect::<Vec<u16>>();
+    String::from_utf16_lossy(&units)
+}
+
+/// NUL separated UTF-16LE strings, the empty string that ends the list and any after it are
+/// dropped.
+pub fn decode_multi_utf16(bytes: &[u8]) -> Vec<String> {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect::<Vec<u16>>();
+    units
+        .split(|unit| *unit == 0)
+        .take_while(|string| !string.is_empty())
+        .map(String::from_utf16_lossy)
+        .collect()
+}
+
+/// Bytes the way regedit exports them, eg. `hex:4d,5a,90`.
+fn hex(bytes: &[u8]) -> String {
+    let bytes = bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<String>>()
+        .join(",");
+    format!("hex:{}", bytes)
+}
+
+/// Value data decoded according to its type. Data that doesn't fit its type, like a REG_DWORD
+/// that isn't 4 bytes long, is kept as `Malformed` with its raw bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegData {
+    None(Vec<u8>),
+    Sz(String),
+    ExpandSz(String),
+    Binary(Vec<u8>),
+    Dword(u32),
+    DwordBigEndian(u32),
+    Link(String),
+    MultiSz(Vec<String>),
+    ResourceList(ResourceList),
+    FullResourceDescriptor(FullResourceDescriptor),
+    ResourceRequirementsList(RequirementsList),
+    Qword(u64),
+    Malformed { vtype: RegType, bytes: Vec<u8> },
+}
+
+impl RegData {
+    pub fn from_value(value: &RegistryValue) -> Self {
+        let bytes = &value.bytes;
+        match value.vtype {
+            RegType::REG_NONE => Self::None(bytes.to_owned()),
+            RegType::REG_SZ => Self::Sz(decode_utf16(bytes)),
+            RegType::REG_EXPAND_SZ => Self::ExpandSz(decode_utf16(bytes)),
+            RegType::REG_BINARY => Self::Binary(bytes.to_owned()),
+            RegType::REG_DWORD => match bytes.as_slice().try_into() {
+                Ok(dword) => Self::Dword(u32::from_le_bytes(dword)),
+                Err(_) => Self::malformed(value),
+            },
+            RegType::REG_DWORD_BIG_ENDIAN => match bytes.as_slice().try_into() {
+                Ok(dword) => Self::DwordBigEndian(u32::from_be_bytes(dword)),
+                Err(_) => Self::malformed(value),
+            },
+            RegType::REG_LINK => Self::Link(decode_utf16(bytes)),
+            RegType::REG_MULTI_SZ => Self::MultiSz(decode_multi_utf16(bytes)),
+            RegType::REG_RESOURCE_LIST => match resource_list::parse_resource_list(bytes) {
+                Ok(list) => Self::ResourceList(list),
+                Err(_) => Self::malformed(value),
+            },
+            RegType::REG_FULL_RESOURCE_DESCRIPTOR => {
+                match resource_list::parse_full_resource_descriptor(bytes) {
+                    Ok(descriptor) => Self::FullResourceDescriptor(descriptor),
+                    Err(_) => Self::malformed(value),
+                }
+            }
+            RegType::REG_RESOURCE_REQUIREMENTS_LIST => {
+                match resource_list::parse_requirements_list(bytes) {
+                    Ok(list) => Self::ResourceRequirementsList(list),
+                    Err(_) => Self::malformed(value),
+                }
+            }
+            RegType::REG_QWORD => match bytes.as_slice().try_into() {
+                Ok(qword) => Self::Qword(u64::from_le_bytes(qword)),
+                Err(_) => Self::malformed(value),
+            },
+        }
+    }
+
+    fn malformed(value: &RegistryValue) -> Self {
+        Self::Malformed {
+            vtype: value.vtype,
+            bytes: value.bytes.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for RegData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::None(bytes) if bytes.is_empty() => write!(f, "None"),
+            Self::Sz(text) | Self::ExpandSz(text) | Self::Link(text) => write!(f, "{}", text),
+            Self::Binary(bytes) => write!(f, "BIN_LENGTH: {}", bytes.len()),
+            Self::Dword(number) | Self::DwordBigEndian(number) => write!(f, "{}", number),
+            Self::Qword(number) => write!(f, "{}", number),
+            Self::MultiSz(strings) => write!(f, "{}", strings.join(", ")),
+            Self::ResourceList(list) => write!(f, "{}", list),
+            Self::FullResourceDescriptor(descriptor) => write!(f, "{}", descriptor),
+            Self::ResourceRequirementsList(list) => write!(f, "{}", list),
+            Self::None(bytes) | Self::Malformed { bytes, .. } => write!(f, "{}", hex(bytes)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reg_file::to_utf16_bytes;
+
+    fn data(vtype: RegType, bytes: Vec<u8>) -> RegData {
+        RegData::from_value(&RegistryValue { vtype, bytes })
+    }
+
+    #[test]
+    fn decodes_utf16_strings_up_to_their_terminator() {
+        let mut bytes = to_utf16_bytes("C:\\Users\\Zoë\\日本\0");
+        bytes.extend(to_utf16_bytes("garbage"));
+        assert_eq!(
+            data(RegType::REG_SZ, bytes),
+            RegData::Sz("C:\\Users\\Zoë\\日本".into())
+        );
+        assert_eq!(
+            data(RegType::REG_EXPAND_SZ, to_utf16_bytes("%TEMP%")).to_string(),
+            "%TEMP%"
+        );
+        let mut odd = to_utf16_bytes("ab");
+        odd.push(b'c');
+        assert_eq!(decode_utf16(&odd), "ab");
+    }
+
+    #[test]
+    fn splits_multi_strings() {
+        let value = data(RegType::REG_MULTI_SZ, to_utf16_bytes("one\0twö\0\0junk\0"));
+        assert_eq!(value, RegData::MultiSz(vec!["one".into(), "twö".into()]));
+        assert_eq!(value.to_string(), "one, twö");
+        assert_eq!(
+            data(RegType::REG_MULTI_SZ, Vec::new()),
+            RegData::MultiSz(Vec::new())
+        );
+    }
+
+    #[test]
+    fn decodes_numbers() {
+        assert_eq!(
+            data(RegType::REG_DWORD, vec![2, 0, 0, 0]),
+            RegData::Dword(2)
+        );
+        assert_eq!(
+            data(RegType::REG_DWORD_BIG_ENDIAN, vec![0, 0, 1, 0]).to_string(),
+            "256"
+        );
+        assert_eq!(
+            data(RegType::REG_QWORD, u64::MAX.to_le_bytes().to_vec()),
+            RegData::Qword(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn keeps_malformed_data_as_bytes() {
+        let value = data(RegType::REG_DWORD, vec![1, 2, 3]);
+        assert_eq!(
+            value,
+            RegData::Malformed {
+                vtype: RegType::REG_DWORD,
+                bytes: vec![1, 2, 3]
+            }
+        );
+        assert_eq!(value.to_string(), "hex:01,02,03");
+        assert_eq!(data(RegType::REG_NONE, Vec::new()).to_string(), "None");
+        assert_eq!(data(RegType::REG_NONE, vec![0xAB]).to_string(), "hex:ab");
+        assert_eq!(
+            data(RegType::REG_RESOURCE_LIST, vec![1, 0, 0, 0, 1]).to_string(),
+            "hex:01,00,00,00,01"
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use std::{fs, path::Path};
+
+use crate::{
+    memory_source::MemorySource,
+    registry_source::{RegType, RegistryValue},
+};
+
+const REGEDIT5_HEADER: &str = "Windows Registry Editor Version 5.00";
+const REGEDIT4_HEADER: &str = "REGEDIT4";
+
+/// regedit /e writes UTF-16LE with a BOM, REGEDIT4 files are ANSI.
+pub fn decode_text(bytes: &[u8]) -> String {
+    if let Some(bytes) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        let units = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect::<Vec<u16>>();
+        return String::from_utf16_lossy(&units);
+    }
+    let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&byte| byte as char).collect(),
+    }
+}
+
+pub fn to_utf16_bytes(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect()
+}
+
+/// Parses a quoted string starting at the opening quote, returns the unescaped string and the rest.
+fn parse_quoted(text: &str) -> Option<(String, &str)> {
+    let mut chars = text.strip_prefix('"')?.char_indices();
+    let mut string = String::new();
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some((_, escaped)) => string.push(escaped),
+                None => return None,
+            },
+            '"' => return Some((string, &text[index + 2..])),
+            ch => string.push(ch),
+        }
+    }
+    None
+}
+
+fn parse_hex_bytes(text: &str) -> Result<Vec<u8>, String> {
+    text.split(',')
+        .map(|byte| byte.trim())
+        .filter(|byte| !byte.is_empty())
+        .map(|byte| {
+            u8::from_str_radix(byte, 16).map_err(|_| format!("Invalid hex byte \"{}\"", byte))
+        })
+        .collect()
+}
+
+/// The value, along with a warning when it loaded but not as written.
+fn parse_data(data: &str, ansi: bool) -> Result<(RegistryValue, Option<String>), String> {
+    if let Some((string, rest)) = parse_quoted(data) {
+        if !rest.trim().is_empty() {
+            return Err(format!("Unexpected \"{}\" after string", rest.trim()));
+        }
+        return Ok((
+            RegistryValue {
+                vtype: RegType::REG_SZ,
+                bytes: to_utf16_bytes(&string),
+            },
+            None,
+        ));
+    }
+    if let Some(dword) = data.strip_prefix("dword:") {
+        let dword = u32::from_str_radix(dword.trim(), 16)
+            .map_err(|_| format!("Invalid dword \"{}\"", dword.trim()))?;
+        return Ok((
+            RegistryValue {
+                vtype: RegType::REG_DWORD,
+                bytes: dword.to_le_bytes().to_vec(),
+            },
+            None,
+        ));
+    }
+    let (vtype, hex) = if let Some(hex) = data.strip_prefix("hex:") {
+        (RegType::REG_BINARY as u32, hex)
+    } else if let Some(rest) = data.strip_prefix("hex(") {
+        let (vtype, hex) = match rest.split_once("):") {
+            Some(split) => split,
+            None => return Err(format!("Invalid hex type \"{}\"", data)),
+        };
+        match u32::from_str_radix(vtype, 16) {
+            Ok(vtype) => (vtype, hex),
+            Err(_) => return Err(format!("Invalid hex type \"{}\"", vtype)),
+        }
+    } else {
+        return Err(format!("Unrecognised value data \"{}\"", data));
+    };
+    let (vtype, warning) = match RegType::from_u32(vtype) {
+        Some(known) => (known, None),
+        None => (
+            RegType::REG_NONE,
+            Some(format!(
+                "unknown value type {:x}, loaded as REG_NONE",
+                vtype
+            )),
+        ),
+    };
+    let mut bytes = parse_hex_bytes(hex)?;
+    if ansi
+        && matches!(
+            vtype,
+            RegType::REG_SZ | RegType::REG_EXPAND_SZ | RegType::REG_MULTI_SZ
+        )
+    {
+        //REGEDIT4 stores string types as single byte characters, widen them to match the registry
+        bytes = bytes.iter().flat_map(|&byte| [byte, 0]).collect();
+    }
+    Ok((RegistryValue { vtype, bytes }, warning))
+}
+
+/// Joins lines ending in `\` with the next line, keeping the line number of the first.
+fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (index, line) in text.lines().enumerate() {
+        let line = match current.as_ref() {
+            Some(_) => line.trim_start(),
+            None => line,
+        };
+        let (line_number, mut joined) = current.take().unwrap_or((index + 1, String::new()));
+        match line.trim_end().strip_suffix('\\') {
+            Some(continued) => {
+                joined.push_str(continued);
+                current = Some((line_number, joined));
+            }
+            None => {
+                joined.push_str(line.trim_end());
+                lines.push((line_number, joined));
+            }
+        }
+    }
+    if let Some(line) = current {
+        lines.push(line);
+    }
+    lines
+}
+
+fn split_root(key_path: &str) -> (&str, &str) {
+    key_path.split_once('\\').unwrap_or((key_path, ""))
+}
+
+/// Loads a regedit export into `source`, applying deletions in file order.
+/// Lines that can't be parsed are skipped and returned as warnings.
+pub fn load(path: &Path, source: &mut MemorySource) -> Result<Vec<String>, String> {
+    let bytes = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    parse(&decode_text(&bytes), source).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+pub fn parse(text: &str, source: &mut MemorySource) -> Result<Vec<String>, String> {
+    let mut lines = logical_lines(text).into_iter();
+    let ansi = match lines.next() {
+        Some((_, header)) if header.trim() == REGEDIT5_HEADER => false,
+        Some((_, header)) if header.trim() == REGEDIT4_HEADER => true,
+        _ => return Err("Missing REGEDIT4 or REGEDIT5 header".into()),
+    };
+
+    let mut warnings = Vec::new();
+    let mut current_key: Option<String> = None;
+    for (line_number, line) in lines {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        if let Some(key_path) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            if let Some(key_path) = key_path.strip_prefix('-') {
+                let (root, path) = split_root(key_path);
+                source.delete_key(root, path);
+                current_key = None;
+            } else {
+                let (root, path) = split_root(key_path);
+                source.insert_key(root, path);
+                current_key = Some(key_path.to_string());
+            }
+            continue;
+        }
+        let key_path = match current_key.as_ref() {
+            Some(key_path) => key_path,
+            None => {
+                warnings.push(format!("Line {}: value outside of a key", line_number));
+                continue;
+            }
+        };
+        let (name, data) = if let Some(data) = line.strip_prefix('@') {
+            (String::new(), data)
+        } else {
+            match parse_quoted(line) {
+                Some(name_and_data) => name_and_data,
+                None => {
+                    warnings.push(format!("Line {}: invalid value name", line_number));
+                    continue;
+                }
+            }
+        };
+        let data = match data.trim_start().strip_prefix('=') {
+            Some(data) => data.trim(),
+            None => {
+                warnings.push(format!("Line {}: missing '='", line_number));
+                continue;
+            }
+        };
+        let (root, path) = split_root(key_path);
+        if data == "-" {
+            source.delete_value(root, path, &name);
+            continue;
+        }
+        match parse_data(data, ansi) {
+            Ok((value, warning)) => {
+                source.set_value(root, path, &name, value);
+                if let Some(warning) = warning {
+                    warnings.push(format!("Line {}: {}", line_number, warning));
+                }
+            }
+            Err(err) => warnings.push(format!("Line {}: {}", line_number, err)),
+        }
+    }
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry_source::RegistrySource;
+
+    fn values(source: &MemorySource, root: &str, path: &str) -> Vec<(String, RegistryValue)> {
+        let key = source.open_key(root, path).unwrap();
+        source
+            .enum_values(&key)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    fn load(text: &str) -> (MemorySource, Vec<String>) {
+        let mut source = MemorySource::default();
+        let warnings = parse(text, &mut source).unwrap();
+        (source, warnings)
+    }
+
+    #[test]
+    fn parses_every_data_form() {
+        let (source, warnings) = load(
+            "Windows Registry Editor Version 5.00\r\n\
+             \r\n\
+             [HKEY_CURRENT_USER\\Software\\Test]\r\n\
+             @=\"default\"\r\n\
+             \"Quoted \\\"name\\\"\"=\"C:\\\\Temp\\\\x.exe\"\r\n\
+             \"Number\"=dword:0000002a\r\n\
+             \"Binary\"=hex:de,ad,\\\r\n  be,ef\r\n\
+             \"Expand\"=hex(2):25,00,00,00\r\n\
+             \"Odd\"=hex(ffff0012):01\r\n",
+        );
+        assert_eq!(
+            warnings,
+            ["Line 10: unknown value type ffff0012, loaded as REG_NONE"]
+        );
+        let values = values(&source, "HKEY_CURRENT_USER", "Software\\Test");
+        let expected = [
+            ("", RegType::REG_SZ, to_utf16_bytes("default")),
+            (
+                "Quoted \"name\"",
+                RegType::REG_SZ,
+                to_utf16_bytes("C:\\Temp\\x.exe"),
+            ),
+            ("Number", RegType::REG_DWORD, vec![0x2A, 0, 0, 0]),
+            ("Binary", RegType::REG_BINARY, vec![0xDE, 0xAD, 0xBE, 0xEF]),
+            ("Expand", RegType::REG_EXPAND_SZ, vec![0x25, 0, 0, 0]),
+            ("Odd", RegType::REG_NONE, vec![1]),
+        ];
+        assert_eq!(values.len(), expected.len());
+        for ((name, value), (expected_name, vtype, bytes)) in values.iter().zip(expected) {
+            assert_eq!(name, expected_name);
+            assert_eq!(value.vtype, vtype);
+            assert_eq!(value.bytes, bytes);
+        }
+    }
+
+    #[test]
+    fn widens_regedit4_strings() {
+        let (source, _) = load("REGEDIT4\n\n[HKEY_LOCAL_MACHINE\\A]\n\"Multi\"=hex(7):61,00,00\n");
+        let values = values(&source, "HKEY_LOCAL_MACHINE", "A");
+        assert_eq!(values[0].1.bytes, vec![0x61, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn applies_deletions_in_order() {
+        let (source, warnings) = load(
+            "Windows Registry Editor Version 5.00\n\
+             [HKEY_CURRENT_USER\\A\\B]\n\
+             \"x\"=\"1\"\n\
+             \"y\"=\"2\"\n\
+             [HKEY_CURRENT_USER\\C]\n\
+             [-HKEY_CURRENT_USER\\C]\n\
+             [HKEY_CURRENT_USER\\A\\B]\n\
+             \"x\"=-\n",
+        );
+        assert!(warnings.is_empty());
+        assert!(source.open_key("HKEY_CURRENT_USER", "C").is_err());
+        let values = values(&source, "HKEY_CURRENT_USER", "a\\b");
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].0, "y");
+    }
+
+    #[test]
+    fn decodes_utf16_files() {
+        let text = "Windows Registry Editor Version 5.00\r\n[HKEY_USERS\\Ключ]\r\n";
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        let (source, _) = load(&decode_text(&bytes));
+        assert!(source.open_key("HKEY_USERS", "ключ").is_ok());
+    }
+
+    #[test]
+    fn warns_about_lines_it_cannot_parse() {
+        let (_, warnings) = load(
+            "REGEDIT4\n\
+             \"orphan\"=\"x\"\n\
+             [HKEY_CURRENT_USER\\A]\n\
+             \"no equals\"\n\
+             \"bad\"=dword:xyz\n\
+             \"unknown\"=qword:1\n",
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                "Line 2: value outside of a key",
+                "Line 4: missing '='",
+                "Line 5: Invalid dword \"xyz\"",
+                "Line 6: Unrecognised value data \"qword:1\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn needs_a_header() {
+        let mut source = MemorySource::default();
+        assert!(parse("[HKEY_CURRENT_USER\\A]\n", &mut source).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RegType {
+    REG_NONE = 0,
+    REG_SZ = 1,
+    REG_EXPAND_SZ = 2,
+    REG_BINARY = 3,
+    REG_DWORD = 4,
+    REG_DWORD_BIG_ENDIAN = 5,
+    REG_LINK = 6,
+    REG_MULTI_SZ = 7,
+    REG_RESOURCE_LIST = 8,
+    REG_FULL_RESOURCE_DESCRIPTOR = 9,
+    REG_RESOURCE_REQUIREMENTS_LIST = 10,
+    REG_QWORD = 11,
+}
+
+impl RegType {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Self::REG_NONE),
+            1 => Some(Self::REG_SZ),
+            2 => Some(Self::REG_EXPAND_SZ),
+            3 => Some(Self::REG_BINARY),
+            4 => Some(Self::REG_DWORD),
+            5 => Some(Self::REG_DWORD_BIG_ENDIAN),
+            6 => Some(Self::REG_LINK),
+            7 => Some(Self::REG_MULTI_SZ),
+            8 => Some(Self::REG_RESOURCE_LIST),
+            9 => Some(Self::REG_FULL_RESOURCE_DESCRIPTOR),
+            10 => Some(Self::REG_RESOURCE_REQUIREMENTS_LIST),
+            11 => Some(Self::REG_QWORD),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RegType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Raw value data as stored by the registry, independent of the backend it was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryValue {
+    pub vtype: RegType,
+    pub bytes: Vec<u8>,
+}
+
+/// Key metadata, `last_write_time` is a FILETIME (100ns intervals since 1601-01-01 UTC).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyInfo {
+    pub last_write_time: u64,
+    pub class_name: Option<String>,
+    pub subkey_count: u32,
+    pub value_count: u32,
+    pub max_value_len: u32,
+}
+
+/// Subkey names, with the subkey itself when the backend can hand it out.
+pub type Subkeys<K> = Vec<Result<(String, Option<K>), String>>;
+
+/// A backend the search engine can walk.
+///
+/// Roots are addressed by their display name (eg. "HKEY_LOCAL_MACHINE"), key paths are
+/// relative to the root and `\` separated. An empty path opens the root itself.
+pub trait RegistrySource: Send + Sync {
+    type Key: Send;
+
+    fn roots(&self) -> Vec<String>;
+    fn open_key(&self, root: &str, path: &str) -> Result<Self::Key, String>;
+    fn enum_keys(&self, key: &Self::Key) -> Vec<Result<String, String>>;
+
+    /// Subkey names along with the subkey itself, for backends that can open it without looking
+    /// its path up from the root again. `None` subkeys are opened by path.
+    fn enum_subkeys(&self, key: &Self::Key) -> Subkeys<Self::Key> {
+        self.enum_keys(key)
+            .into_iter()
+            .map(|name| name.map(|name| (name, None)))
+            .collect()
+    }
+
+    fn enum_values(&self, key: &Self::Key) -> Vec<Result<(String, RegistryValue), String>>;
+    fn key_info(&self, key: &Self::Key) -> Result<KeyInfo, String>;
+
+    /// The key's self-relative security descriptor, for backends that can read it.
+    fn security_descriptor(&self, _key: &Self::Key) -> Result<Option<Vec<u8>>, String> {
+        Ok(None)
+    }
+
+    /// Short note appended to results from this key, eg. where an offline key was recovered from.
+    fn annotation(&self, _key: &Self::Key) -> Option<String> {
+        None
+    }
+
+    /// Short note appended to results from every key, eg. the file they were loaded from.
+    /// Unlike annotations it isn't captured in snapshots.
+    fn label(&self) -> Option<String> {
+        None
+    }
+}
@@ -23,13 +23,16 @@ use ratatui::{
 };
 use tracing::error;
 
-use crate::{static_selection::StaticSelection, Focus, KEY_COUNT, SELECTION_COLOUR, VALUE_COUNT};
+use crate::{
+    static_selection::StaticSelection, Focus, KEY_COUNT, SELECTION_COLOUR, SUPPRESSED_COUNT,
+    VALUE_COUNT,
+};
 
 pub fn renderer_wrappers_wrapper(
     static_menu_selection: Arc<StaticSelection>,
     focus: Arc<RwLock<Focus>>,
     stop: Arc<AtomicBool>,
-) -> Result<(), ()> {
+) -> Result<(), String> {
     let result = renderer_wrapper(
         static_menu_selection.to_owned(),
         focus.to_owned(),
@@ -37,10 +40,10 @@ pub fn renderer_wrappers_wrapper(
     );
     stop.store(true, Ordering::SeqCst);
     match result {
-        Ok(_) => return Ok(()),
+        Ok(_) => Ok(()),
         Err(err) => {
             error!("{}", err);
-            return Err(());
+            Err(err.to_string())
         }
     }
 }
@@ -146,6 +149,10 @@ pub fn renderer(
                     " [Results count: {}]",
                     static_menu_selection.results.lock().len()
                 )),
+                Span::raw(format!(
+                    " [Suppressed: {}]",
+                    SUPPRESSED_COUNT.load(Ordering::SeqCst)
+                )),
             ]))
             .block(Block::default())
             .wrap(Wrap { trim: true });
@@ -174,19 +181,26 @@ pub fn renderer(
 
             let pane_selected = static_menu_selection.pane_selected.load(Ordering::SeqCst);
 
-            let roots_paragraph = Paragraph::new(static_menu_selection.generate_root_list()).block(
-                Block::default()
-                    .title(Span::styled(
-                        " 1. Root Selection ",
-                        Style::default().fg(Color::White),
-                    ))
-                    .borders(Borders::ALL)
-                    .border_style(Style::default().fg(if pane_selected == 0 {
-                        SELECTION_COLOUR
-                    } else {
-                        Color::White
-                    })),
-            );
+            //keeps the selected root in view when an image adds more roots than fit
+            let visible_roots = left_chunks[0].height.saturating_sub(2) as usize;
+            let root_scroll = static_menu_selection
+                .root_selected()
+                .saturating_sub(visible_roots.saturating_sub(1));
+            let roots_paragraph = Paragraph::new(static_menu_selection.generate_root_list())
+                .block(
+                    Block::default()
+                        .title(Span::styled(
+                            " 1. Root Selection ",
+                            Style::default().fg(Color::White),
+                        ))
+                        .borders(Borders::ALL)
+                        .border_style(Style::default().fg(if pane_selected == 0 {
+                            SELECTION_COLOUR
+                        } else {
+                            Color::White
+                        })),
+                )
+                .scroll((root_scroll as u16, 0));
 
             let search_terms_paragraph = Paragraph::new(
                 static_menu_selection
@@ -278,16 +292,18 @@ pub fn renderer(
                                 .border_style(Style::default().fg(Color::White)),
                         ),
                         Focus::SearchMod(search_editor) => {
-                            Paragraph::new(search_editor.read().as_ref().unwrap().render()).block(
-                                Block::default()
-                                    .title(Span::styled(
-                                        "Search Modify",
-                                        Style::default().fg(Color::White),
-                                    ))
-                                    .style(Style::default().bg(Color::DarkGray))
-                                    .borders(Borders::ALL)
-                                    .border_style(Style::default().fg(Color::White)),
-                            )
+                            Paragraph::new(search_editor.read().as_ref().unwrap().render())
+                                .wrap(Wrap { trim: false })
+                                .block(
+                                    Block::default()
+                                        .title(Span::styled(
+                                            "Search Modify",
+                                            Style::default().fg(Color::White),
+                                        ))
+                                        .style(Style::default().bg(Color::DarkGray))
+                                        .borders(Borders::ALL)
+                                        .border_style(Style::default().fg(Color::White)),
+                                )
                         }
                         Focus::Main => unreachable!(), //this case will never run
                     };
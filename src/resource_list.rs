@@ -0,0 +1,666 @@
+use std::fmt;
+
+use crate::hive::{read_u16, read_u32, read_u64};
+
+/// Partial resource descriptors are 16 bytes when written by 32 bit Windows and 20 by 64 bit,
+/// where the interrupt affinity is a 64 bit mask.
+const PARTIAL_DESCRIPTOR_SIZES: [usize; 2] = [20, 16];
+const IO_DESCRIPTOR_SIZE: usize = 32;
+
+const RESOURCE_PORT: u8 = 1;
+const RESOURCE_INTERRUPT: u8 = 2;
+const RESOURCE_MEMORY: u8 = 3;
+const RESOURCE_DMA: u8 = 4;
+const RESOURCE_DEVICE_SPECIFIC: u8 = 5;
+const RESOURCE_BUS_NUMBER: u8 = 6;
+const RESOURCE_MEMORY_LARGE: u8 = 7;
+
+const MEMORY_LARGE_40: u16 = 0x200;
+const MEMORY_LARGE_48: u16 = 0x400;
+const MEMORY_LARGE_64: u16 = 0x800;
+
+/// INTERFACE_TYPE, the kind of bus a device sits on.
+fn interface_type_name(interface_type: u32) -> String {
+    let name = match interface_type as i32 {
+        -1 => "Undefined",
+        0 => "Internal",
+        1 => "Isa",
+        2 => "Eisa",
+        3 => "MicroChannel",
+        4 => "TurboChannel",
+        5 => "PCIBus",
+        6 => "VMEBus",
+        7 => "NuBus",
+        8 => "PCMCIABus",
+        9 => "CBus",
+        10 => "MPIBus",
+        11 => "MPSABus",
+        12 => "ProcessorInternal",
+        13 => "InternalPowerBus",
+        14 => "PNPISABus",
+        15 => "PNPBus",
+        16 => "Vmcs",
+        17 => "ACPIBus",
+        other => return format!("InterfaceType{}", other),
+    };
+    name.to_string()
+}
+
+fn share_disposition_name(share_disposition: u8) -> &'static str {
+    match share_disposition {
+        0 => "undetermined",
+        1 => "device exclusive",
+        2 => "driver exclusive",
+        3 => "shared",
+        _ => "unknown sharing",
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn truncated(&self) -> String {
+        format!("Resource data is truncated at 0x{:X}", self.offset)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| self.truncated())?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let value = read_u16(self.data, self.offset).ok_or_else(|| self.truncated())?;
+        self.offset += 2;
+        Ok(value)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let value = read_u32(self.data, self.offset).ok_or_else(|| self.truncated())?;
+        self.offset += 4;
+        Ok(value)
+    }
+
+    fn is_done(&self) -> bool {
+        self.offset == self.data.len()
+    }
+}
+
+/// One CM_PARTIAL_RESOURCE_DESCRIPTOR, a range of ports or memory, an interrupt and so on that
+/// was assigned to a device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resource {
+    Port {
+        start: u64,
+        length: u32,
+    },
+    Interrupt {
+        level: u32,
+        vector: u32,
+        affinity: u64,
+    },
+    Memory {
+        start: u64,
+        length: u64,
+    },
+    Dma {
+        channel: u32,
+        port: u32,
+    },
+    DeviceSpecific {
+        data: Vec<u8>,
+    },
+    BusNumber {
+        start: u32,
+        length: u32,
+    },
+    Other {
+        resource_type: u8,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialResource {
+    pub share_disposition: u8,
+    pub flags: u16,
+    pub resource: Resource,
+}
+
+impl fmt::Display for PartialResource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.resource {
+            Resource::Port { start, length } => {
+                write!(f, "Port start=0x{:X} length=0x{:X}", start, length)?
+            }
+            Resource::Interrupt {
+                level,
+                vector,
+                affinity,
+            } => write!(
+                f,
+                "Interrupt level={} vector={} affinity=0x{:X}",
+                level, vector, affinity
+            )?,
+            Resource::Memory { start, length } => {
+                write!(f, "Memory start=0x{:X} length=0x{:X}", start, length)?
+            }
+            Resource::Dma { channel, port } => write!(f, "DMA channel={} port={}", channel, port)?,
+            Resource::DeviceSpecific { data } => {
+                write!(f, "DeviceSpecific length=0x{:X}", data.len())?
+            }
+            Resource::BusNumber { start, length } => {
+                write!(f, "BusNumber start={} length={}", start, length)?
+            }
+            Resource::Other { resource_type, .. } => write!(f, "Type{}", resource_type)?,
+        }
+        write!(
+            f,
+            " flags=0x{:X} {}",
+            self.flags,
+            share_disposition_name(self.share_disposition)
+        )
+    }
+}
+
+fn parse_partial_resource(
+    reader: &mut Reader,
+    descriptor_size: usize,
+) -> Result<PartialResource, String> {
+    let descriptor = reader.bytes(descriptor_size)?;
+    let resource_type = descriptor[0];
+    let share_disposition = descriptor[1];
+    let flags = read_u16(descriptor, 2).unwrap();
+    let u32_at = |offset| read_u32(descriptor, offset).unwrap();
+    let u64_at = |offset| read_u64(descriptor, offset).unwrap();
+    let resource = match resource_type {
+        RESOURCE_PORT => Resource::Port {
+            start: u64_at(4),
+            length: u32_at(12),
+        },
+        RESOURCE_INTERRUPT => Resource::Interrupt {
+            level: u32_at(4),
+            vector: u32_at(8),
+            affinity: match descriptor_size {
+                16 => u32_at(12) as u64,
+                _ => u64_at(12),
+            },
+        },
+        RESOURCE_MEMORY => Resource::Memory {
+            start: u64_at(4),
+            length: u32_at(12) as u64,
+        },
+        RESOURCE_MEMORY_LARGE => {
+            let shift = match flags {
+                flags if flags & MEMORY_LARGE_40 != 0 => 8,
+                flags if flags & MEMORY_LARGE_48 != 0 => 16,
+                flags if flags & MEMORY_LARGE_64 != 0 => 32,
+                _ => 0,
+            };
+            Resource::Memory {
+                start: u64_at(4),
+                length: (u32_at(12) as u64) << shift,
+            }
+        }
+        RESOURCE_DMA => Resource::Dma {
+            channel: u32_at(4),
+            port: u32_at(8),
+        },
+        //the data follows the descriptor
+        RESOURCE_DEVICE_SPECIFIC => Resource::DeviceSpecific {
+            data: reader.bytes(u32_at(4) as usize)?.to_vec(),
+        },
+        RESOURCE_BUS_NUMBER => Resource::BusNumber {
+            start: u32_at(4),
+            length: u32_at(8),
+        },
+        resource_type => Resource::Other {
+            resource_type,
+            data: descriptor[4..].to_vec(),
+        },
+    };
+    Ok(PartialResource {
+        share_disposition,
+        flags,
+        resource,
+    })
+}
+
+/// CM_FULL_RESOURCE_DESCRIPTOR, the resources of the devices on one bus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FullResourceDescriptor {
+    pub interface_type: u32,
+    pub bus_number: u32,
+    pub version: u16,
+    pub revision: u16,
+    pub resources: Vec<PartialResource>,
+}
+
+impl fmt::Display for FullResourceDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} bus={}",
+            interface_type_name(self.interface_type),
+            self.bus_number
+        )?;
+        for (index, resource) in self.resources.iter().enumerate() {
+            let separator = if index == 0 { ":" } else { "," };
+            write!(f, "{} {}", separator, resource)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_full_descriptor(
+    reader: &mut Reader,
+    descriptor_size: usize,
+) -> Result<FullResourceDescriptor, String> {
+    let interface_type = reader.u32()?;
+    let bus_number = reader.u32()?;
+    let version = reader.u16()?;
+    let revision = reader.u16()?;
+    let count = reader.u32()?;
+    let resources = (0..count)
+        .map(|_| parse_partial_resource(reader, descriptor_size))
+        .collect::<Result<Vec<PartialResource>, String>>()?;
+    Ok(FullResourceDescriptor {
+        interface_type,
+        bus_number,
+        version,
+        revision,
+        resources,
+    })
+}
+
+/// Tries both descriptor sizes, preferring the one that accounts for every byte.
+fn parse_sized<T>(
+    data: &[u8],
+    parse: impl Fn(&mut Reader, usize) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut fallback = None;
+    for descriptor_size in PARTIAL_DESCRIPTOR_SIZES {
+        let mut reader = Reader { data, offset: 0 };
+        match parse(&mut reader, descriptor_size) {
+            Ok(parsed) if reader.is_done() => return Ok(parsed),
+            Ok(parsed) => fallback = fallback.or(Some(Ok(parsed))),
+            Err(err) => fallback = fallback.or(Some(Err(err))),
+        }
+    }
+    fallback.unwrap()
+}
+
+/// REG_FULL_RESOURCE_DESCRIPTOR data.
+pub fn parse_full_resource_descriptor(data: &[u8]) -> Result<FullResourceDescriptor, String> {
+    parse_sized(data, parse_full_descriptor)
+}
+
+/// CM_RESOURCE_LIST, REG_RESOURCE_LIST data as found under `HKLM\HARDWARE\RESOURCEMAP`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceList {
+    pub descriptors: Vec<FullResourceDescriptor>,
+}
+
+impl fmt::Display for ResourceList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let descriptors = self
+            .descriptors
+            .iter()
+            .map(|descriptor| descriptor.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "{}", descriptors.join("; "))
+    }
+}
+
+/// REG_RESOURCE_LIST data.
+pub fn parse_resource_list(data: &[u8]) -> Result<ResourceList, String> {
+    parse_sized(data, |reader, descriptor_size| {
+        let count = reader.u32()?;
+        let descriptors = (0..count)
+            .map(|_| parse_full_descriptor(reader, descriptor_size))
+            .collect::<Result<Vec<FullResourceDescriptor>, String>>()?;
+        Ok(ResourceList { descriptors })
+    })
+}
+
+/// One IO_RESOURCE_DESCRIPTOR, the range a device can accept a resource from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Requirement {
+    Port {
+        length: u32,
+        alignment: u32,
+        minimum: u64,
+        maximum: u64,
+    },
+    Interrupt {
+        minimum_vector: u32,
+        maximum_vector: u32,
+    },
+    Memory {
+        length: u32,
+        alignment: u32,
+        minimum: u64,
+        maximum: u64,
+    },
+    Dma {
+        minimum_channel: u32,
+        maximum_channel: u32,
+    },
+    BusNumber {
+        length: u32,
+        minimum: u32,
+        maximum: u32,
+    },
+    Other {
+        resource_type: u8,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IoResource {
+    /// IO_RESOURCE_PREFERRED, IO_RESOURCE_DEFAULT, IO_RESOURCE_ALTERNATIVE bits.
+    pub option: u8,
+    pub share_disposition: u8,
+    pub flags: u16,
+    pub requirement: Requirement,
+}
+
+impl fmt::Display for IoResource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.requirement {
+            Requirement::Port {
+                length,
+                alignment,
+                minimum,
+                maximum,
+            } => write!(
+                f,
+                "Port length=0x{:X} alignment=0x{:X} minimum=0x{:X} maximum=0x{:X}",
+                length, alignment, minimum, maximum
+            )?,
+            Requirement::Interrupt {
+                minimum_vector,
+                maximum_vector,
+            } => write!(
+                f,
+                "Interrupt minimum={} maximum={}",
+                minimum_vector, maximum_vector
+            )?,
+            Requirement::Memory {
+                length,
+                alignment,
+                minimum,
+                maximum,
+            } => write!(
+                f,
+                "Memory length=0x{:X} alignment=0x{:X} minimum=0x{:X} maximum=0x{:X}",
+                length, alignment, minimum, maximum
+            )?,
+            Requirement::Dma {
+                minimum_channel,
+                maximum_channel,
+            } => write!(
+                f,
+                "DMA minimum={} maximum={}",
+                minimum_channel, maximum_channel
+            )?,
+            Requirement::BusNumber {
+                length,
+                minimum,
+                maximum,
+            } => write!(
+                f,
+                "BusNumber length={} minimum={} maximum={}",
+                length, minimum, maximum
+            )?,
+            Requirement::Other { resource_type } => write!(f, "Type{}", resource_type)?,
+        }
+        write!(
+            f,
+            " option=0x{:X} flags=0x{:X} {}",
+            self.option,
+            self.flags,
+            share_disposition_name(self.share_disposition)
+        )
+    }
+}
+
+fn parse_io_resource(reader: &mut Reader) -> Result<IoResource, String> {
+    let descriptor = reader.bytes(IO_DESCRIPTOR_SIZE)?;
+    let u32_at = |offset| read_u32(descriptor, offset).unwrap();
+    let u64_at = |offset| read_u64(descriptor, offset).unwrap();
+    let requirement = match descriptor[1] {
+        RESOURCE_PORT => Requirement::Port {
+            length: u32_at(8),
+            alignment: u32_at(12),
+            minimum: u64_at(16),
+            maximum: u64_at(24),
+        },
+        RESOURCE_INTERRUPT => Requirement::Interrupt {
+            minimum_vector: u32_at(8),
+            maximum_vector: u32_at(12),
+        },
+        RESOURCE_MEMORY | RESOURCE_MEMORY_LARGE => Requirement::Memory {
+            length: u32_at(8),
+            alignment: u32_at(12),
+            minimum: u64_at(16),
+            maximum: u64_at(24),
+        },
+        RESOURCE_DMA => Requirement::Dma {
+            minimum_channel: u32_at(8),
+            maximum_channel: u32_at(12),
+        },
+        RESOURCE_BUS_NUMBER => Requirement::BusNumber {
+            length: u32_at(8),
+            minimum: u32_at(12),
+            maximum: u32_at(16),
+        },
+        resource_type => Requirement::Other { resource_type },
+    };
+    Ok(IoResource {
+        option: descriptor[0],
+        share_disposition: descriptor[2],
+        flags: read_u16(descriptor, 4).unwrap(),
+        requirement,
+    })
+}
+
+/// IO_RESOURCE_REQUIREMENTS_LIST, REG_RESOURCE_REQUIREMENTS_LIST data. Each alternative is a
+/// complete set of resources the device can work with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequirementsList {
+    pub interface_type: u32,
+    pub bus_number: u32,
+    pub slot_number: u32,
+    pub alternatives: Vec<Vec<IoResource>>,
+}
+
+impl fmt::Display for RequirementsList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} bus={} slot={}",
+            interface_type_name(self.interface_type),
+            self.bus_number,
+            self.slot_number
+        )?;
+        for (index, alternative) in self.alternatives.iter().enumerate() {
+            let resources = alternative
+                .iter()
+                .map(|resource| resource.to_string())
+                .collect::<Vec<String>>();
+            write!(f, "; alternative {}: {}", index + 1, resources.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_requirements_list(data: &[u8]) -> Result<RequirementsList, String> {
+    let mut reader = Reader { data, offset: 0 };
+    let list_size = reader.u32()? as usize;
+    if list_size > data.len() {
+        return Err(format!(
+            "Requirements list is 0x{:X} bytes but only 0x{:X} are present",
+            list_size,
+            data.len()
+        ));
+    }
+    let interface_type = reader.u32()?;
+    let bus_number = reader.u32()?;
+    let slot_number = reader.u32()?;
+    reader.bytes(12)?; //reserved
+    let alternative_count = reader.u32()?;
+    let mut alternatives = Vec::new();
+    for _ in 0..alternative_count {
+        let _version = reader.u16()?;
+        let _revision = reader.u16()?;
+        let count = reader.u32()?;
+        let resources = (0..count)
+            .map(|_| parse_io_resource(&mut reader))
+            .collect::<Result<Vec<IoResource>, String>>()?;
+        alternatives.push(resources);
+    }
+    Ok(RequirementsList {
+        interface_type,
+        bus_number,
+        slot_number,
+        alternatives,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partial(resource_type: u8, share: u8, flags: u16, body: &[u8], size: usize) -> Vec<u8> {
+        let mut bytes = vec![resource_type, share];
+        bytes.extend(flags.to_le_bytes());
+        bytes.extend(body);
+        bytes.resize(size, 0);
+        bytes
+    }
+
+    fn full(interface_type: i32, bus_number: u32, partials: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = interface_type.to_le_bytes().to_vec();
+        bytes.extend(bus_number.to_le_bytes());
+        bytes.extend([1, 0, 1, 0]);
+        bytes.extend((partials.len() as u32).to_le_bytes());
+        partials.iter().for_each(|partial| bytes.extend(partial));
+        bytes
+    }
+
+    #[test]
+    fn decodes_64_bit_resource_lists() {
+        let port = [0x3F8u64.to_le_bytes().as_slice(), &8u32.to_le_bytes()].concat();
+        let interrupt = [
+            4u32.to_le_bytes().as_slice(),
+            &4u32.to_le_bytes(),
+            &0xFFu64.to_le_bytes(),
+        ]
+        .concat();
+        let memory = [
+            0xF000_0000u64.to_le_bytes().as_slice(),
+            &0x10u32.to_le_bytes(),
+        ]
+        .concat();
+        let mut data = 2u32.to_le_bytes().to_vec();
+        data.extend(full(
+            1,
+            0,
+            &[
+                partial(RESOURCE_PORT, 3, 1, &port, 20),
+                partial(RESOURCE_INTERRUPT, 1, 1, &interrupt, 20),
+            ],
+        ));
+        data.extend(full(
+            5,
+            2,
+            &[partial(
+                RESOURCE_MEMORY_LARGE,
+                1,
+                MEMORY_LARGE_48,
+                &memory,
+                20,
+            )],
+        ));
+        let list = parse_resource_list(&data).unwrap();
+        assert_eq!(
+            list.descriptors[0].resources[1].resource,
+            Resource::Interrupt {
+                level: 4,
+                vector: 4,
+                affinity: 0xFF
+            }
+        );
+        assert_eq!(
+            list.to_string(),
+            "Isa bus=0: Port start=0x3F8 length=0x8 flags=0x1 shared, \
+             Interrupt level=4 vector=4 affinity=0xFF flags=0x1 device exclusive; \
+             PCIBus bus=2: Memory start=0xF0000000 length=0x100000 flags=0x400 device exclusive"
+        );
+    }
+
+    #[test]
+    fn decodes_32_bit_descriptors_and_device_specific_data() {
+        let dma = [3u32.to_le_bytes(), 0u32.to_le_bytes()].concat();
+        let mut device_specific = partial(RESOURCE_DEVICE_SPECIFIC, 0, 0, &3u32.to_le_bytes(), 16);
+        device_specific.extend([0xAA, 0xBB, 0xCC]);
+        let data = full(
+            -1,
+            0,
+            &[partial(RESOURCE_DMA, 1, 0, &dma, 16), device_specific],
+        );
+        let descriptor = parse_full_resource_descriptor(&data).unwrap();
+        assert_eq!(
+            descriptor.resources[1].resource,
+            Resource::DeviceSpecific {
+                data: vec![0xAA, 0xBB, 0xCC]
+            }
+        );
+        assert!(descriptor
+            .to_string()
+            .starts_with("Undefined bus=0: DMA channel=3 port=0"));
+        assert!(parse_full_resource_descriptor(&data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn decodes_requirements_lists() {
+        let mut io_port = vec![1, RESOURCE_PORT, 1, 0, 1, 0, 0, 0];
+        io_port.extend(8u32.to_le_bytes());
+        io_port.extend(8u32.to_le_bytes());
+        io_port.extend(0x3F8u64.to_le_bytes());
+        io_port.extend(0x3FFu64.to_le_bytes());
+        let mut io_interrupt = vec![0, RESOURCE_INTERRUPT, 3, 0, 0, 0, 0, 0];
+        io_interrupt.extend(3u32.to_le_bytes());
+        io_interrupt.extend(4u32.to_le_bytes());
+        io_interrupt.resize(IO_DESCRIPTOR_SIZE, 0);
+
+        let mut data = 0u32.to_le_bytes().to_vec();
+        for field in [15u32, 0, 7, 0, 0, 0, 1] {
+            data.extend(field.to_le_bytes());
+        }
+        data.extend([1, 0, 1, 0]);
+        data.extend(2u32.to_le_bytes());
+        data.extend(io_port);
+        data.extend(io_interrupt);
+        let size = data.len() as u32;
+        data[..4].copy_from_slice(&size.to_le_bytes());
+
+        let list = parse_requirements_list(&data).unwrap();
+        assert_eq!(list.alternatives.len(), 1);
+        assert_eq!(
+            list.to_string(),
+            "PNPBus bus=0 slot=7; alternative 1: \
+             Port length=0x8 alignment=0x8 minimum=0x3F8 maximum=0x3FF option=0x1 flags=0x1 device exclusive, \
+             Interrupt minimum=3 maximum=4 option=0x0 flags=0x0 shared"
+        );
+        data[..4].copy_from_slice(&(size + 1).to_le_bytes());
+        assert!(parse_requirements_list(&data).is_err());
+    }
+}
@@ -3,12 +3,18 @@ use ratatui::{
     text::{Line, Span},
 };
 
-use crate::EditorMode;
+use crate::{
+    path_glob::PATH_PREFIX,
+    search_term::{SearchTerm, TermOptions},
+    EditorMode,
+};
 
 #[derive(Debug, Clone)]
 pub struct SearchEditor {
     mode: EditorMode,
     state: String,
+    options: TermOptions,
+    error: Option<String>, //why the term couldn't be saved, cleared by the next edit
 }
 
 impl SearchEditor {
@@ -16,28 +22,92 @@ impl SearchEditor {
         Self {
             mode: EditorMode::Add,
             state: String::new(),
+            options: TermOptions::default(),
+            error: None,
         }
     }
-    pub fn new_edit(original: String) -> Self {
+    pub fn new_edit(original: String, options: TermOptions) -> Self {
         Self {
             mode: EditorMode::Edit(original.to_owned()),
             state: original,
+            options,
+            error: None,
         }
     }
     pub fn add_char(&mut self, ch: char) {
         self.state.push(ch);
+        self.error = None;
     }
     pub fn backspace(&mut self) {
         let _ = self.state.pop();
+        self.error = None;
+    }
+    /// Flips the option bound to Alt + `ch`, other characters are ignored.
+    pub fn toggle_option(&mut self, ch: char) {
+        let option = match ch.to_ascii_lowercase() {
+            'c' => &mut self.options.case_sensitive,
+            'w' => &mut self.options.whole_word,
+            'e' => &mut self.options.exact,
+            'p' => &mut self.options.path,
+            'n' => &mut self.options.name,
+            'd' => &mut self.options.data,
+            _ => return,
+        };
+        *option = !*option;
+        self.error = None;
+    }
+    /// Whether the term can be saved, an invalid regex, byte pattern or test is kept to be shown
+    /// under the term.
+    pub fn validate(&mut self) -> bool {
+        match SearchTerm::parse_with(&self.state, &self.options) {
+            Ok(_) => true,
+            Err(err) => {
+                self.error = Some(err);
+                false
+            }
+        }
     }
-    pub fn resolve(self) -> (EditorMode, String) {
-        (self.mode, self.state)
+    pub fn resolve(self) -> (EditorMode, String, TermOptions) {
+        (self.mode, self.state, self.options)
     }
 
-    pub fn render(&self) -> Line<'static> {
-        Line::from(vec![Span::styled(
-            format!("{}", self.state),
+    pub fn render(&self) -> Vec<Line<'static>> {
+        let mut lines = vec![Line::from(vec![Span::styled(
+            self.state.to_owned(),
             Style::default().fg(Color::White),
-        )])
+        )])];
+        let options = [
+            ("C", "case sensitive", self.options.case_sensitive),
+            ("W", "whole word", self.options.whole_word),
+            ("E", "exact", self.options.exact),
+            ("P", "path", self.options.path),
+            ("N", "name", self.options.name),
+            ("D", "data", self.options.data),
+        ];
+        lines.push(Line::from(
+            options
+                .into_iter()
+                .map(|(key, label, set)| {
+                    Span::styled(
+                        format!("[Alt+{} {}{}] ", key, if set { "x " } else { "  " }, label),
+                        Style::default().fg(if set { Color::Yellow } else { Color::Gray }),
+                    )
+                })
+                .collect::<Vec<Span>>(),
+        ));
+        if self.state.contains(PATH_PREFIX) {
+            lines.push(Line::from(vec![Span::styled(
+                "path:TEXT finds TEXT anywhere in the key's path, with * ? or [ it's a glob over \
+                 the whole path from the root, eg. path:HKLM\\**\\Run",
+                Style::default().fg(Color::Gray),
+            )]));
+        }
+        if let Some(error) = self.error.as_ref() {
+            lines.push(Line::from(vec![Span::styled(
+                error.to_owned(),
+                Style::default().fg(Color::Red),
+            )]));
+        }
+        lines
     }
 }
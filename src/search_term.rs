@@ -0,0 +1,236 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    byte_pattern::BytePattern,
+    number_predicate::NumberPredicate,
+    path_glob::{PathGlob, PATH_PREFIX},
+    query::{Field, Query},
+    security_descriptor::{AclPredicate, ACL_PREFIX},
+    timestamp::{DateRange, DATE_PREFIX},
+};
+
+/// Prefix that turns a term into a byte pattern matched against raw value data.
+pub const HEX_PREFIX: &str = "hex:";
+
+/// Prefix that turns a term into a regular expression matched wherever text terms are.
+pub const REGEX_PREFIX: &str = "re:";
+
+/// Prefix that turns a term into an exclusion, vetoing the results it matches.
+pub const EXCLUDE_PREFIX: &str = "exclude:";
+
+/// How a text or `re:` term is matched, set for each term in the Search Modify popup. Other
+/// kinds of terms already say what they're matched against and ignore these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TermOptions {
+    pub case_sensitive: bool,
+    /// Only matches between characters that aren't letters, digits or `_`, or at the ends.
+    pub whole_word: bool,
+    /// The whole path, name or data has to be the term.
+    pub exact: bool,
+    /// Matched against key paths.
+    pub path: bool,
+    /// Matched against value names.
+    pub name: bool,
+    /// Matched against value data, expanded or pulled out of binary data included.
+    pub data: bool,
+}
+
+impl Default for TermOptions {
+    fn default() -> Self {
+        Self {
+            case_sensitive: false,
+            whole_word: false,
+            exact: false,
+            path: true,
+            name: true,
+            data: true,
+        }
+    }
+}
+
+impl TermOptions {
+    pub fn applies_to(&self, field: Field) -> bool {
+        match field {
+            Field::Path => self.path,
+            Field::Name => self.name,
+            Field::Data => self.data,
+        }
+    }
+
+    /// The same options matched against every field, for terms a qualifier already scopes.
+    pub fn unscoped(&self) -> Self {
+        Self {
+            path: true,
+            name: true,
+            data: true,
+            ..*self
+        }
+    }
+
+    /// Short labels for the options that aren't the default, eg. `["Aa", "word", "name,data"]`.
+    pub fn badges(&self) -> Vec<String> {
+        let mut badges = Vec::new();
+        for (set, badge) in [
+            (self.case_sensitive, "Aa"),
+            (self.whole_word, "word"),
+            (self.exact, "exact"),
+        ] {
+            if set {
+                badges.push(badge.to_string());
+            }
+        }
+        if *self != self.unscoped() {
+            let fields = [
+                (self.path, "path"),
+                (self.name, "name"),
+                (self.data, "data"),
+            ]
+            .into_iter()
+            .filter_map(|(set, field)| set.then_some(field))
+            .collect::<Vec<&str>>();
+            badges.push(fields.join(","));
+        }
+        badges
+    }
+}
+
+/// A compiled `re:` term, case insensitive like text terms unless its options say otherwise.
+/// Text terms with options are compiled into one too. Terms are compared by their pattern and
+/// options.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    regex: Regex,
+    options: TermOptions,
+}
+
+impl Pattern {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        Self::with_options(pattern, &TermOptions::default())
+    }
+
+    pub fn with_options(pattern: &str, options: &TermOptions) -> Result<Self, String> {
+        let pattern = if options.exact {
+            format!("^(?:{})$", pattern)
+        } else if options.whole_word {
+            format!(r"(?:^|\W)(?:{})(?:\W|$)", pattern)
+        } else {
+            pattern.to_string()
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .build()
+            .map(|regex| Self {
+                regex,
+                options: *options,
+            })
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+
+    pub fn applies_to(&self, field: Field) -> bool {
+        self.options.applies_to(field)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str() && self.options == other.options
+    }
+}
+
+impl Eq for Pattern {}
+
+/// A search term as typed into the search terms pane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchTerm {
+    /// Matched case insensitively against key paths, value names and value data as text, stored
+    /// lowercased. Text with options is a `Regex` instead.
+    Text(String),
+    /// `re:^C:\\Users\\[^\\]+\\AppData\\.*\.exe$`, matched against the same text as `Text` terms.
+    Regex(Pattern),
+    /// `path:HKLM\Software\*\CurrentVersion\Run`, matched against the full path of every key and
+    /// value.
+    Path(PathGlob),
+    /// `hex:4D 5A ?? [2-4] 50 45`, matched against the raw bytes of every value.
+    Bytes(BytePattern),
+    /// `>= 4096`, `in 1..=3`, `& 0x4`, tested on the number a REG_DWORD, REG_DWORD_BIG_ENDIAN or
+    /// REG_QWORD holds.
+    Number(NumberPredicate),
+    /// `date:2024-01-01..2024-01-31`, tested on the FILETIME, SYSTEMTIME or Unix time found in a
+    /// value's data.
+    Date(DateRange),
+    /// `acl:Everyone or Authenticated Users has KEY_SET_VALUE`, tested on the DACL of every key.
+    Acl(AclPredicate),
+    /// `path:Services AND name:ImagePath NOT data:"C:\Windows"`, evaluated on every key and
+    /// value.
+    Query(Box<Query>),
+    /// `exclude:Installer`, keys and values the inner term matches aren't results, and neither
+    /// are the values of keys it matches.
+    Exclude(Box<SearchTerm>),
+}
+
+impl SearchTerm {
+    pub fn parse(term: &str) -> Result<Self, String> {
+        Self::parse_with(term, &TermOptions::default())
+    }
+
+    pub fn parse_with(term: &str, options: &TermOptions) -> Result<Self, String> {
+        let parsed = if !(options.path || options.name || options.data) {
+            Err("It has to be matched against paths, names or data".into())
+        } else {
+            match term.strip_prefix(EXCLUDE_PREFIX) {
+                Some("") => Err(format!("{} needs something to match", EXCLUDE_PREFIX)),
+                Some(excluded) => Self::parse_query_or_term(excluded, options)
+                    .map(|excluded| Self::Exclude(Box::new(excluded))),
+                None => Self::parse_query_or_term(term, options),
+            }
+        };
+        parsed.map_err(|err| format!("Search term \"{}\": {}", term, err))
+    }
+
+    fn parse_query_or_term(term: &str, options: &TermOptions) -> Result<Self, String> {
+        match Query::is_query(term) {
+            true => Query::parse_with(term, options).map(|query| Self::Query(Box::new(query))),
+            false => Self::parse_term(term, options),
+        }
+    }
+
+    /// Parses a single term, never a query.
+    pub fn parse_term(term: &str, options: &TermOptions) -> Result<Self, String> {
+        if let Some(pattern) = term.strip_prefix(HEX_PREFIX) {
+            BytePattern::parse(pattern).map(Self::Bytes)
+        } else if let Some(pattern) = term.strip_prefix(PATH_PREFIX) {
+            PathGlob::parse(pattern).map(Self::Path)
+        } else if let Some(pattern) = term.strip_prefix(REGEX_PREFIX) {
+            Pattern::with_options(pattern, options).map(Self::Regex)
+        } else if let Some(predicate) = term.strip_prefix(ACL_PREFIX) {
+            AclPredicate::parse(predicate).map(Self::Acl)
+        } else if let Some(range) = term.strip_prefix(DATE_PREFIX) {
+            DateRange::parse(range).map(Self::Date)
+        } else if NumberPredicate::is_predicate(term) {
+            NumberPredicate::parse(term).map(Self::Number)
+        } else if *options != TermOptions::default() {
+            Pattern::with_options(&regex::escape(term), options).map(Self::Regex)
+        } else {
+            Ok(Self::Text(term.to_lowercase()))
+        }
+    }
+}
+
+/// Parses every term with its options, the ones that can't be parsed are returned as errors.
+pub fn parse_all<'a>(
+    terms: impl IntoIterator<Item = (&'a String, &'a TermOptions)>,
+) -> (Vec<SearchTerm>, Vec<String>) {
+    let mut parsed = Vec::new();
+    let mut errors = Vec::new();
+    for (term, options) in terms {
+        match SearchTerm::parse_with(term, options) {
+            Ok(term) => parsed.push(term),
+            Err(err) => errors.push(err),
+        }
+    }
+    (parsed, errors)
+}
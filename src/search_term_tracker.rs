@@ -1,4 +1,4 @@
-use std::{collections::BTreeSet, time::Instant};
+use std::{collections::BTreeMap, time::Instant};
 
 use ratatui::{
     style::{Color, Style},
@@ -6,12 +6,12 @@ use ratatui::{
 };
 use tracing::{debug, error};
 
-use crate::{EditorMode, DEBOUNCE, SELECTION_COLOUR};
+use crate::{search_term::TermOptions, EditorMode, DEBOUNCE, SELECTION_COLOUR};
 
 pub struct SearchTermTracker {
     pub search_term_selected: usize,
     pub search_term_last_changed: Instant,
-    pub search_terms: BTreeSet<String>,
+    pub search_terms: BTreeMap<String, TermOptions>,
 }
 
 impl Default for SearchTermTracker {
@@ -19,43 +19,48 @@ impl Default for SearchTermTracker {
         Self {
             search_term_selected: 0,
             search_term_last_changed: Instant::now(),
-            search_terms: BTreeSet::new(),
+            search_terms: BTreeMap::new(),
         }
     }
 }
 
 impl SearchTermTracker {
-    fn get_value_from_index(&self, index: usize) -> Option<String> {
+    fn get_value_from_index(&self, index: usize) -> Option<(String, TermOptions)> {
         if self.search_terms.is_empty() {
             return None;
         }
-        self.search_terms.iter().nth(index).cloned()
+        self.search_terms
+            .iter()
+            .nth(index)
+            .map(|(term, options)| (term.to_owned(), *options))
     }
 
-    pub fn get_value_at_current_index(&self) -> Option<String> {
+    pub fn get_value_at_current_index(&self) -> Option<(String, TermOptions)> {
         self.get_value_from_index(self.search_term_selected)
     }
 
-    pub fn update(&mut self, editor_mode: EditorMode, state: String) {
-        let mut current_index_value = self.get_value_at_current_index();
-        if current_index_value.is_none() && self.search_terms.len() > 0 {
+    pub fn update(&mut self, editor_mode: EditorMode, state: String, options: TermOptions) {
+        let mut current_index_value = self
+            .get_value_at_current_index()
+            .map(|(search_term, _)| search_term);
+        if current_index_value.is_none() && !self.search_terms.is_empty() {
             error!("Error retrieving value from search terms by index when map is not empty. Add/Edit action discarded.");
             return;
         }
         match editor_mode {
             EditorMode::Add => {
-                let _ = self.search_terms.insert(state);
+                let _ = self.search_terms.insert(state, options);
             }
             EditorMode::Edit(original) => {
                 if current_index_value.as_ref().unwrap() == &original {
                     current_index_value = Some(state.to_owned());
                 }
                 self.search_terms.remove(&original);
-                let _ = self.search_terms.insert(state);
+                let _ = self.search_terms.insert(state, options);
             }
         }
         if let Some(current_index_value) = &current_index_value {
-            for (index, search_term) in self.search_terms.iter().enumerate() {
+            for (index, search_term) in self.search_terms.keys().enumerate() {
                 if search_term == current_index_value {
                     if self.search_term_selected != index {
                         self.search_term_selected = index;
@@ -69,7 +74,7 @@ impl SearchTermTracker {
         }
     }
 
-    pub fn remove(&mut self, term: String) {}
+    pub fn remove(&mut self, _term: String) {}
 
     pub fn up(&mut self) {
         if self.search_term_last_changed.elapsed() < DEBOUNCE {
@@ -103,27 +108,31 @@ impl SearchTermTracker {
             search_terms_len
         };
         let current = self.search_term_selected;
-        self.search_term_selected = if current + 1 <= max_index {
-            current + 1
-        } else {
-            0
-        };
+        self.search_term_selected = if current < max_index { current + 1 } else { 0 };
         self.search_term_last_changed = Instant::now();
     }
 
+    /// Each term with its options that aren't the default as badges, eg. `Run [Aa] [name]`.
     pub fn render(&self, pane_selected: bool) -> Vec<Line<'static>> {
         self.search_terms
             .iter()
             .enumerate()
-            .map(|(index, term)| {
-                Line::from(vec![Span::styled(
+            .map(|(index, (term, options))| {
+                let mut spans = vec![Span::styled(
                     term.to_string(),
                     Style::default().fg(if pane_selected && index == self.search_term_selected {
                         SELECTION_COLOUR
                     } else {
                         Color::White
                     }),
-                )])
+                )];
+                for badge in options.badges() {
+                    spans.push(Span::styled(
+                        format!(" [{}]", badge),
+                        Style::default().fg(Color::Yellow),
+                    ));
+                }
+                Line::from(spans)
             })
             .collect::<Vec<Line>>()
     }
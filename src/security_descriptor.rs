@@ -0,0 +1,528 @@
+use std::fmt;
+
+use crate::{
+    hive::{read_u16, read_u32},
+    number_predicate::parse_number,
+};
+
+/// Prefix that turns a term into a test on who a key's DACL grants which rights.
+pub const ACL_PREFIX: &str = "acl:";
+
+const SE_DACL_PRESENT: u16 = 0x0004;
+
+const INHERIT_ONLY_ACE: u8 = 0x08;
+const INHERITED_ACE: u8 = 0x10;
+
+const KEY_QUERY_VALUE: u32 = 0x0001;
+const KEY_SET_VALUE: u32 = 0x0002;
+const KEY_CREATE_SUB_KEY: u32 = 0x0004;
+const KEY_ENUMERATE_SUB_KEYS: u32 = 0x0008;
+const KEY_NOTIFY: u32 = 0x0010;
+const KEY_CREATE_LINK: u32 = 0x0020;
+const DELETE: u32 = 0x0001_0000;
+const READ_CONTROL: u32 = 0x0002_0000;
+const WRITE_DAC: u32 = 0x0004_0000;
+const WRITE_OWNER: u32 = 0x0008_0000;
+const KEY_READ: u32 = READ_CONTROL | KEY_QUERY_VALUE | KEY_ENUMERATE_SUB_KEYS | KEY_NOTIFY;
+const KEY_WRITE: u32 = READ_CONTROL | KEY_SET_VALUE | KEY_CREATE_SUB_KEY;
+const KEY_ALL_ACCESS: u32 = 0x000F_003F;
+const GENERIC_ALL: u32 = 0x1000_0000;
+const GENERIC_EXECUTE: u32 = 0x2000_0000;
+const GENERIC_WRITE: u32 = 0x4000_0000;
+const GENERIC_READ: u32 = 0x8000_0000;
+
+/// Access rights by name, combinations first so a mask is shown by the widest name that fits.
+const RIGHTS: [(&str, u32); 17] = [
+    ("KEY_ALL_ACCESS", KEY_ALL_ACCESS),
+    ("KEY_READ", KEY_READ),
+    ("KEY_WRITE", KEY_WRITE),
+    ("KEY_QUERY_VALUE", KEY_QUERY_VALUE),
+    ("KEY_SET_VALUE", KEY_SET_VALUE),
+    ("KEY_CREATE_SUB_KEY", KEY_CREATE_SUB_KEY),
+    ("KEY_ENUMERATE_SUB_KEYS", KEY_ENUMERATE_SUB_KEYS),
+    ("KEY_NOTIFY", KEY_NOTIFY),
+    ("KEY_CREATE_LINK", KEY_CREATE_LINK),
+    ("DELETE", DELETE),
+    ("READ_CONTROL", READ_CONTROL),
+    ("WRITE_DAC", WRITE_DAC),
+    ("WRITE_OWNER", WRITE_OWNER),
+    ("GENERIC_ALL", GENERIC_ALL),
+    ("GENERIC_EXECUTE", GENERIC_EXECUTE),
+    ("GENERIC_WRITE", GENERIC_WRITE),
+    ("GENERIC_READ", GENERIC_READ),
+];
+
+const WELL_KNOWN_SIDS: [(&str, &str); 19] = [
+    ("S-1-1-0", "Everyone"),
+    ("S-1-2-0", "LOCAL"),
+    ("S-1-3-0", "CREATOR OWNER"),
+    ("S-1-3-1", "CREATOR GROUP"),
+    ("S-1-5-4", "INTERACTIVE"),
+    ("S-1-5-6", "SERVICE"),
+    ("S-1-5-7", "ANONYMOUS LOGON"),
+    ("S-1-5-11", "Authenticated Users"),
+    ("S-1-5-12", "RESTRICTED"),
+    ("S-1-5-18", "SYSTEM"),
+    ("S-1-5-19", "LOCAL SERVICE"),
+    ("S-1-5-20", "NETWORK SERVICE"),
+    ("S-1-5-32-544", "Administrators"),
+    ("S-1-5-32-545", "Users"),
+    ("S-1-5-32-546", "Guests"),
+    ("S-1-5-32-547", "Power Users"),
+    ("S-1-5-32-551", "Backup Operators"),
+    ("S-1-15-2-1", "ALL APPLICATION PACKAGES"),
+    (
+        "S-1-5-80-956008885-3418522649-1831038044-1853292631-2271478464",
+        "TrustedInstaller",
+    ),
+];
+
+/// Groups any unprivileged user is a member of.
+const NON_ADMIN_SIDS: [&str; 9] = [
+    "S-1-1-0",
+    "S-1-2-0",
+    "S-1-5-4",
+    "S-1-5-7",
+    "S-1-5-11",
+    "S-1-5-32-545",
+    "S-1-5-32-546",
+    "S-1-5-32-547",
+    "S-1-15-2-1",
+];
+
+/// Relative ids of the administrator accounts and groups of a machine or domain.
+const ADMIN_RIDS: [u32; 4] = [500, 512, 518, 519];
+
+/// Generic rights stand for these key rights.
+fn map_generic(mask: u32) -> u32 {
+    let mut mapped = mask & !(GENERIC_ALL | GENERIC_EXECUTE | GENERIC_WRITE | GENERIC_READ);
+    if mask & GENERIC_ALL != 0 {
+        mapped |= KEY_ALL_ACCESS;
+    }
+    if mask & (GENERIC_READ | GENERIC_EXECUTE) != 0 {
+        mapped |= KEY_READ;
+    }
+    if mask & GENERIC_WRITE != 0 {
+        mapped |= KEY_WRITE;
+    }
+    mapped
+}
+
+/// A mask as `|` separated right names, bits without a name are added in hex.
+pub fn rights_to_string(mask: u32) -> String {
+    let mut names = Vec::new();
+    let mut rest = mask;
+    for (name, right) in RIGHTS {
+        if right & rest == right {
+            names.push(name.to_string());
+            rest &= !right;
+        }
+    }
+    if rest != 0 || names.is_empty() {
+        names.push(format!("{:#X}", rest));
+    }
+    names.join("|")
+}
+
+fn parse_rights(text: &str) -> Result<u32, String> {
+    let mut mask = 0;
+    for right in text.split(['|', ',']).map(|right| right.trim()) {
+        mask |= match RIGHTS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(right))
+        {
+            Some((_, right)) => *right,
+            None => parse_number(right)
+                .map_err(|_| format!("\"{}\" is not an access right", right))?
+                as u32,
+        };
+    }
+    Ok(mask)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Sid {
+    pub authority: u64,
+    pub sub_authorities: Vec<u32>,
+}
+
+impl Sid {
+    fn read(data: &[u8], offset: usize) -> Result<Self, String> {
+        let invalid = || format!("SID at 0x{:X} is truncated", offset);
+        let count = *data.get(offset + 1).ok_or_else(invalid)? as usize;
+        let authority = data
+            .get(offset + 2..offset + 8)
+            .ok_or_else(invalid)?
+            .iter()
+            .fold(0u64, |authority, byte| authority << 8 | *byte as u64);
+        let sub_authorities = (0..count)
+            .map(|index| read_u32(data, offset + 8 + index * 4).ok_or_else(invalid))
+            .collect::<Result<Vec<u32>, String>>()?;
+        Ok(Self {
+            authority,
+            sub_authorities,
+        })
+    }
+
+    /// `S-1-5-32-544` or the name of a well-known SID, case insensitively.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let text = WELL_KNOWN_SIDS
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(text))
+            .map_or(text, |(sid, _)| sid);
+        let invalid = || format!("\"{}\" is not a SID or well-known group", text);
+        let mut parts = text
+            .strip_prefix("S-1-")
+            .or_else(|| text.strip_prefix("s-1-"))
+            .ok_or_else(invalid)?
+            .split('-');
+        let authority = parts
+            .next()
+            .and_then(|authority| authority.parse().ok())
+            .ok_or_else(invalid)?;
+        let sub_authorities = parts
+            .map(|part| part.parse().map_err(|_| invalid()))
+            .collect::<Result<Vec<u32>, String>>()?;
+        Ok(Self {
+            authority,
+            sub_authorities,
+        })
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        let sid = self.to_string();
+        WELL_KNOWN_SIDS
+            .iter()
+            .find(|(well_known, _)| *well_known == sid)
+            .map(|(_, name)| *name)
+    }
+
+    /// Groups every user belongs to, and accounts of a machine or domain other than its
+    /// administrators.
+    pub fn is_non_admin(&self) -> bool {
+        let account = self.authority == 5
+            && self.sub_authorities.len() == 5
+            && self.sub_authorities[0] == 21
+            && !ADMIN_RIDS.contains(&self.sub_authorities[4]);
+        account || NON_ADMIN_SIDS.contains(&self.to_string().as_str())
+    }
+}
+
+impl fmt::Display for Sid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "S-1-{}", self.authority)?;
+        for sub_authority in self.sub_authorities.iter() {
+            write!(f, "-{}", sub_authority)?;
+        }
+        Ok(())
+    }
+}
+
+/// A SID by its well-known name when it has one.
+struct Trustee<'a>(&'a Sid);
+
+impl fmt::Display for Trustee<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AceType {
+    Allow,
+    Deny,
+    /// Object and callback ACEs, which are kept without their SID.
+    Other(u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ace {
+    pub ace_type: AceType,
+    pub flags: u8,
+    pub mask: u32,
+    pub sid: Option<Sid>,
+}
+
+impl Ace {
+    /// Only passed on to subkeys, it doesn't apply to the key itself.
+    pub fn is_inherit_only(&self) -> bool {
+        self.flags & INHERIT_ONLY_ACE != 0
+    }
+}
+
+impl fmt::Display for Ace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.ace_type {
+            AceType::Allow => write!(f, "allow")?,
+            AceType::Deny => write!(f, "deny")?,
+            AceType::Other(ace_type) => write!(f, "type {}", ace_type)?,
+        }
+        if let Some(sid) = self.sid.as_ref() {
+            write!(f, " {}", Trustee(sid))?;
+        }
+        write!(f, " {}", rights_to_string(self.mask))?;
+        if self.flags & INHERITED_ACE != 0 {
+            write!(f, " (inherited)")?;
+        }
+        if self.is_inherit_only() {
+            write!(f, " (subkeys only)")?;
+        }
+        Ok(())
+    }
+}
+
+/// A self-relative security descriptor as stored in a hive's sk cells. The SACL isn't read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityDescriptor {
+    pub owner: Option<Sid>,
+    pub group: Option<Sid>,
+    /// `None` when there's no DACL, which grants everyone full access.
+    pub dacl: Option<Vec<Ace>>,
+}
+
+impl SecurityDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 20 || data[0] != 1 {
+            return Err("Security descriptor is truncated or not revision 1".into());
+        }
+        let control = read_u16(data, 0x02).unwrap_or(0);
+        let sid_at = |offset: u32| match offset {
+            0 => Ok(None),
+            offset => Sid::read(data, offset as usize).map(Some),
+        };
+        let owner = sid_at(read_u32(data, 0x04).unwrap_or(0))?;
+        let group = sid_at(read_u32(data, 0x08).unwrap_or(0))?;
+        let dacl = match read_u32(data, 0x10).unwrap_or(0) {
+            offset if control & SE_DACL_PRESENT != 0 && offset != 0 => {
+                Some(Self::read_acl(data, offset as usize)?)
+            }
+            _ => None,
+        };
+        Ok(Self { owner, group, dacl })
+    }
+
+    fn read_acl(data: &[u8], offset: usize) -> Result<Vec<Ace>, String> {
+        let count = read_u16(data, offset + 4).ok_or("ACL is truncated")?;
+        let mut aces = Vec::new();
+        let mut ace_offset = offset + 8;
+        for _ in 0..count {
+            let header = data
+                .get(ace_offset..ace_offset + 4)
+                .ok_or_else(|| format!("ACE at 0x{:X} is truncated", ace_offset))?;
+            let size = read_u16(header, 2).unwrap_or(0) as usize;
+            if size < 8 {
+                return Err(format!("ACE at 0x{:X} has an invalid size", ace_offset));
+            }
+            let ace_type = match header[0] {
+                0 => AceType::Allow,
+                1 => AceType::Deny,
+                ace_type => AceType::Other(ace_type),
+            };
+            let sid = match ace_type {
+                AceType::Other(_) => None,
+                _ => Some(Sid::read(data, ace_offset + 8)?),
+            };
+            aces.push(Ace {
+                ace_type,
+                flags: header[1],
+                mask: read_u32(data, ace_offset + 4).unwrap_or(0),
+                sid,
+            });
+            ace_offset += size;
+        }
+        Ok(aces)
+    }
+
+    /// Rights the DACL grants a SID directly, generic rights mapped to key rights. ACEs are
+    /// applied in order so a deny ahead of an allow takes the right away.
+    pub fn granted(&self, sid: &Sid) -> u32 {
+        let aces = match self.dacl.as_ref() {
+            Some(aces) => aces,
+            None => return KEY_ALL_ACCESS,
+        };
+        let (mut granted, mut denied) = (0, 0);
+        for ace in aces.iter().filter(|ace| !ace.is_inherit_only()) {
+            if ace.sid.as_ref() != Some(sid) {
+                continue;
+            }
+            let mask = map_generic(ace.mask);
+            match ace.ace_type {
+                AceType::Allow => granted |= mask & !denied,
+                AceType::Deny => denied |= mask & !granted,
+                AceType::Other(_) => {}
+            }
+        }
+        granted
+    }
+
+    /// SIDs the DACL mentions, in order and without repeats.
+    pub fn trustees(&self) -> Vec<&Sid> {
+        let mut trustees: Vec<&Sid> = Vec::new();
+        for sid in self
+            .dacl
+            .iter()
+            .flatten()
+            .filter_map(|ace| ace.sid.as_ref())
+        {
+            if !trustees.contains(&sid) {
+                trustees.push(sid);
+            }
+        }
+        trustees
+    }
+}
+
+impl fmt::Display for SecurityDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(owner) = self.owner.as_ref() {
+            parts.push(format!("owner {}", Trustee(owner)));
+        }
+        if let Some(group) = self.group.as_ref() {
+            parts.push(format!("group {}", Trustee(group)));
+        }
+        match self.dacl.as_ref() {
+            Some(aces) => parts.extend(aces.iter().map(|ace| ace.to_string())),
+            None => parts.push("no DACL, everyone has full access".into()),
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TrusteeTerm {
+    Sid(Sid),
+    /// Any group or account that isn't an administrator.
+    NonAdmins,
+}
+
+/// `Everyone or Authenticated Users has KEY_SET_VALUE`, matches keys whose DACL grants any of
+/// the trustees any of the rights.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AclPredicate {
+    trustees: Vec<TrusteeTerm>,
+    rights: u32,
+}
+
+impl AclPredicate {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let lowercase = text.to_ascii_lowercase();
+        let (trustees, rights) = lowercase.split_once(" has ").ok_or_else(|| {
+            format!(
+                "\"{}\" is not an ACL test, expected TRUSTEES has RIGHTS",
+                text
+            )
+        })?;
+        let trustees = trustees
+            .split(',')
+            .flat_map(|part| part.split(" or "))
+            .filter(|trustee| !trustee.trim().is_empty())
+            .map(|trustee| match trustee.trim() {
+                "non-admins" => Ok(TrusteeTerm::NonAdmins),
+                trustee => Sid::parse(trustee).map(TrusteeTerm::Sid),
+            })
+            .collect::<Result<Vec<TrusteeTerm>, String>>()?;
+        if trustees.is_empty() {
+            return Err(format!("\"{}\" names no trustees", text));
+        }
+        let rights = map_generic(parse_rights(&rights.replace(" or ", "|"))?);
+        if rights == 0 {
+            return Err(format!("\"{}\" names no rights", text));
+        }
+        Ok(Self { trustees, rights })
+    }
+
+    pub fn matches(&self, descriptor: &SecurityDescriptor) -> bool {
+        let everyone = Sid::parse("S-1-1-0").unwrap();
+        let trustees = match descriptor.dacl {
+            Some(_) => descriptor.trustees(),
+            None => vec![&everyone],
+        };
+        trustees.into_iter().any(|sid| {
+            let named = self.trustees.iter().any(|trustee| match trustee {
+                TrusteeTerm::Sid(named) => named == sid,
+                TrusteeTerm::NonAdmins => sid.is_non_admin(),
+            });
+            named && descriptor.granted(sid) & self.rights != 0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_hive::security_descriptor;
+
+    #[test]
+    fn decodes_owner_group_and_dacl() {
+        let descriptor = SecurityDescriptor::parse(&security_descriptor()).unwrap();
+        assert_eq!(descriptor.owner, Some(Sid::parse("S-1-5-32-544").unwrap()));
+        assert_eq!(
+            descriptor.to_string(),
+            "owner Administrators, group SYSTEM, allow Everyone KEY_SET_VALUE"
+        );
+        assert_eq!(
+            rights_to_string(0x000F_003F | GENERIC_READ | 0x0100_0000),
+            "KEY_ALL_ACCESS|GENERIC_READ|0x1000000"
+        );
+        assert_eq!(
+            rights_to_string(KEY_READ | KEY_SET_VALUE),
+            "KEY_READ|KEY_SET_VALUE"
+        );
+        assert!(Sid::parse("S-1-5-21-1-2-3-1001").unwrap().is_non_admin());
+        assert!(!Sid::parse("S-1-5-21-1-2-3-500").unwrap().is_non_admin());
+        assert!(!Sid::parse("system").unwrap().is_non_admin());
+        assert!(SecurityDescriptor::parse(&[1, 0, 4]).is_err());
+    }
+
+    #[test]
+    fn tests_rights_granted_to_trustees() {
+        let descriptor = SecurityDescriptor::parse(&security_descriptor()).unwrap();
+        for (term, matches) in [
+            ("Everyone or Authenticated Users has KEY_SET_VALUE", true),
+            ("non-admins has KEY_WRITE", true),
+            ("S-1-1-0 has KEY_CREATE_SUB_KEY|WRITE_DAC", false),
+            ("Users, Guests has GENERIC_WRITE", false),
+        ] {
+            let predicate = AclPredicate::parse(term).unwrap();
+            assert_eq!(predicate.matches(&descriptor), matches, "{}", term);
+        }
+
+        let everyone = Sid::parse("Everyone").unwrap();
+        let ace = |ace_type, flags, mask| Ace {
+            ace_type,
+            flags,
+            mask,
+            sid: Some(everyone.to_owned()),
+        };
+        let denied = SecurityDescriptor {
+            owner: None,
+            group: None,
+            dacl: Some(vec![
+                ace(AceType::Deny, 0, KEY_SET_VALUE),
+                ace(AceType::Allow, INHERIT_ONLY_ACE, KEY_ALL_ACCESS),
+                ace(AceType::Allow, INHERITED_ACE, GENERIC_WRITE),
+            ]),
+        };
+        assert_eq!(denied.granted(&everyone), KEY_WRITE & !KEY_SET_VALUE);
+        let null_dacl = SecurityDescriptor {
+            dacl: None,
+            ..denied
+        };
+        assert!(AclPredicate::parse("non-admins has key_set_value")
+            .unwrap()
+            .matches(&null_dacl));
+
+        for term in [
+            "Everyone",
+            "Nobody has KEY_READ",
+            "Everyone has KEY_NOTHING",
+        ] {
+            assert!(AclPredicate::parse(term).is_err(), "{}", term);
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Read, Write},
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use parking_lot::Mutex;
+
+use crate::{
+    hive::{read_u32, read_u64},
+    memory_source::MemorySource,
+    registry_source::{KeyInfo, RegType, RegistryValue},
+    timestamp::UNIX_EPOCH_FILETIME,
+};
+
+const SNAPSHOT_SIGNATURE: &[u8] = b"WRSS";
+pub const SNAPSHOT_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 16;
+const RECORD_KEY: u8 = 1;
+const RECORD_END: u8 = 0;
+
+/// The time now as a FILETIME.
+fn filetime_now() -> u64 {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    UNIX_EPOCH_FILETIME + (since_epoch.as_nanos() / 100) as u64
+}
+
+/// One key as it was captured, `path` is relative to `root` and empty for the root itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotKey {
+    pub root: String,
+    pub path: String,
+    pub info: KeyInfo,
+    pub annotation: Option<String>,
+    pub values: Vec<(String, RegistryValue)>,
+}
+
+fn put_bytes(record: &mut Vec<u8>, bytes: &[u8]) {
+    record.extend((bytes.len() as u32).to_le_bytes());
+    record.extend(bytes);
+}
+
+fn put_option(record: &mut Vec<u8>, text: Option<&str>) {
+    match text {
+        Some(text) => {
+            record.push(1);
+            put_bytes(record, text.as_bytes());
+        }
+        None => record.push(0),
+    }
+}
+
+impl SnapshotKey {
+    fn encode(&self) -> Vec<u8> {
+        let mut record = vec![RECORD_KEY];
+        put_bytes(&mut record, self.root.as_bytes());
+        put_bytes(&mut record, self.path.as_bytes());
+        record.extend(self.info.last_write_time.to_le_bytes());
+        put_option(&mut record, self.info.class_name.as_deref());
+        for field in [
+            self.info.subkey_count,
+            self.info.value_count,
+            self.info.max_value_len,
+        ] {
+            record.extend(field.to_le_bytes());
+        }
+        put_option(&mut record, self.annotation.as_deref());
+        record.extend((self.values.len() as u32).to_le_bytes());
+        for (name, value) in self.values.iter() {
+            put_bytes(&mut record, name.as_bytes());
+            record.extend((value.vtype as u32).to_le_bytes());
+            put_bytes(&mut record, &value.bytes);
+        }
+        record
+    }
+}
+
+/// Cursor over the decompressed records of a snapshot.
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| format!("Record at 0x{:X} is truncated", self.offset))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let value = read_u32(self.data, self.offset)
+            .ok_or_else(|| format!("Record at 0x{:X} is truncated", self.offset))?;
+        self.offset += 4;
+        Ok(value)
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let value = read_u64(self.data, self.offset)
+            .ok_or_else(|| format!("Record at 0x{:X} is truncated", self.offset))?;
+        self.offset += 8;
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        let offset = self.offset;
+        String::from_utf8(self.bytes(len)?.to_vec())
+            .map_err(|_| format!("String at 0x{:X} is not UTF-8", offset))
+    }
+
+    fn option(&mut self) -> Result<Option<String>, String> {
+        match self.u8()? {
+            0 => Ok(None),
+            _ => Ok(Some(self.string()?)),
+        }
+    }
+
+    /// The next key, `None` at the end marker.
+    fn key(&mut self) -> Result<Option<SnapshotKey>, String> {
+        let offset = self.offset;
+        match self.u8()? {
+            RECORD_END => return Ok(None),
+            RECORD_KEY => {}
+            tag => return Err(format!("Unknown record type {} at 0x{:X}", tag, offset)),
+        }
+        let root = self.string()?;
+        let path = self.string()?;
+        let last_write_time = self.u64()?;
+        let class_name = self.option()?;
+        let info = KeyInfo {
+            last_write_time,
+            class_name,
+            subkey_count: self.u32()?,
+            value_count: self.u32()?,
+            max_value_len: self.u32()?,
+        };
+        let annotation = self.option()?;
+        let mut values = Vec::new();
+        for _ in 0..self.u32()? {
+            let name = self.string()?;
+            let vtype = RegType::from_u32(self.u32()?).unwrap_or(RegType::REG_NONE);
+            let len = self.u32()? as usize;
+            let bytes = self.bytes(len)?.to_vec();
+            values.push((name, RegistryValue { vtype, bytes }));
+        }
+        Ok(Some(SnapshotKey {
+            root,
+            path,
+            info,
+            annotation,
+            values,
+        }))
+    }
+}
+
+/// What a finished capture wrote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotSummary {
+    pub keys: usize,
+    pub values: usize,
+}
+
+/// A snapshot file being written. Keys can be added from any number of worker threads, each is
+/// encoded before the lock is taken and written as one record.
+pub struct SnapshotWriter {
+    encoder: Mutex<Option<ZlibEncoder<BufWriter<File>>>>,
+    keys: AtomicUsize,
+    values: AtomicUsize,
+}
+
+impl SnapshotWriter {
+    /// Creates the file and writes the uncompressed header, the records after it are zlib
+    /// compressed.
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let error = |err: std::io::Error| format!("{}: {}", path.display(), err);
+        let mut file = BufWriter::new(File::create(path).map_err(error)?);
+        let mut header = SNAPSHOT_SIGNATURE.to_vec();
+        header.extend(SNAPSHOT_VERSION.to_le_bytes());
+        header.extend(filetime_now().to_le_bytes());
+        file.write_all(&header).map_err(error)?;
+        Ok(Self {
+            encoder: Mutex::new(Some(ZlibEncoder::new(file, Compression::default()))),
+            keys: AtomicUsize::new(0),
+            values: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn add(&self, key: &SnapshotKey) -> Result<(), String> {
+        let record = key.encode();
+        match self.encoder.lock().as_mut() {
+            Some(encoder) => encoder.write_all(&record).map_err(|err| err.to_string())?,
+            None => return Err("Snapshot has already been finished".into()),
+        }
+        self.keys.fetch_add(1, Ordering::SeqCst);
+        self.values.fetch_add(key.values.len(), Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Writes the end marker and flushes the file, no more keys can be added after this.
+    pub fn finish(&self) -> Result<SnapshotSummary, String> {
+        let mut encoder = match self.encoder.lock().take() {
+            Some(encoder) => encoder,
+            None => return Err("Snapshot has already been finished".into()),
+        };
+        encoder
+            .write_all(&[RECORD_END])
+            .and_then(|_| encoder.finish())
+            .and_then(|mut file| file.flush())
+            .map_err(|err| err.to_string())?;
+        Ok(SnapshotSummary {
+            keys: self.keys.load(Ordering::SeqCst),
+            values: self.values.load(Ordering::SeqCst),
+        })
+    }
+}
+
+/// A snapshot read back in full, `captured` is a FILETIME.
+#[derive(Debug)]
+pub struct Snapshot {
+    pub version: u32,
+    pub captured: u64,
+    pub keys: Vec<SnapshotKey>,
+    pub warnings: Vec<String>,
+}
+
+/// Keys before a truncated or corrupt record are kept, the problem is returned as a warning.
+pub fn parse(data: &[u8]) -> Result<Snapshot, String> {
+    if data.len() < HEADER_SIZE || &data[0..4] != SNAPSHOT_SIGNATURE {
+        return Err("Not a snapshot file".into());
+    }
+    let version = read_u32(data, 0x04).unwrap_or(0);
+    if version != SNAPSHOT_VERSION {
+        return Err(format!("Unsupported snapshot version {}", version));
+    }
+    let mut warnings = Vec::new();
+    let mut body = Vec::new();
+    if let Err(err) = ZlibDecoder::new(&data[HEADER_SIZE..]).read_to_end(&mut body) {
+        warnings.push(format!("Decompression stopped early: {}", err));
+    }
+    let mut reader = Reader {
+        data: &body,
+        offset: 0,
+    };
+    let mut keys = Vec::new();
+    loop {
+        match reader.key() {
+            Ok(Some(key)) => keys.push(key),
+            Ok(None) => break,
+            Err(err) => {
+                warnings.push(err);
+                break;
+            }
+        }
+    }
+    Ok(Snapshot {
+        version,
+        captured: read_u64(data, 0x08).unwrap_or(0),
+        keys,
+        warnings,
+    })
+}
+
+/// Loads every captured key into `source`, returns the warnings from reading the file.
+pub fn load(path: &Path, source: &mut MemorySource) -> Result<Vec<String>, String> {
+    let data = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let snapshot = parse(&data).map_err(|err| format!("{}: {}", path.display(), err))?;
+    for key in snapshot.keys {
+        source.set_key_info(&key.root, &key.path, key.info);
+        if let Some(annotation) = key.annotation.as_deref() {
+            source.set_annotation(&key.root, &key.path, annotation);
+        }
+        for (name, value) in key.values {
+            source.set_value(&key.root, &key.path, &name, value);
+        }
+    }
+    Ok(snapshot.warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{registry_source::RegistrySource, test_hive::temp_dir};
+
+    fn key(path: &str, values: Vec<(String, RegistryValue)>) -> SnapshotKey {
+        SnapshotKey {
+            root: "HKEY_LOCAL_MACHINE".into(),
+            path: path.into(),
+            info: KeyInfo {
+                last_write_time: 133_000_000_000_000_000,
+                class_name: Some("Class".into()),
+                subkey_count: 0,
+                value_count: values.len() as u32,
+                max_value_len: 0,
+            },
+            annotation: None,
+            values,
+        }
+    }
+
+    fn capture(name: &str, keys: &[SnapshotKey]) -> Vec<u8> {
+        let directory = temp_dir(name);
+        let path = directory.join("capture.snapshot");
+        let writer = SnapshotWriter::create(&path).unwrap();
+        for key in keys {
+            writer.add(key).unwrap();
+        }
+        let summary = writer.finish().unwrap();
+        assert_eq!(summary.keys, keys.len());
+        assert!(writer.add(&keys[0]).is_err());
+        let data = fs::read(&path).unwrap();
+        fs::remove_dir_all(directory).unwrap();
+        data
+    }
+
+    #[test]
+    fn round_trips_keys_and_values() {
+        let keys = vec![
+            key("", Vec::new()),
+            key(
+                "SOFTWARE\\Run",
+                vec![
+                    (
+                        "Updater".to_string(),
+                        RegistryValue {
+                            vtype: RegType::REG_SZ,
+                            bytes: b"e\0v\0i\0l\0\0\0".to_vec(),
+                        },
+                    ),
+                    (
+                        String::new(),
+                        RegistryValue {
+                            vtype: RegType::REG_BINARY,
+                            bytes: vec![0; 4096],
+                        },
+                    ),
+                ],
+            ),
+        ];
+        let data = capture("snapshot_round_trip", &keys);
+        //the zeroes compress well
+        assert!(data.len() < 1024);
+        let snapshot = parse(&data).unwrap();
+        assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+        assert!(snapshot.captured > UNIX_EPOCH_FILETIME);
+        assert!(snapshot.warnings.is_empty());
+        assert_eq!(snapshot.keys, keys);
+    }
+
+    #[test]
+    fn keeps_keys_before_a_truncated_record() {
+        let keys = (0..50)
+            .map(|index| key(&format!("Key{}", index), Vec::new()))
+            .collect::<Vec<SnapshotKey>>();
+        let data = capture("snapshot_truncated", &keys);
+        let snapshot = parse(&data[..data.len() - 16]).unwrap();
+        assert!(!snapshot.warnings.is_empty());
+        assert!(snapshot.keys.len() < keys.len());
+        assert_eq!(snapshot.keys[..], keys[..snapshot.keys.len()]);
+    }
+
+    #[test]
+    fn rejects_other_files_and_versions() {
+        assert!(parse(b"regf").is_err());
+        let mut data = capture("snapshot_version", &[key("", Vec::new())]);
+        data[0x04] = 2;
+        assert!(parse(&data).unwrap_err().contains("version 2"));
+    }
+
+    #[test]
+    fn loads_into_a_memory_source() {
+        let mut deleted = key("Gone", Vec::new());
+        deleted.annotation = Some("deleted".into());
+        let data = capture("snapshot_load", &[key("A\\B", Vec::new()), deleted]);
+        let directory = temp_dir("snapshot_load_file");
+        let path = directory.join("capture.snapshot");
+        fs::write(&path, data).unwrap();
+        let mut source = MemorySource::default();
+        assert!(load(&path, &mut source).unwrap().is_empty());
+        fs::remove_dir_all(directory).unwrap();
+        let b = source.open_key("HKEY_LOCAL_MACHINE", "a\\b").unwrap();
+        let info = source.key_info(&b).unwrap();
+        assert_eq!(info.class_name.as_deref(), Some("Class"));
+        assert_eq!(info.last_write_time, 133_000_000_000_000_000);
+        let gone = source.open_key("HKEY_LOCAL_MACHINE", "Gone").unwrap();
+        assert_eq!(source.annotation(&gone).as_deref(), Some("deleted"));
+    }
+}
@@ -1,6 +1,8 @@
 use crate::{
+    offline_source::SelectedSources,
     root::{Root, SelectedRoots},
     search_term_tracker::SearchTermTracker,
+    worker_manager::SearchOptions,
     DEBOUNCE, SELECTION_COLOUR,
 };
 use parking_lot::{Mutex, RwLock};
@@ -11,7 +13,7 @@ use ratatui::{
 use std::{
     collections::BTreeSet,
     sync::{
-        atomic::{AtomicBool, AtomicU8, Ordering},
+        atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
         Arc,
     },
     time::Instant,
@@ -19,24 +21,30 @@ use std::{
 use strum::IntoEnumIterator;
 use tokio::sync::Notify;
 
+pub type RunTimer = Arc<RwLock<Option<(Instant, Option<Instant>)>>>;
+
 pub struct StaticSelection {
     pub pane_selected: Arc<AtomicU8>,       //horizontal
     pane_last_changed: Arc<Mutex<Instant>>, //horizontal
 
     pub search_term_tracker: Arc<RwLock<SearchTermTracker>>,
+    pub search_options: Arc<RwLock<SearchOptions>>,
 
-    root_selected: Arc<AtomicU8>,
+    root_selected: Arc<AtomicUsize>,
     root_selection_last_changed: Arc<Mutex<Instant>>,
 
     pub selected_roots: Arc<RwLock<SelectedRoots>>,
+    pub selected_sources: Arc<RwLock<SelectedSources>>, //searched instead of the live registry when not empty
 
     pub running: Arc<Mutex<bool>>,
-    pub timer: Arc<RwLock<Option<(Instant, Option<Instant>)>>>,
+    pub timer: RunTimer,
     pub run_control_temporarily_disabled: Arc<AtomicBool>, //running thread resets this once closed
     pub stop: Arc<AtomicBool>,                             //running thread resets this once closed
     pub stop_notify: Arc<Notify>,
 
     pub results: Arc<Mutex<BTreeSet<String>>>,
+    pub run_errors: Arc<Mutex<BTreeSet<String>>>, //why the last run couldn't search everything, shown above the results
+    pub setup_errors: Arc<Mutex<Vec<String>>>, //arguments that couldn't be loaded, added to every run's errors
 }
 
 impl Default for StaticSelection {
@@ -44,31 +52,49 @@ impl Default for StaticSelection {
         Self {
             pane_selected: Arc::new(AtomicU8::new(0)),
             pane_last_changed: Arc::new(Mutex::new(Instant::now())),
-            root_selected: Arc::new(AtomicU8::new(0)),
+            root_selected: Arc::new(AtomicUsize::new(0)),
             root_selection_last_changed: Arc::new(Mutex::new(Instant::now())),
             search_term_tracker: Arc::new(RwLock::new(SearchTermTracker::default())),
+            search_options: Arc::new(RwLock::new(SearchOptions::default())),
             selected_roots: Arc::new(RwLock::new(SelectedRoots::default())),
+            selected_sources: Arc::new(RwLock::new(SelectedSources::default())),
             running: Arc::new(Mutex::new(false)),
             timer: Arc::new(RwLock::new(None)),
             run_control_temporarily_disabled: Arc::new(AtomicBool::new(false)),
             stop: Arc::new(AtomicBool::new(false)),
             stop_notify: Arc::new(Notify::new()),
             results: Arc::new(Mutex::new(BTreeSet::new())),
+            run_errors: Arc::new(Mutex::new(BTreeSet::new())),
+            setup_errors: Arc::new(Mutex::new(Vec::new())),
         }
     }
 }
 
 impl StaticSelection {
+    /// Offline sources replace the live roots in the list when any were given.
     pub fn generate_root_list(&self) -> Vec<Line<'static>> {
         let root_selected = self.root_selected.load(Ordering::SeqCst);
         let pane_selected = self.pane_selected.load(Ordering::SeqCst) == 0;
-        Root::iter()
-            .map(|root| {
-                let root_enabled = self.selected_roots.read().is_enabled(&root);
+        let selected_sources = self.selected_sources.read();
+        let roots = if selected_sources.is_empty() {
+            let selected_roots = self.selected_roots.read();
+            Root::iter()
+                .map(|root| (root.to_string(), selected_roots.is_enabled(&root)))
+                .collect::<Vec<(String, bool)>>()
+        } else {
+            selected_sources
+                .iter()
+                .map(|(source, enabled)| (source.to_string(), *enabled))
+                .collect::<Vec<(String, bool)>>()
+        };
+        roots
+            .into_iter()
+            .enumerate()
+            .map(|(index, (root, root_enabled))| {
                 Line::from(vec![
                     Span::styled(
-                        format!("{:38}", root.to_string(),),
-                        Style::default().fg(if pane_selected && root as u8 == root_selected {
+                        format!("{:38}", root),
+                        Style::default().fg(if pane_selected && index == root_selected {
                             SELECTION_COLOUR
                         } else {
                             Color::White
@@ -87,17 +113,38 @@ impl StaticSelection {
             .collect::<Vec<Line>>()
     }
 
+    pub fn root_selected(&self) -> usize {
+        self.root_selected.load(Ordering::SeqCst)
+    }
+
+    fn root_count(&self) -> usize {
+        let selected_sources = self.selected_sources.read();
+        if selected_sources.is_empty() {
+            Root::iter().count()
+        } else {
+            selected_sources.len()
+        }
+    }
+
     pub fn generate_results(&self) -> Vec<Line<'static>> {
-        self.results
+        let mut lines = self
+            .run_errors
             .lock()
             .iter()
-            .map(|result| {
+            .map(|run_error| {
                 Line::from(vec![Span::styled(
-                    result.to_string(),
-                    Style::default().fg(Color::White),
+                    run_error.to_string(),
+                    Style::default().fg(Color::Red),
                 )])
             })
-            .collect::<Vec<Line>>()
+            .collect::<Vec<Line>>();
+        lines.extend(self.results.lock().iter().map(|result| {
+            Line::from(vec![Span::styled(
+                result.to_string(),
+                Style::default().fg(Color::White),
+            )])
+        }));
+        lines
     }
 
     pub fn pane_left(&self) {
@@ -134,7 +181,7 @@ impl StaticSelection {
         }
         let current_value = self.root_selected.load(Ordering::SeqCst);
         let new_value = if current_value == 0 {
-            9
+            self.root_count() - 1
         } else {
             current_value - 1
         };
@@ -146,14 +193,17 @@ impl StaticSelection {
         if self.root_selection_last_changed.lock().elapsed() < DEBOUNCE {
             return;
         }
-        let new_value = (self.root_selected.load(Ordering::SeqCst) + 1) % 10;
+        let new_value = (self.root_selected.load(Ordering::SeqCst) + 1) % self.root_count();
         self.root_selected.store(new_value, Ordering::SeqCst);
         *self.root_selection_last_changed.lock() = Instant::now();
     }
 
     pub fn root_toggle(&self) {
         let selected = self.root_selected.load(Ordering::SeqCst);
-        if let Some(root) = Root::from_u8(selected) {
+        let mut selected_sources = self.selected_sources.write();
+        if !selected_sources.is_empty() {
+            selected_sources.toggle(selected);
+        } else if let Some(root) = Root::from_u8(selected as u8) {
             self.selected_roots.write().toggle(&root);
         }
     }
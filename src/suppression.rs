@@ -0,0 +1,156 @@
+use std::{fs, path::Path, path::PathBuf};
+
+use directories::BaseDirs;
+
+use crate::{path_glob, search_term::SearchTerm};
+
+/// Prefix of a suppression list line that's matched against values rather than paths.
+const VALUE_PREFIX: &str = "value:";
+
+/// The list used when `--suppressions` isn't given, if it exists.
+pub fn default_path() -> Option<PathBuf> {
+    let path = BaseDirs::new()?
+        .config_dir()
+        .join("windows_registry_search/suppressions.txt");
+    path.exists().then_some(path)
+}
+
+/// Well-known noisy places whose results are left out of every run. Each line of the file is
+/// a path prefix, eg. `HKLM\SOFTWARE\Classes\Installer`, which suppresses the key and
+/// everything below it, or `value:` followed by a search term, eg. `value:re:^MRUList`,
+/// which suppresses the values it matches. Blank lines and lines starting with `#` are
+/// skipped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SuppressionList {
+    /// Full `ROOT\path` prefixes with their root abbreviations expanded, lowercased.
+    prefixes: Vec<String>,
+    value_terms: Vec<SearchTerm>,
+}
+
+impl SuppressionList {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut list = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.strip_prefix(VALUE_PREFIX) {
+                Some(term) => {
+                    let term = SearchTerm::parse(term)
+                        .and_then(|term| match term {
+                            SearchTerm::Exclude(_) => {
+                                Err(format!("\"{}\" can't be an exclusion", line))
+                            }
+                            SearchTerm::Path(_) => Err(format!(
+                                "\"{}\" only matches paths, list it without {}",
+                                line, VALUE_PREFIX
+                            )),
+                            SearchTerm::Query(query) if query.only_paths() => Err(format!(
+                                "\"{}\" only matches paths, list them without {}",
+                                line, VALUE_PREFIX
+                            )),
+                            term => Ok(term),
+                        })
+                        .map_err(|err| format!("Line {}: {}", number + 1, err))?;
+                    list.value_terms.push(term);
+                }
+                None => {
+                    let mut components = line.split('\\').filter(|component| !component.is_empty());
+                    let root = components.next().map(path_glob::expand_root);
+                    let prefix = root
+                        .into_iter()
+                        .chain(components)
+                        .collect::<Vec<&str>>()
+                        .join("\\");
+                    list.prefixes.push(prefix.to_lowercase());
+                }
+            }
+        }
+        Ok(list)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        Self::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn len(&self) -> usize {
+        self.prefixes.len() + self.value_terms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether a key, and so every value in it, is at or below a suppressed prefix.
+    pub fn suppresses_path(&self, full_path: &str) -> bool {
+        let full_path = full_path.to_lowercase();
+        self.prefixes.iter().any(|prefix| {
+            full_path
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('\\'))
+        })
+    }
+
+    /// Terms whose matching values are suppressed.
+    pub fn value_terms(&self) -> &[SearchTerm] {
+        &self.value_terms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_prefixes_and_value_terms() {
+        let list = SuppressionList::parse(
+            "# Noise\n\nHKLM\\SOFTWARE\\Classes\\Installer\n  HKEY_CURRENT_USER\\Software\\Classes\\Interface\\  \nvalue:re:^MRUList\n",
+        )
+        .unwrap();
+        assert_eq!(list.len(), 3);
+        for path in [
+            "HKEY_LOCAL_MACHINE\\SOFTWARE\\Classes\\Installer",
+            "hkey_local_machine\\software\\classes\\installer\\Products\\1234",
+            "HKEY_CURRENT_USER\\Software\\Classes\\Interface\\{0000}",
+        ] {
+            assert!(list.suppresses_path(path), "{}", path);
+        }
+        for path in [
+            "HKEY_LOCAL_MACHINE\\SOFTWARE\\Classes\\InstallerCache",
+            "HKEY_LOCAL_MACHINE\\SOFTWARE\\Classes",
+        ] {
+            assert!(!list.suppresses_path(path), "{}", path);
+        }
+        assert_eq!(
+            list.value_terms(),
+            [SearchTerm::parse("re:^MRUList").unwrap()]
+        );
+        assert_eq!(
+            SuppressionList::parse("value:path:Run AND name:MRUList")
+                .unwrap()
+                .len(),
+            1
+        );
+        for (text, error) in [
+            ("value:re:(", "Line 1: Search term"),
+            (
+                "# Noise\nvalue:exclude:temp",
+                "Line 2: \"value:exclude:temp\" can't",
+            ),
+            (
+                "\nvalue:path:HKLM\\*\\Run",
+                "Line 2: \"value:path:HKLM\\*\\Run\" only",
+            ),
+            (
+                "value:path:Run OR path:RunOnce",
+                "Line 1: \"value:path:Run OR path:RunOnce\" only",
+            ),
+        ] {
+            let err = SuppressionList::parse(text).unwrap_err();
+            assert!(err.starts_with(error), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,399 @@
+//! Builds small regf hives in memory for tests.
+
+use std::{env, fs, path::PathBuf, process};
+
+use crate::hive::{base_block_checksum, read_u32, BASE_BLOCK_SIZE, KEY_COMP_NAME, KEY_HIVE_ENTRY};
+
+const BIG_DATA_SEGMENT_SIZE: usize = 16344;
+const NONE: u32 = 0xFFFFFFFF;
+pub const DEFAULT_TIME: u64 = 132_000_000_000_000_000;
+
+/// A key and everything below it.
+#[derive(Debug, Clone)]
+pub struct TestKey {
+    pub name: String,
+    pub class: Option<String>,
+    pub time: u64,
+    pub values: Vec<(String, u32, Vec<u8>)>,
+    pub keys: Vec<TestKey>,
+}
+
+impl TestKey {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            class: None,
+            time: DEFAULT_TIME,
+            values: Vec::new(),
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn class(mut self, class: &str) -> Self {
+        self.class = Some(class.to_string());
+        self
+    }
+
+    pub fn time(mut self, time: u64) -> Self {
+        self.time = time;
+        self
+    }
+
+    pub fn value(mut self, name: &str, data_type: u32, bytes: Vec<u8>) -> Self {
+        self.values.push((name.to_string(), data_type, bytes));
+        self
+    }
+
+    pub fn key(mut self, key: TestKey) -> Self {
+        self.keys.push(key);
+        self
+    }
+}
+
+/// NUL terminated UTF-16LE, like REG_SZ data.
+pub fn sz(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .chain([0])
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+/// Latin-1, the "compressed" form of key and value names.
+fn latin1(text: &str) -> Vec<u8> {
+    text.chars().map(|ch| ch as u8).collect()
+}
+
+/// A self-relative security descriptor, owned by Administrators and granting Everyone
+/// KEY_SET_VALUE.
+pub fn security_descriptor() -> Vec<u8> {
+    let sid = |authority: u8, sub_authorities: &[u32]| {
+        let mut sid = vec![1, sub_authorities.len() as u8, 0, 0, 0, 0, 0, authority];
+        for sub_authority in sub_authorities {
+            sid.extend(sub_authority.to_le_bytes());
+        }
+        sid
+    };
+    let owner = sid(5, &[32, 544]);
+    let group = sid(5, &[18]);
+    let everyone = sid(1, &[0]);
+    let mut ace = vec![0, 0];
+    ace.extend((8 + everyone.len() as u16).to_le_bytes());
+    ace.extend(2u32.to_le_bytes());
+    ace.extend(everyone);
+    let mut acl = vec![2, 0];
+    acl.extend((8 + ace.len() as u16).to_le_bytes());
+    acl.extend(1u16.to_le_bytes());
+    acl.extend(0u16.to_le_bytes());
+    acl.extend(ace);
+    let header = 20u32;
+    let mut descriptor = vec![1, 0];
+    descriptor.extend(0x8004u16.to_le_bytes());
+    descriptor.extend(header.to_le_bytes());
+    descriptor.extend((header + owner.len() as u32).to_le_bytes());
+    descriptor.extend(0u32.to_le_bytes());
+    descriptor.extend((header + (owner.len() + group.len()) as u32).to_le_bytes());
+    descriptor.extend(owner);
+    descriptor.extend(group);
+    descriptor.extend(acl);
+    descriptor
+}
+
+/// The hive bins being written, offsets are relative to the first hbin like cell offsets.
+struct Writer {
+    data: Vec<u8>,
+    minor_version: u32,
+    security: u32,
+    offsets: Vec<(String, u32)>,
+}
+
+impl Writer {
+    fn cell(&mut self, bytes: &[u8], allocated: bool) -> u32 {
+        let size = (bytes.len() + 4 + 7) & !7;
+        let offset = self.data.len() as u32;
+        let size_field = match allocated {
+            true => -(size as i32),
+            false => size as i32,
+        };
+        self.data.extend(size_field.to_le_bytes());
+        self.data.extend(bytes);
+        self.data.resize(offset as usize + size, 0);
+        offset
+    }
+
+    fn alloc(&mut self, bytes: &[u8]) -> u32 {
+        self.cell(bytes, true)
+    }
+
+    fn value(&mut self, name: &str, data_type: u32, bytes: &[u8]) -> u32 {
+        let (data_size, data_offset) = if bytes.len() <= 4 {
+            let mut resident = [0; 4];
+            resident[..bytes.len()].copy_from_slice(bytes);
+            (
+                bytes.len() as u32 | 0x80000000,
+                u32::from_le_bytes(resident),
+            )
+        } else if bytes.len() > BIG_DATA_SEGMENT_SIZE && self.minor_version >= 4 {
+            let segments = bytes
+                .chunks(BIG_DATA_SEGMENT_SIZE)
+                .map(|segment| self.alloc(segment))
+                .collect::<Vec<u32>>();
+            let list = self.alloc(
+                &segments
+                    .iter()
+                    .flat_map(|segment| segment.to_le_bytes())
+                    .collect::<Vec<u8>>(),
+            );
+            let mut db = b"db".to_vec();
+            db.extend((segments.len() as u16).to_le_bytes());
+            db.extend(list.to_le_bytes());
+            (bytes.len() as u32, self.alloc(&db))
+        } else {
+            (bytes.len() as u32, self.alloc(bytes))
+        };
+        let name = latin1(name);
+        let mut vk = b"vk".to_vec();
+        vk.extend((name.len() as u16).to_le_bytes());
+        vk.extend(data_size.to_le_bytes());
+        vk.extend(data_offset.to_le_bytes());
+        vk.extend(data_type.to_le_bytes());
+        vk.extend(1u16.to_le_bytes());
+        vk.extend(0u16.to_le_bytes());
+        vk.extend(name);
+        self.alloc(&vk)
+    }
+
+    fn key(&mut self, key: &TestKey, parent: u32, path: &str) -> u32 {
+        let values = key
+            .values
+            .iter()
+            .map(|(name, data_type, bytes)| self.value(name, *data_type, bytes))
+            .collect::<Vec<u32>>();
+        let value_list = match values.is_empty() {
+            true => NONE,
+            false => self.alloc(
+                &values
+                    .iter()
+                    .flat_map(|value| value.to_le_bytes())
+                    .collect::<Vec<u8>>(),
+            ),
+        };
+        let class = key.class.as_deref().map(sz).map(|mut class| {
+            class.truncate(class.len() - 2);
+            class
+        });
+        let class_offset = match class.as_ref() {
+            Some(class) => self.alloc(class),
+            None => NONE,
+        };
+        let name = latin1(&key.name);
+        let max_value_data_size = key
+            .values
+            .iter()
+            .map(|(_, _, bytes)| bytes.len() as u32)
+            .max()
+            .unwrap_or(0);
+        let mut nk = b"nk".to_vec();
+        let flags = KEY_COMP_NAME | if parent == NONE { KEY_HIVE_ENTRY } else { 0 };
+        nk.extend(flags.to_le_bytes());
+        nk.extend(key.time.to_le_bytes());
+        for field in [
+            0,
+            parent,
+            0,
+            0,
+            NONE,
+            NONE,
+            values.len() as u32,
+            value_list,
+            self.security,
+            class_offset,
+            0,
+            0,
+            0,
+            max_value_data_size,
+            0,
+        ] {
+            nk.extend(field.to_le_bytes());
+        }
+        nk.extend((name.len() as u16).to_le_bytes());
+        nk.extend((class.map_or(0, |class| class.len()) as u16).to_le_bytes());
+        nk.extend(name);
+        let offset = self.alloc(&nk);
+        self.offsets.push((path.to_string(), offset));
+
+        let mut subkeys = key.keys.iter().collect::<Vec<&TestKey>>();
+        subkeys.sort_by_key(|subkey| subkey.name.to_uppercase());
+        let subkey_offsets = subkeys
+            .iter()
+            .map(|subkey| {
+                let subkey_path = match path.is_empty() {
+                    true => subkey.name.to_owned(),
+                    false => format!("{}\\{}", path, subkey.name),
+                };
+                self.key(subkey, offset, &subkey_path)
+            })
+            .collect::<Vec<u32>>();
+        if !subkey_offsets.is_empty() {
+            let mut lh = b"lh".to_vec();
+            lh.extend((subkey_offsets.len() as u16).to_le_bytes());
+            for subkey_offset in subkey_offsets.iter() {
+                lh.extend(subkey_offset.to_le_bytes());
+                lh.extend(0u32.to_le_bytes());
+            }
+            let list = self.alloc(&lh);
+            let cell = offset as usize + 4;
+            self.data[cell + 0x14..cell + 0x18]
+                .copy_from_slice(&(subkey_offsets.len() as u32).to_le_bytes());
+            self.data[cell + 0x1C..cell + 0x20].copy_from_slice(&list.to_le_bytes());
+        }
+        offset
+    }
+}
+
+/// A hive as it would be written to disk, with the offset of every key by its path below the
+/// root (the root itself is "").
+pub struct TestHive {
+    pub bytes: Vec<u8>,
+    pub offsets: Vec<(String, u32)>,
+}
+
+impl TestHive {
+    pub fn offset(&self, path: &str) -> u32 {
+        self.offsets
+            .iter()
+            .find(|(key_path, _)| key_path == path)
+            .map(|(_, offset)| *offset)
+            .expect("no key at that path")
+    }
+
+    /// Frees the cell at `offset` as if it had been deleted, leaving its contents in place.
+    pub fn free_cell(&mut self, offset: u32) {
+        let start = BASE_BLOCK_SIZE + offset as usize;
+        let size = i32::from_le_bytes(self.bytes[start..start + 4].try_into().unwrap());
+        self.bytes[start..start + 4].copy_from_slice(&size.unsigned_abs().to_le_bytes());
+    }
+
+    fn field(&self, cell: u32, at: usize) -> u32 {
+        read_u32(&self.bytes, BASE_BLOCK_SIZE + 4 + cell as usize + at).unwrap()
+    }
+
+    fn set_field(&mut self, cell: u32, at: usize, bytes: &[u8]) {
+        let start = BASE_BLOCK_SIZE + 4 + cell as usize + at;
+        self.bytes[start..start + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Drops the key at `path` from its parent's subkey list and frees it along with its values,
+    /// like deleting it would. Subkeys have to be deleted first.
+    pub fn delete_key(&mut self, path: &str) {
+        let offset = self.offset(path);
+        let parent_path = path.rsplit_once('\\').map_or("", |(parent, _)| parent);
+        let parent = self.offset(parent_path);
+        let list = self.field(parent, 0x1C);
+        let entries = (0..self.field(parent, 0x14) as usize)
+            .map(|index| self.field(list, 4 + index * 8))
+            .filter(|entry| *entry != offset)
+            .collect::<Vec<u32>>();
+        for (index, entry) in entries.iter().enumerate() {
+            self.set_field(list, 4 + index * 8, &entry.to_le_bytes());
+        }
+        self.set_field(list, 2, &(entries.len() as u16).to_le_bytes());
+        self.set_field(parent, 0x14, &(entries.len() as u32).to_le_bytes());
+
+        let value_list = self.field(offset, 0x28);
+        if value_list != NONE {
+            for index in 0..self.field(offset, 0x24) as usize {
+                let value = self.field(value_list, index * 4);
+                let data_size = self.field(value, 0x04);
+                if data_size & 0x80000000 == 0 && data_size > 0 {
+                    self.free_cell(self.field(value, 0x08));
+                }
+                self.free_cell(value);
+            }
+            self.free_cell(value_list);
+        }
+        self.free_cell(offset);
+    }
+
+    /// Sets the sequence numbers and recomputes the checksum, they differ in a dirty hive.
+    pub fn set_sequence(&mut self, primary: u32, secondary: u32) {
+        self.bytes[0x04..0x08].copy_from_slice(&primary.to_le_bytes());
+        self.bytes[0x08..0x0C].copy_from_slice(&secondary.to_le_bytes());
+        self.update_checksum();
+    }
+
+    pub fn update_checksum(&mut self) {
+        let checksum = base_block_checksum(&self.bytes);
+        self.bytes[0x1FC..0x200].copy_from_slice(&checksum.to_le_bytes());
+    }
+}
+
+pub fn build(root: &TestKey) -> TestHive {
+    build_with_version(root, 5)
+}
+
+pub fn build_with_version(root: &TestKey, minor_version: u32) -> TestHive {
+    let mut writer = Writer {
+        data: vec![0; 0x20],
+        minor_version,
+        security: 0,
+        offsets: Vec::new(),
+    };
+    let descriptor = security_descriptor();
+    let mut sk = b"sk".to_vec();
+    sk.extend([0; 2]);
+    sk.extend(0x20u32.to_le_bytes()); //the only sk cell links to itself
+    sk.extend(0x20u32.to_le_bytes());
+    sk.extend(1u32.to_le_bytes());
+    sk.extend((descriptor.len() as u32).to_le_bytes());
+    sk.extend(descriptor);
+    writer.security = writer.alloc(&sk);
+    let root_cell = writer.key(root, NONE, "");
+    let hive_bins_size = (writer.data.len() + 0xFFF) & !0xFFF;
+    let padding = hive_bins_size - writer.data.len();
+    if padding >= 8 {
+        writer.cell(&vec![0; padding - 4], false);
+    } else {
+        writer.data.resize(hive_bins_size, 0);
+    }
+    writer.data[0..4].copy_from_slice(b"hbin");
+    writer.data[8..12].copy_from_slice(&(hive_bins_size as u32).to_le_bytes());
+
+    let mut bytes = vec![0; BASE_BLOCK_SIZE];
+    bytes[0..4].copy_from_slice(b"regf");
+    for (offset, field) in [
+        (0x04, 1),
+        (0x08, 1),
+        (0x14, 1),
+        (0x18, minor_version),
+        (0x20, 1),
+        (0x24, root_cell),
+        (0x28, hive_bins_size as u32),
+        (0x2C, 1),
+    ] {
+        bytes[offset..offset + 4].copy_from_slice(&field.to_le_bytes());
+    }
+    let file_name = "test"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect::<Vec<u8>>();
+    bytes[0x30..0x30 + file_name.len()].copy_from_slice(&file_name);
+    bytes.extend(writer.data);
+    let mut hive = TestHive {
+        bytes,
+        offsets: writer.offsets,
+    };
+    hive.update_checksum();
+    hive
+}
+
+/// An empty directory for a test to write files to.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!(
+        "windows_registry_search-{}-{}",
+        process::id(),
+        name
+    ));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
@@ -0,0 +1,345 @@
+use std::fmt;
+
+use crate::registry_source::{RegType, RegistryValue};
+
+/// 1970-01-01 as a FILETIME, 100ns intervals since 1601-01-01 UTC.
+pub const UNIX_EPOCH_FILETIME: u64 = 116_444_736_000_000_000;
+const TICKS_PER_SECOND: u64 = 10_000_000;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Timestamps found without a telling value name must fall in these years to be believed,
+/// so counters and ids aren't taken for dates.
+const PLAUSIBLE_YEARS: std::ops::RangeInclusive<i64> = 1980..=2100;
+
+/// Prefix that turns a term into a date range timestamps in value data are tested against.
+pub const DATE_PREFIX: &str = "date:";
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// A FILETIME from a UTC date and time, `None` before 1601.
+fn to_filetime(year: i64, month: u32, day: u32, seconds_of_day: i64) -> Option<u64> {
+    let unix_seconds = days_from_civil(year, month, day) * SECONDS_PER_DAY + seconds_of_day;
+    let ticks = (unix_seconds as i128) * TICKS_PER_SECOND as i128 + UNIX_EPOCH_FILETIME as i128;
+    u64::try_from(ticks).ok()
+}
+
+fn unix_seconds(filetime: u64) -> i64 {
+    (filetime / TICKS_PER_SECOND) as i64 - (UNIX_EPOCH_FILETIME / TICKS_PER_SECOND) as i64
+}
+
+fn year_of(filetime: u64) -> i64 {
+    civil_from_days(unix_seconds(filetime).div_euclid(SECONDS_PER_DAY)).0
+}
+
+/// A FILETIME as `2024-01-31 12:34:56 UTC`.
+pub fn format_filetime(filetime: u64) -> String {
+    let unix_seconds = unix_seconds(filetime);
+    let (year, month, day) = civil_from_days(unix_seconds.div_euclid(SECONDS_PER_DAY));
+    let seconds_of_day = unix_seconds.rem_euclid(SECONDS_PER_DAY);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Parses `2024-01-31`, `2024-01-31 12:34`, or `2024-01-31T12:34:56`, all UTC. The second part
+/// of the result is how many seconds the text leaves unspecified, a whole day for a date alone.
+fn parse_datetime(text: &str) -> Result<(u64, i64), String> {
+    let invalid = || {
+        format!(
+            "\"{}\" is not a date, expected YYYY-MM-DD[ HH:MM[:SS]]",
+            text
+        )
+    };
+    let text = text.trim();
+    let (date, time) = match text.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time.trim())),
+        None => (text, None),
+    };
+    let date = date
+        .split('-')
+        .map(|part| part.parse::<i64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<i64>, String>>()?;
+    let (year, month, day) = match date[..] {
+        [year, month, day] if (1..=12).contains(&month) => (year, month as u32, day as u32),
+        _ => return Err(invalid()),
+    };
+    if day < 1 || day > days_in_month(year, month) {
+        return Err(invalid());
+    }
+    let (seconds_of_day, precision) = match time {
+        None => (0, SECONDS_PER_DAY),
+        Some(time) => {
+            let time = time
+                .split(':')
+                .map(|part| part.parse::<i64>().map_err(|_| invalid()))
+                .collect::<Result<Vec<i64>, String>>()?;
+            match time[..] {
+                [hour, minute] if hour < 24 && minute < 60 => (hour * 3600 + minute * 60, 60),
+                [hour, minute, second] if hour < 24 && minute < 60 && second < 60 => {
+                    (hour * 3600 + minute * 60 + second, 1)
+                }
+                _ => return Err(invalid()),
+            }
+        }
+    };
+    let filetime = to_filetime(year, month, day, seconds_of_day).ok_or_else(invalid)?;
+    Ok((filetime, precision))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampKind {
+    Filetime,
+    Systemtime,
+    /// Seconds since 1970, eg. `InstallDate`.
+    UnixTime,
+}
+
+/// A timestamp found in value data, held as a FILETIME whatever it was stored as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub kind: TimestampKind,
+    pub filetime: u64,
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            TimestampKind::Filetime => "FILETIME",
+            TimestampKind::Systemtime => "SYSTEMTIME",
+            TimestampKind::UnixTime => "Unix time",
+        };
+        write!(f, "{} {}", kind, format_filetime(self.filetime))
+    }
+}
+
+/// Value names that say the value holds a time, eg. `LastWriteTime`, `InstallDate`.
+fn is_time_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["time", "date", "lastwrite"]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+fn systemtime(bytes: &[u8]) -> Option<u64> {
+    let fields = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]) as u32)
+        .collect::<Vec<u32>>();
+    let [year, month, day_of_week, day, hour, minute, second, milliseconds] = fields[..] else {
+        return None;
+    };
+    let valid = (1601..=30827).contains(&year)
+        && (1..=12).contains(&month)
+        && day_of_week < 7
+        && day >= 1
+        && day <= days_in_month(year as i64, month)
+        && hour < 24
+        && minute < 60
+        && second < 60
+        && milliseconds < 1000;
+    if !valid {
+        return None;
+    }
+    let seconds_of_day = (hour * 3600 + minute * 60 + second) as i64;
+    to_filetime(year as i64, month, day, seconds_of_day)
+        .map(|filetime| filetime + milliseconds as u64 * (TICKS_PER_SECOND / 1000))
+}
+
+/// Looks for a FILETIME in 8 byte REG_QWORD, REG_BINARY and REG_NONE data, a SYSTEMTIME in
+/// 16 byte REG_BINARY and REG_NONE data, and seconds since 1970 in a REG_DWORD. Only values
+/// named like a time are taken as Unix times, and other timestamps must fall between 1980 and
+/// 2100 unless the name says what they are.
+pub fn detect(name: &str, value: &RegistryValue) -> Option<Timestamp> {
+    let named = is_time_name(name);
+    let bytes = value.bytes.as_slice();
+    let is_binary = matches!(value.vtype, RegType::REG_BINARY | RegType::REG_NONE);
+    let timestamp = match bytes.len() {
+        8 if is_binary || value.vtype == RegType::REG_QWORD => Timestamp {
+            kind: TimestampKind::Filetime,
+            filetime: u64::from_le_bytes(bytes.try_into().unwrap()),
+        },
+        16 if is_binary => Timestamp {
+            kind: TimestampKind::Systemtime,
+            filetime: systemtime(bytes)?,
+        },
+        4 if named && value.vtype == RegType::REG_DWORD => {
+            let seconds = u32::from_le_bytes(bytes.try_into().unwrap()) as u64;
+            Timestamp {
+                kind: TimestampKind::UnixTime,
+                filetime: UNIX_EPOCH_FILETIME + seconds * TICKS_PER_SECOND,
+            }
+        }
+        _ => return None,
+    };
+    if timestamp.filetime == 0 {
+        return None;
+    }
+    let year = year_of(timestamp.filetime);
+    match named {
+        true => (year <= 9999).then_some(timestamp),
+        false => PLAUSIBLE_YEARS.contains(&year).then_some(timestamp),
+    }
+}
+
+/// `date:2024-01-01..2024-01-31`, timestamps in value data between the two, inclusive. Either
+/// end can be left out and an end given as a date alone takes in that whole day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateRange {
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+}
+
+impl DateRange {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (start, end) = text
+            .split_once("..")
+            .ok_or_else(|| format!("\"{}\" is not a date range, expected START..END", text))?;
+        let start = match start.trim() {
+            "" => None,
+            start => Some(parse_datetime(start)?.0),
+        };
+        let end = match end.trim() {
+            "" => None,
+            end => {
+                let (filetime, precision) = parse_datetime(end)?;
+                Some(filetime + precision as u64 * TICKS_PER_SECOND - 1)
+            }
+        };
+        if let (Some(start), Some(end)) = (start, end) {
+            if start > end {
+                return Err(format!("\"{}\" ends before it starts", text));
+            }
+        }
+        Ok(Self { start, end })
+    }
+
+    pub fn contains(&self, filetime: u64) -> bool {
+        self.start.is_none_or(|start| filetime >= start)
+            && self.end.is_none_or(|end| filetime <= end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(vtype: RegType, bytes: Vec<u8>) -> RegistryValue {
+        RegistryValue { vtype, bytes }
+    }
+
+    fn systemtime_bytes(fields: [u16; 8]) -> Vec<u8> {
+        fields
+            .iter()
+            .flat_map(|field| field.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn converts_between_filetimes_and_dates() {
+        assert_eq!(
+            format_filetime(UNIX_EPOCH_FILETIME),
+            "1970-01-01 00:00:00 UTC"
+        );
+        assert_eq!(format_filetime(0), "1601-01-01 00:00:00 UTC");
+        assert_eq!(
+            format_filetime(133_511_709_670_000_000),
+            "2024-01-31 10:36:07 UTC"
+        );
+        assert_eq!(
+            parse_datetime("2024-02-29T10:36:07").unwrap(),
+            (133_536_765_670_000_000, 1)
+        );
+        assert!(parse_datetime("2023-02-29").is_err());
+        assert!(parse_datetime("2024-01-31 25:00").is_err());
+    }
+
+    #[test]
+    fn detects_timestamps_by_type_size_and_name() {
+        let filetime = 133_511_709_670_000_000u64.to_le_bytes().to_vec();
+        let found = detect("Anything", &value(RegType::REG_QWORD, filetime.to_owned())).unwrap();
+        assert_eq!(found.to_string(), "FILETIME 2024-01-31 10:36:07 UTC");
+        assert!(detect(
+            "Counter",
+            &value(RegType::REG_QWORD, 5u64.to_le_bytes().to_vec())
+        )
+        .is_none());
+        assert!(detect(
+            "LastWriteTime",
+            &value(RegType::REG_BINARY, 5u64.to_le_bytes().to_vec())
+        )
+        .is_some());
+
+        let systemtime = systemtime_bytes([2023, 7, 1, 3, 14, 15, 9, 265]);
+        let found = detect("Data", &value(RegType::REG_BINARY, systemtime)).unwrap();
+        assert_eq!(found.to_string(), "SYSTEMTIME 2023-07-03 14:15:09 UTC");
+        let bad_month = systemtime_bytes([2023, 13, 1, 3, 14, 15, 9, 265]);
+        assert!(detect("Time", &value(RegType::REG_BINARY, bad_month)).is_none());
+
+        let install_date = 1_700_000_000u32.to_le_bytes().to_vec();
+        assert_eq!(
+            detect(
+                "InstallDate",
+                &value(RegType::REG_DWORD, install_date.to_owned())
+            )
+            .unwrap()
+            .to_string(),
+            "Unix time 2023-11-14 22:13:20 UTC"
+        );
+        assert!(detect("Start", &value(RegType::REG_DWORD, install_date)).is_none());
+    }
+
+    #[test]
+    fn date_ranges_take_in_whole_days() {
+        let range = DateRange::parse("2024-01-01..2024-01-31").unwrap();
+        assert!(range.contains(133_511_709_670_000_000));
+        assert!(!range.contains(parse_datetime("2024-02-01").unwrap().0));
+        assert!(range.contains(parse_datetime("2024-01-31 23:59:59").unwrap().0));
+        let open = DateRange::parse("2024-01-31 10:37..").unwrap();
+        assert!(!open.contains(133_511_709_670_000_000));
+        assert!(DateRange::parse("2024-02-01..2024-01-01").is_err());
+        assert!(DateRange::parse("2024-02-01").is_err());
+    }
+}
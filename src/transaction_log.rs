@@ -0,0 +1,421 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::hive::{base_block_checksum, read_u32, read_u64, BaseBlock, BASE_BLOCK_SIZE};
+
+const LOG_BASE_BLOCK_SIZE: usize = 0x200;
+const LOG_ENTRY_HEADER_SIZE: usize = 40;
+const MARVIN32_SEED: u64 = 0x82EF4D887A4E55C5;
+
+fn marvin32_block(low: &mut u32, high: &mut u32) {
+    *high ^= *low;
+    *low = low.rotate_left(20).wrapping_add(*high);
+    *high = high.rotate_left(9) ^ *low;
+    *low = low.rotate_left(27).wrapping_add(*high);
+    *high = high.rotate_left(19);
+}
+
+/// Marvin32 as used for HvLE entry hashes.
+pub fn marvin32(data: &[u8], seed: u64) -> u64 {
+    let mut low = seed as u32;
+    let mut high = (seed >> 32) as u32;
+    let mut words = data.chunks_exact(4);
+    for word in words.by_ref() {
+        low = low.wrapping_add(u32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+        marvin32_block(&mut low, &mut high);
+    }
+    let tail = words.remainder();
+    let last = match tail.len() {
+        0 => 0x80,
+        1 => 0x8000 | tail[0] as u32,
+        2 => 0x800000 | (tail[1] as u32) << 8 | tail[0] as u32,
+        _ => 0x80000000 | (tail[2] as u32) << 16 | (tail[1] as u32) << 8 | tail[0] as u32,
+    };
+    low = low.wrapping_add(last);
+    marvin32_block(&mut low, &mut high);
+    marvin32_block(&mut low, &mut high);
+    (high as u64) << 32 | low as u64
+}
+
+pub struct LogEntry {
+    pub sequence: u32,
+    pub hive_bins_size: u32,
+    pub dirty_pages: Vec<(u32, Vec<u8>)>, //offset relative to the first hbin, page data
+}
+
+pub struct TransactionLog {
+    pub path: PathBuf,
+    pub base_block: BaseBlock,
+    pub entries: Vec<LogEntry>,
+}
+
+impl TransactionLog {
+    /// Reads HvLE entries until the first one that is truncated or fails its hashes.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        if data.len() < LOG_BASE_BLOCK_SIZE {
+            return Err(format!("{}: Log is too small", path.display()));
+        }
+        let mut base_block_data = data[..LOG_BASE_BLOCK_SIZE].to_vec();
+        base_block_data.resize(BASE_BLOCK_SIZE, 0);
+        let base_block = BaseBlock::parse(&base_block_data)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        if base_block_checksum(&base_block_data) != base_block.checksum {
+            return Err(format!(
+                "{}: Log base block checksum mismatch",
+                path.display()
+            ));
+        }
+
+        let mut entries = Vec::new();
+        let mut offset = LOG_BASE_BLOCK_SIZE;
+        while data.get(offset..offset + 4) == Some(b"HvLE") {
+            let size = read_u32(&data, offset + 4).unwrap_or(0) as usize;
+            let entry = match data.get(offset..offset + size) {
+                Some(entry) if size >= LOG_ENTRY_HEADER_SIZE && size & 0x1FF == 0 => entry,
+                _ => break,
+            };
+            let hash_1 = read_u64(entry, 24).unwrap_or(0);
+            let hash_2 = read_u64(entry, 32).unwrap_or(0);
+            if marvin32(&entry[LOG_ENTRY_HEADER_SIZE..], MARVIN32_SEED) != hash_1
+                || marvin32(&entry[..32], MARVIN32_SEED) != hash_2
+            {
+                break;
+            }
+            let page_count = read_u32(entry, 20).unwrap_or(0) as usize;
+            let mut page_data = LOG_ENTRY_HEADER_SIZE + page_count * 8;
+            //every page needs an 8 byte reference, so a corrupt count can't reserve more than that
+            let mut dirty_pages = Vec::with_capacity(page_count.min(entry.len() / 8));
+            for index in 0..page_count {
+                let reference = LOG_ENTRY_HEADER_SIZE + index * 8;
+                let page_offset = read_u32(entry, reference).unwrap_or(0);
+                let page_size = read_u32(entry, reference + 4).unwrap_or(0) as usize;
+                match entry.get(page_data..page_data + page_size) {
+                    Some(page) => dirty_pages.push((page_offset, page.to_vec())),
+                    None => break,
+                }
+                page_data += page_size;
+            }
+            if dirty_pages.len() != page_count {
+                break;
+            }
+            entries.push(LogEntry {
+                sequence: read_u32(entry, 12).unwrap_or(0),
+                hive_bins_size: read_u32(entry, 16).unwrap_or(0),
+                dirty_pages,
+            });
+            offset += size;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            base_block,
+            entries,
+        })
+    }
+}
+
+/// Finds `<hive>.LOG1` and `<hive>.LOG2` next to the hive, ignoring case.
+pub fn find_logs(hive_path: &Path) -> Vec<PathBuf> {
+    let file_name = match hive_path.file_name() {
+        Some(file_name) => file_name.to_string_lossy().to_lowercase(),
+        None => return Vec::new(),
+    };
+    let directory = match hive_path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+    let mut logs = match fs::read_dir(directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().to_lowercase())
+                    .map(|name| {
+                        name == format!("{}.log1", file_name)
+                            || name == format!("{}.log2", file_name)
+                    })
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<PathBuf>>(),
+        Err(_) => Vec::new(),
+    };
+    logs.sort();
+    logs
+}
+
+pub struct LogReplay {
+    pub logs: Vec<PathBuf>,
+    pub entries_applied: usize,
+    pub dirty_ranges: Vec<(u32, u32)>, //start, end relative to the first hbin
+    pub warnings: Vec<String>,
+}
+
+/// Applies log entries with a sequence number from the hive's secondary sequence number onwards,
+/// oldest first, stopping at the first gap, including one before the first entry. The base block is rewritten as clean afterwards.
+pub fn replay(data: &mut Vec<u8>, base_block: &BaseBlock, logs: Vec<TransactionLog>) -> LogReplay {
+    let mut replay = LogReplay {
+        logs: logs.iter().map(|log| log.path.to_owned()).collect(),
+        entries_applied: 0,
+        dirty_ranges: Vec::new(),
+        warnings: Vec::new(),
+    };
+    let mut entries = logs
+        .into_iter()
+        .flat_map(|log| log.entries)
+        .filter(|entry| entry.sequence >= base_block.secondary_sequence)
+        .collect::<Vec<LogEntry>>();
+    entries.sort_by_key(|entry| entry.sequence);
+    entries.dedup_by_key(|entry| entry.sequence);
+
+    let mut last_sequence = None;
+    //None once the sequence number can't go any higher
+    let mut next_sequence = Some(base_block.secondary_sequence);
+    let mut hive_bins_size = base_block.hive_bins_size;
+    for entry in entries {
+        if next_sequence != Some(entry.sequence) {
+            replay.warnings.push(match last_sequence {
+                Some(last_sequence) => format!(
+                    "Log entries stop at sequence {}, next available is {}",
+                    last_sequence, entry.sequence
+                ),
+                None => format!(
+                    "Log entries start at sequence {}, the hive needs {}",
+                    entry.sequence, base_block.secondary_sequence
+                ),
+            });
+            break;
+        }
+        hive_bins_size = entry.hive_bins_size;
+        //grown only as far as the entry's pages reach, a corrupt size can't force a huge allocation
+        let pages_end = entry
+            .dirty_pages
+            .iter()
+            .map(|(offset, page)| BASE_BLOCK_SIZE + *offset as usize + page.len())
+            .max()
+            .unwrap_or(0);
+        let hive_size = (BASE_BLOCK_SIZE + hive_bins_size as usize).min(pages_end);
+        if data.len() < hive_size {
+            data.resize(hive_size, 0);
+        }
+        for (offset, page) in entry.dirty_pages {
+            let start = BASE_BLOCK_SIZE + offset as usize;
+            if start + page.len() > data.len() {
+                replay.warnings.push(format!(
+                    "Log entry {} writes past the end of the hive at 0x{:X}",
+                    entry.sequence, offset
+                ));
+                continue;
+            }
+            data[start..start + page.len()].copy_from_slice(&page);
+            replay
+                .dirty_ranges
+                .push((offset, offset + page.len() as u32));
+        }
+        last_sequence = Some(entry.sequence);
+        next_sequence = entry.sequence.checked_add(1);
+        replay.entries_applied += 1;
+    }
+
+    if let Some(last_sequence) = last_sequence {
+        let sequence = last_sequence.wrapping_add(1).to_le_bytes();
+        data[0x04..0x08].copy_from_slice(&sequence);
+        data[0x08..0x0C].copy_from_slice(&sequence);
+        data[0x28..0x2C].copy_from_slice(&hive_bins_size.to_le_bytes());
+        let checksum = base_block_checksum(data);
+        data[0x1FC..0x200].copy_from_slice(&checksum.to_le_bytes());
+    }
+    replay
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hive::Hive,
+        test_hive::{build, temp_dir, TestHive, TestKey},
+    };
+
+    /// A log holding one entry per `(sequence, hive)`, each rewriting all of that hive's bins.
+    fn log(base_block: &[u8], entries: &[(u32, &TestHive)]) -> Vec<u8> {
+        let mut log = base_block[..LOG_BASE_BLOCK_SIZE].to_vec();
+        for (sequence, hive) in entries {
+            let bins = &hive.bytes[BASE_BLOCK_SIZE..];
+            let mut entry = b"HvLE".to_vec();
+            let size = (LOG_ENTRY_HEADER_SIZE + 8 + bins.len() + 0x1FF) & !0x1FF;
+            for field in [size as u32, 0, *sequence, bins.len() as u32, 1] {
+                entry.extend(field.to_le_bytes());
+            }
+            entry.extend([0; 16]);
+            entry.extend(0u32.to_le_bytes());
+            entry.extend((bins.len() as u32).to_le_bytes());
+            entry.extend(bins);
+            entry.resize(size, 0);
+            let hash_1 = marvin32(&entry[LOG_ENTRY_HEADER_SIZE..], MARVIN32_SEED);
+            entry[24..32].copy_from_slice(&hash_1.to_le_bytes());
+            let hash_2 = marvin32(&entry[..32], MARVIN32_SEED);
+            entry[32..40].copy_from_slice(&hash_2.to_le_bytes());
+            log.extend(entry);
+        }
+        log
+    }
+
+    /// A dirty hive with only `A`, and the same hive with `B` added, as the logs would have it.
+    fn hives() -> (TestHive, TestHive) {
+        let mut old = build(&TestKey::new("ROOT").key(TestKey::new("A")));
+        old.set_sequence(2, 1);
+        let new = build(
+            &TestKey::new("ROOT")
+                .key(TestKey::new("A"))
+                .key(TestKey::new("B").key(TestKey::new("C"))),
+        );
+        (old, new)
+    }
+
+    #[test]
+    fn marvin32_matches_reference_vectors() {
+        let seed = 0x004FB61A001BDBCC;
+        assert_eq!(marvin32(b"", seed), 0x30ED35C100CD3C7D);
+        assert_eq!(marvin32(&[0xAF], seed), 0x48E73FC77D75DDC1);
+        assert_eq!(marvin32(&[0xE7, 0x0F], seed), 0xB5F6E1FC485DBFF8);
+        assert_eq!(marvin32(&[0x37, 0xF4, 0x95], seed), 0xF0B07C789B8CF7E8);
+        assert_eq!(
+            marvin32(&[0x86, 0x42, 0xDC, 0x59], seed),
+            0x7008F2E87E9CF556
+        );
+    }
+
+    #[test]
+    fn replays_a_dirty_hive_from_its_logs() {
+        let directory = temp_dir("replay");
+        let (old, new) = hives();
+        fs::write(directory.join("NTUSER.DAT"), &old.bytes).unwrap();
+        fs::write(
+            directory.join("ntuser.dat.LOG1"),
+            log(&old.bytes, &[(1, &new)]),
+        )
+        .unwrap();
+        fs::write(directory.join("NTUSER.DAT.LOG2"), log(&old.bytes, &[])).unwrap();
+
+        let hive = Hive::open(&directory.join("NTUSER.DAT")).unwrap();
+        let log_replay = hive.log_replay.as_ref().unwrap();
+        assert_eq!(log_replay.logs.len(), 2);
+        assert_eq!(log_replay.entries_applied, 1);
+        assert!(log_replay.warnings.is_empty());
+        assert!(!hive.base_block.is_dirty());
+        assert!(hive.open_path("B\\C").is_ok());
+        assert_eq!(hive.log_only_key_paths(), vec!["B", "B\\C"]);
+        assert!(!hive.is_log_only(&hive.open_path("A").unwrap()));
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn skips_entries_that_fail_their_hashes() {
+        let directory = temp_dir("hashes");
+        let (old, new) = hives();
+        let mut log = log(&old.bytes, &[(1, &new)]);
+        let last = log.len() - 1;
+        log[last] ^= 1;
+        fs::write(directory.join("SYSTEM.LOG1"), log).unwrap();
+        let transaction_log = TransactionLog::open(&directory.join("SYSTEM.LOG1")).unwrap();
+        assert!(transaction_log.entries.is_empty());
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn stops_at_a_sequence_gap() {
+        let directory = temp_dir("gap");
+        let (old, new) = hives();
+        fs::write(
+            directory.join("SYSTEM.LOG1"),
+            log(&old.bytes, &[(0, &new), (1, &new), (3, &new)]),
+        )
+        .unwrap();
+        let transaction_log = TransactionLog::open(&directory.join("SYSTEM.LOG1")).unwrap();
+        assert_eq!(transaction_log.entries.len(), 3);
+        let mut data = old.bytes.to_owned();
+        let base_block = BaseBlock::parse(&data).unwrap();
+        let log_replay = replay(&mut data, &base_block, vec![transaction_log]);
+        //entry 0 is older than the hive's secondary sequence number
+        assert_eq!(log_replay.entries_applied, 1);
+        assert_eq!(
+            log_replay.warnings,
+            vec!["Log entries stop at sequence 1, next available is 3"]
+        );
+        assert_eq!(read_u32(&data, 0x04), Some(2));
+        assert_eq!(read_u32(&data, 0x08), Some(2));
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn stops_when_the_first_entry_is_missing() {
+        let directory = temp_dir("first_gap");
+        let (old, new) = hives();
+        fs::write(
+            directory.join("SYSTEM.LOG1"),
+            log(&old.bytes, &[(2, &new), (3, &new)]),
+        )
+        .unwrap();
+        let transaction_log = TransactionLog::open(&directory.join("SYSTEM.LOG1")).unwrap();
+        let mut data = old.bytes.to_owned();
+        let base_block = BaseBlock::parse(&data).unwrap();
+        let log_replay = replay(&mut data, &base_block, vec![transaction_log]);
+        assert_eq!(log_replay.entries_applied, 0);
+        assert_eq!(
+            log_replay.warnings,
+            vec!["Log entries start at sequence 2, the hive needs 1"]
+        );
+        assert_eq!(data, old.bytes);
+
+        let (mut old, new) = hives();
+        old.set_sequence(u32::MAX, u32::MAX);
+        fs::write(
+            directory.join("SYSTEM.LOG1"),
+            log(&old.bytes, &[(u32::MAX, &new)]),
+        )
+        .unwrap();
+        let transaction_log = TransactionLog::open(&directory.join("SYSTEM.LOG1")).unwrap();
+        let mut data = old.bytes.to_owned();
+        let base_block = BaseBlock::parse(&data).unwrap();
+        let log_replay = replay(&mut data, &base_block, vec![transaction_log]);
+        assert_eq!(log_replay.entries_applied, 1);
+        assert_eq!(read_u32(&data, 0x04), Some(0));
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn grows_the_hive_only_as_far_as_the_dirty_pages() {
+        let (old, new) = hives();
+        let bins = new.bytes[BASE_BLOCK_SIZE..].to_vec();
+        let transaction_log = TransactionLog {
+            path: PathBuf::from("SYSTEM.LOG1"),
+            base_block: BaseBlock::parse(&old.bytes).unwrap(),
+            entries: vec![LogEntry {
+                sequence: 1,
+                hive_bins_size: 0xFFFF_F000,
+                dirty_pages: vec![(0, bins.to_owned())],
+            }],
+        };
+        let mut data = old.bytes.to_owned();
+        let base_block = BaseBlock::parse(&data).unwrap();
+        let log_replay = replay(&mut data, &base_block, vec![transaction_log]);
+        assert_eq!(log_replay.entries_applied, 1);
+        assert_eq!(data.len(), BASE_BLOCK_SIZE + bins.len());
+        assert_eq!(data[BASE_BLOCK_SIZE..], bins[..]);
+    }
+
+    #[test]
+    fn rejects_logs_with_a_bad_base_block() {
+        let directory = temp_dir("base_block");
+        let (old, _) = hives();
+        let mut log = log(&old.bytes, &[]);
+        log[0x40] ^= 1;
+        fs::write(directory.join("SAM.LOG1"), log).unwrap();
+        fs::write(directory.join("SAM.LOG2"), [0; 16]).unwrap();
+        assert!(TransactionLog::open(&directory.join("SAM.LOG1")).is_err());
+        assert!(TransactionLog::open(&directory.join("SAM.LOG2")).is_err());
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,153 @@
+use std::{io, ptr};
+
+use windows_sys::Win32::{
+    Foundation::{ERROR_INSUFFICIENT_BUFFER, ERROR_MORE_DATA, ERROR_SUCCESS},
+    Security::{DACL_SECURITY_INFORMATION, GROUP_SECURITY_INFORMATION, OWNER_SECURITY_INFORMATION},
+    System::Registry::{RegGetKeySecurity, RegQueryInfoKeyW},
+};
+use winreg::{
+    enums::{
+        HKEY_CLASSES_ROOT, HKEY_CURRENT_CONFIG, HKEY_CURRENT_USER,
+        HKEY_CURRENT_USER_LOCAL_SETTINGS, HKEY_DYN_DATA, HKEY_LOCAL_MACHINE, HKEY_PERFORMANCE_DATA,
+        HKEY_PERFORMANCE_NLSTEXT, HKEY_PERFORMANCE_TEXT, HKEY_USERS, KEY_READ,
+    },
+    RegKey,
+};
+
+use strum::IntoEnumIterator;
+
+use crate::{
+    registry_source::{KeyInfo, RegType, RegistrySource, RegistryValue},
+    root::Root,
+};
+
+fn predef(root: &Root) -> isize {
+    match root {
+        Root::HkeyClassesRoot => HKEY_CLASSES_ROOT,
+        Root::HkeyCurrentUser => HKEY_CURRENT_USER,
+        Root::HkeyLocalMachine => HKEY_LOCAL_MACHINE,
+        Root::HkeyUsers => HKEY_USERS,
+        Root::HkeyCurrentConfig => HKEY_CURRENT_CONFIG,
+        Root::HkeyPerformanceData => HKEY_PERFORMANCE_DATA,
+        Root::HkeyPerformanceText => HKEY_PERFORMANCE_TEXT,
+        Root::HkeyPerformanceNLSText => HKEY_PERFORMANCE_NLSTEXT,
+        Root::HkeyDynData => HKEY_DYN_DATA,
+        Root::HkeyCurrentUserLocalSettings => HKEY_CURRENT_USER_LOCAL_SETTINGS,
+    }
+}
+
+fn os_error(status: u32) -> String {
+    io::Error::from_raw_os_error(status as i32).to_string()
+}
+
+/// The key's class name, which `RegKey::query_info` doesn't ask for.
+fn class_name(key: &RegKey) -> Result<Option<String>, String> {
+    let mut class = vec![0u16; 256];
+    loop {
+        let mut length = class.len() as u32;
+        let status = unsafe {
+            RegQueryInfoKeyW(
+                key.raw_handle(),
+                class.as_mut_ptr(),
+                &mut length,
+                ptr::null(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        };
+        match status {
+            ERROR_SUCCESS => {
+                class.truncate(length as usize);
+                return Ok((!class.is_empty()).then(|| String::from_utf16_lossy(&class)));
+            }
+            ERROR_MORE_DATA => class.resize(class.len() * 2, 0),
+            status => return Err(os_error(status)),
+        }
+    }
+}
+
+/// The live registry of the machine this is running on.
+pub struct WinregSource;
+
+impl RegistrySource for WinregSource {
+    type Key = RegKey;
+
+    fn roots(&self) -> Vec<String> {
+        Root::iter().map(|root| root.to_string()).collect()
+    }
+
+    fn open_key(&self, root: &str, path: &str) -> Result<RegKey, String> {
+        let root = match Root::from_name(root) {
+            Some(root) => root,
+            None => return Err(format!("Unknown root \"{}\"", root)),
+        };
+        RegKey::predef(predef(&root))
+            .open_subkey_with_flags(path, KEY_READ)
+            .map_err(|err| err.to_string())
+    }
+
+    fn enum_keys(&self, key: &RegKey) -> Vec<Result<String, String>> {
+        key.enum_keys()
+            .map(|key_result| key_result.map_err(|err| err.to_string()))
+            .collect()
+    }
+
+    fn enum_values(&self, key: &RegKey) -> Vec<Result<(String, RegistryValue), String>> {
+        key.enum_values()
+            .map(|value_result| match value_result {
+                Ok((value_name, reg_value)) => Ok((
+                    value_name,
+                    RegistryValue {
+                        vtype: RegType::from_u32(reg_value.vtype as u32)
+                            .unwrap_or(RegType::REG_NONE),
+                        bytes: reg_value.bytes,
+                    },
+                )),
+                Err(err) => Err(err.to_string()),
+            })
+            .collect()
+    }
+
+    fn key_info(&self, key: &RegKey) -> Result<KeyInfo, String> {
+        let info = key.query_info().map_err(|err| err.to_string())?;
+        Ok(KeyInfo {
+            last_write_time: ((info.last_write_time.dwHighDateTime as u64) << 32)
+                | info.last_write_time.dwLowDateTime as u64,
+            class_name: class_name(key)?,
+            subkey_count: info.sub_keys,
+            value_count: info.values,
+            max_value_len: info.max_value_len,
+        })
+    }
+
+    fn security_descriptor(&self, key: &RegKey) -> Result<Option<Vec<u8>>, String> {
+        let information =
+            OWNER_SECURITY_INFORMATION | GROUP_SECURITY_INFORMATION | DACL_SECURITY_INFORMATION;
+        let mut descriptor = vec![0u8; 256];
+        loop {
+            let mut length = descriptor.len() as u32;
+            let status = unsafe {
+                RegGetKeySecurity(
+                    key.raw_handle(),
+                    information,
+                    descriptor.as_mut_ptr().cast(),
+                    &mut length,
+                )
+            };
+            match status {
+                ERROR_SUCCESS => {
+                    descriptor.truncate(length as usize);
+                    return Ok(Some(descriptor));
+                }
+                ERROR_INSUFFICIENT_BUFFER => descriptor.resize(length as usize, 0),
+                status => return Err(os_error(status)),
+            }
+        }
+    }
+}
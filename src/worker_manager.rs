@@ -1,9 +1,24 @@
 use crate::{
-    alt_reg_value_to_string, root::Root, KEY_COUNT, REGEDIT_OUTPUT_FOR_BLANK_NAMES, VALUE_COUNT,
+    binary_strings,
+    environment::Environment,
+    key_filter::{self, KeyFilter},
+    lint::{self, LintReport},
+    path_glob::PathGlob,
+    query::{Field, Query},
+    reg_data::RegData,
+    registry_source::{RegType, RegistrySource, RegistryValue},
+    search_term::{Pattern, SearchTerm},
+    security_descriptor::SecurityDescriptor,
+    snapshot::{SnapshotKey, SnapshotWriter},
+    suppression::SuppressionList,
+    timestamp::{self, Timestamp},
+    KEY_COUNT, REGEDIT_OUTPUT_FOR_BLANK_NAMES, SUPPRESSED_COUNT, VALUE_COUNT,
 };
 use parking_lot::Mutex;
 use std::{
+    borrow::Cow,
     collections::{BTreeSet, HashSet, VecDeque},
+    ptr, slice,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc,
@@ -11,9 +26,8 @@ use std::{
     time::Duration,
 };
 use tokio::sync::Notify;
-use winreg::{enums::*, RegKey};
 
-pub async fn run_thread(worker_manager: Arc<WorkerManager>) {
+pub async fn run_thread<S: RegistrySource>(worker_manager: Arc<WorkerManager<S>>) {
     loop {
         let key_pair = match worker_manager.get_work().await {
             Some(key_pair) => key_pair,
@@ -23,33 +37,107 @@ pub async fn run_thread(worker_manager: Arc<WorkerManager>) {
     }
 }
 
-pub struct WorkerManager {
+/// Root name, key path and the key itself when the source handed it out with its parent's
+/// subkeys, otherwise it's opened by path.
+pub type QueuedKey<K> = (String, String, Option<K>);
+
+/// A value along with its data decoded the ways terms are matched against.
+struct DecodedValue<'a> {
+    name: &'a str,
+    value: &'a RegistryValue,
+    data: RegData,
+    text: String,
+    timestamp: Option<Timestamp>,
+}
+
+impl<'a> DecodedValue<'a> {
+    fn new(name: &'a str, value: &'a RegistryValue) -> Self {
+        let data = RegData::from_value(value);
+        Self {
+            name,
+            value,
+            text: data.to_string(),
+            data,
+            timestamp: timestamp::detect(name, value),
+        }
+    }
+}
+
+/// A key as terms are matched against it, either itself or as the key a value is in.
+struct KeyRecord<'a, K> {
+    root_name: &'a str,
+    key_path: &'a str,
+    registry_key: Option<&'a K>,
+}
+
+impl<K> KeyRecord<'_, K> {
+    fn full_path(&self) -> String {
+        format!("{}\\{}", self.root_name, self.key_path)
+    }
+}
+
+/// How values are matched beyond comparing terms with their decoded data.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Also match terms against printable runs at least this long pulled out of binary values.
+    pub string_min_length: Option<usize>,
+    /// Also match REG_EXPAND_SZ data with its variables expanded from this profile.
+    pub environment: Option<Environment>,
+    /// Only keys whose metadata meets this, and their values, are results. Without any terms
+    /// every key that meets it is.
+    pub key_filter: KeyFilter,
+    /// Results from these paths, or values matching these terms, are left out and counted.
+    pub suppressions: SuppressionList,
+}
+
+pub struct WorkerManager<S: RegistrySource> {
+    source: S,
     threads: usize,
-    search_terms: Vec<String>,
-    key_queue: Arc<Mutex<VecDeque<(isize, String)>>>,
+    search_terms: Vec<SearchTerm>,
+    /// The terms of `exclude:` exclusions, kept apart so they never find anything themselves.
+    exclusions: Vec<SearchTerm>,
+    key_queue: Arc<Mutex<VecDeque<QueuedKey<S::Key>>>>,
     work_ready_for_processing: Arc<Notify>,
     threads_waiting_for_work: Arc<AtomicUsize>,
     no_work_left: Arc<Notify>,
     pub results: Arc<Mutex<BTreeSet<String>>>,
     pub errors: Arc<Mutex<HashSet<String>>>,
+    /// Results left out by exclusions or the suppression list.
+    pub suppressed: AtomicUsize,
+    snapshot: Option<Arc<SnapshotWriter>>,
+    lint: Option<Arc<LintReport>>,
+    options: SearchOptions,
+    #[allow(dead_code)] //runs aren't cut short when these are set yet
     stop: Arc<AtomicBool>,
+    #[allow(dead_code)]
     stop_notify: Arc<Notify>,
 }
 
-impl WorkerManager {
+impl<S: RegistrySource> WorkerManager<S> {
     pub fn new(
-        search_terms: Vec<String>,
+        source: S,
+        search_terms: Vec<SearchTerm>,
         threads_to_use: usize,
         results: Arc<Mutex<BTreeSet<String>>>,
         stop: Arc<AtomicBool>,
         stop_notify: Arc<Notify>,
     ) -> Self {
+        let mut exclusions = Vec::new();
+        let search_terms = search_terms
+            .into_iter()
+            .filter_map(|term| match term {
+                SearchTerm::Exclude(excluded) => {
+                    exclusions.push(*excluded);
+                    None
+                }
+                term => Some(term),
+            })
+            .collect();
         Self {
+            source,
             threads: threads_to_use,
-            search_terms: search_terms
-                .into_iter()
-                .map(|term| term.to_lowercase())
-                .collect(),
+            search_terms,
+            exclusions,
             key_queue: Arc::new(Mutex::new(VecDeque::new())),
             work_ready_for_processing: Arc::new(Notify::new()),
             threads_waiting_for_work: Arc::new(AtomicUsize::new(0)),
@@ -58,44 +146,241 @@ impl WorkerManager {
 
             results,
             errors: Arc::new(Mutex::new(HashSet::new())),
+            suppressed: AtomicUsize::new(0),
+            snapshot: None,
+            lint: None,
+            options: SearchOptions::default(),
 
             stop,
             stop_notify,
         }
     }
 
-    fn feed_queue_and_process_values(&self, (reg_key, key_path): (isize, String)) {
-        if self.string_matches(&key_path) {
-            let root_name = match Root::from_isize(reg_key) {
-                Some(root) => root.to_string(),
-                None => "InvalidRoot".into(),
-            };
-            self.results
+    /// Every key walked is also written to `snapshot`, values and all, whether it matched or not.
+    pub fn with_snapshot(mut self, snapshot: Arc<SnapshotWriter>) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Every value walked is also checked for data that doesn't fit its type, anomalies are added
+    /// to `lint`.
+    pub fn with_lint(mut self, lint: Arc<LintReport>) -> Self {
+        self.lint = Some(lint);
+        self
+    }
+
+    pub fn with_options(mut self, options: SearchOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Counts a result left out, here and in the run's total.
+    fn suppress(&self) {
+        self.suppressed.fetch_add(1, Ordering::SeqCst);
+        SUPPRESSED_COUNT.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn capture_key(
+        &self,
+        root_name: &str,
+        key_path: &str,
+        registry_key: &S::Key,
+        values: Vec<(String, RegistryValue)>,
+    ) {
+        let snapshot = match self.snapshot.as_ref() {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+        let info = match self.source.key_info(registry_key) {
+            Ok(info) => info,
+            Err(err) => {
+                self.errors
+                    .lock()
+                    .insert(format!("{}, Key info error: \"{}\"", key_path, err));
+                Default::default()
+            }
+        };
+        let key = SnapshotKey {
+            root: root_name.to_string(),
+            path: key_path.to_string(),
+            info,
+            annotation: self.source.annotation(registry_key),
+            values,
+        };
+        if let Err(err) = snapshot.add(&key) {
+            self.errors
                 .lock()
-                .insert(format!("{}\\{}", root_name, &key_path));
+                .insert(format!("{}, Snapshot error: \"{}\"", key_path, err));
         }
-        let registry_key =
-            match RegKey::predef(reg_key).open_subkey_with_flags(key_path.to_owned(), KEY_READ) {
-                Ok(registry_key) => registry_key,
+    }
+
+    fn lint_value(&self, key_path: &str, value_name: &str, value: &RegistryValue) {
+        let lint = match self.lint.as_ref() {
+            Some(lint) => lint,
+            None => return,
+        };
+        let value_name = if value_name.is_empty() && REGEDIT_OUTPUT_FOR_BLANK_NAMES {
+            "(Default)"
+        } else {
+            value_name
+        };
+        let value_path = format!("{}\\{}", key_path, value_name);
+        for anomaly in lint::check(value)
+            .into_iter()
+            .chain(lint::dangling_link(&self.source, value))
+        {
+            lint.add(&value_path, value.vtype, anomaly);
+        }
+    }
+
+    /// Queues the top level keys of each root.
+    pub fn feed_roots(&self, roots: Vec<String>) {
+        let mut work = Vec::new();
+        for root_name in roots {
+            if !self.could_match(&root_name) {
+                continue;
+            }
+            let root_key = match self.source.open_key(&root_name, "") {
+                Ok(root_key) => root_key,
                 Err(err) => {
-                    let root_name = match Root::from_isize(reg_key) {
-                        Some(root) => root.to_string(),
-                        None => "InvalidRoot".into(),
-                    };
-                    self.errors.lock().insert(format!(
-                        "{}: {}, Key error: \"{}\"",
-                        &key_path, root_name, err
-                    ));
-                    return;
+                    self.errors
+                        .lock()
+                        .insert(format!("{}, Key error: \"{}\"", root_name, err));
+                    continue;
                 }
             };
+            if self.snapshot.is_some() || self.lint.is_some() {
+                let values = self
+                    .source
+                    .enum_values(&root_key)
+                    .into_iter()
+                    .filter_map(|value_result| value_result.ok())
+                    .collect::<Vec<(String, RegistryValue)>>();
+                for (value_name, value) in values.iter() {
+                    self.lint_value(&root_name, value_name, value);
+                }
+                self.capture_key(&root_name, "", &root_key, values);
+            }
+            for key_result in self.source.enum_subkeys(&root_key) {
+                KEY_COUNT.fetch_add(1, Ordering::SeqCst);
+                match key_result {
+                    Ok((key_name, subkey)) => {
+                        if self.could_match(&format!("{}\\{}", root_name, key_name)) {
+                            work.push((root_name.to_owned(), key_name, subkey));
+                        }
+                    }
+                    Err(err) => {
+                        self.errors
+                            .lock()
+                            .insert(format!("{}, Subkey error: \"{}\"", root_name, err));
+                    }
+                }
+            }
+        }
+        self.feed_queue(work);
+    }
+
+    fn feed_queue_and_process_values(
+        &self,
+        (root_name, key_path, registry_key): QueuedKey<S::Key>,
+    ) {
+        let registry_key = match registry_key {
+            Some(registry_key) => Ok(registry_key),
+            None => self.source.open_key(&root_name, &key_path),
+        };
+        let annotation = registry_key
+            .as_ref()
+            .ok()
+            .and_then(|registry_key| self.source.annotation(registry_key));
+        let annotation = match (self.source.label(), annotation) {
+            (Some(label), Some(annotation)) => format!(" [{}, {}]", label, annotation),
+            (Some(note), None) | (None, Some(note)) => format!(" [{}]", note),
+            (None, None) => String::new(),
+        };
+        let key_filter = &self.options.key_filter;
+        let key = KeyRecord {
+            root_name: &root_name,
+            key_path: &key_path,
+            registry_key: registry_key.as_ref().ok(),
+        };
+        // Values are only results for the queries that don't already hold for their key, or
+        // every value of a key found by `path:` would be listed with it.
+        let key_queries = Self::queries(&self.search_terms)
+            .filter_map(|query| Some((query, self.query_matches(query, &key, None)?)))
+            .collect::<Vec<(&Query, String)>>();
+        let value_terms = match key_queries.is_empty() {
+            true => Cow::Borrowed(self.search_terms.as_slice()),
+            false => Cow::Owned(
+                self.search_terms
+                    .iter()
+                    .filter(|term| match term {
+                        SearchTerm::Query(query) => !key_queries
+                            .iter()
+                            .any(|(held, _)| ptr::eq(*held, query.as_ref())),
+                        _ => true,
+                    })
+                    .cloned()
+                    .collect::<Vec<SearchTerm>>(),
+            ),
+        };
+        let key_found = if self.search_terms.is_empty() && !key_filter.is_empty() {
+            Some(String::new())
+        } else {
+            self.key_matches(&self.search_terms, &key)
+                .or_else(|| key_queries.into_iter().next().map(|(_, found)| found))
+        };
+        let key_matches = key_found.is_some();
+        let key_vetoed = self.key_vetoed(&key);
+        let key_info = match registry_key.as_ref() {
+            Ok(registry_key) if key_matches || !key_filter.is_empty() => {
+                match self.source.key_info(registry_key) {
+                    Ok(info) => Some(info),
+                    Err(err) => {
+                        self.errors
+                            .lock()
+                            .insert(format!("{}, Key info error: \"{}\"", key_path, err));
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        let in_filter = key_filter.is_empty()
+            || key_info
+                .as_ref()
+                .is_some_and(|info| key_filter.matches(info));
+        if key_vetoed && key_matches && in_filter {
+            self.suppress();
+        } else if let Some(found) = key_found.filter(|_| in_filter) {
+            let metadata = key_info
+                .as_ref()
+                .map(key_filter::describe)
+                .unwrap_or_default();
+            self.results.lock().insert(format!(
+                "{}\\{}{}{}{}",
+                root_name, &key_path, metadata, found, annotation
+            ));
+        }
+        let registry_key = match registry_key {
+            Ok(registry_key) => registry_key,
+            Err(err) => {
+                self.errors.lock().insert(format!(
+                    "{}: {}, Key error: \"{}\"",
+                    &key_path, root_name, err
+                ));
+                return;
+            }
+        };
         {
             let mut key_paths = Vec::new();
-            for key_result in registry_key.enum_keys() {
+            for key_result in self.source.enum_subkeys(&registry_key) {
                 KEY_COUNT.fetch_add(1, Ordering::SeqCst);
                 match key_result {
-                    Ok(key_name) => {
-                        key_paths.push((reg_key, format!("{}\\{}", &key_path, key_name)));
+                    Ok((key_name, subkey)) => {
+                        let subkey_path = format!("{}\\{}", &key_path, key_name);
+                        if self.could_match(&format!("{}\\{}", root_name, subkey_path)) {
+                            key_paths.push((root_name.to_owned(), subkey_path, subkey));
+                        }
                     }
                     Err(err) => {
                         self.errors
@@ -108,13 +393,39 @@ impl WorkerManager {
             self.work_ready_for_processing.notify_waiters();
         }
 
-        for value_result in registry_key.enum_values() {
+        let key = KeyRecord {
+            root_name: &root_name,
+            key_path: &key_path,
+            registry_key: Some(&registry_key),
+        };
+        let mut captured_values = Vec::new();
+        for value_result in self.source.enum_values(&registry_key) {
             VALUE_COUNT.fetch_add(1, Ordering::SeqCst);
             match value_result {
                 Ok((value_name, reg_value)) => {
+                    if self.snapshot.is_some() {
+                        captured_values.push((value_name.to_owned(), reg_value.to_owned()));
+                    }
+                    self.lint_value(
+                        &format!("{}\\{}", root_name, key_path),
+                        &value_name,
+                        &reg_value,
+                    );
                     let vtype = reg_value.vtype.to_owned();
-                    let data = alt_reg_value_to_string(reg_value);
-                    if self.any_string_matches(&value_name, &data) {
+                    if !in_filter {
+                        continue;
+                    }
+                    let decoded = DecodedValue::new(&value_name, &reg_value);
+                    if let Some(found) = self.value_matches(&value_terms, &key, &decoded) {
+                        if key_vetoed || self.value_vetoed(&key, &decoded) {
+                            self.suppress();
+                            continue;
+                        }
+                        let timestamp = match decoded.timestamp {
+                            Some(timestamp) => format!(" [{}]", timestamp),
+                            None => String::new(),
+                        };
+                        let data = decoded.text;
                         let value_name = if value_name.is_empty() {
                             if REGEDIT_OUTPUT_FOR_BLANK_NAMES {
                                 "(Default)".to_string()
@@ -124,13 +435,16 @@ impl WorkerManager {
                         } else {
                             value_name
                         };
-                        let root_name = match Root::from_isize(reg_key) {
-                            Some(root) => root.to_string(),
-                            None => "InvalidRoot".into(),
-                        };
                         self.results.lock().insert(format!(
-                            "{}\\{}\\{} = \"{}\" ({:?})",
-                            root_name, &key_path, value_name, data, vtype,
+                            "{}\\{}\\{} = \"{}\" ({:?}){}{}{}",
+                            root_name,
+                            &key_path,
+                            value_name,
+                            data,
+                            vtype,
+                            timestamp,
+                            found,
+                            annotation,
                         ));
                     }
                 }
@@ -141,9 +455,10 @@ impl WorkerManager {
                 }
             }
         }
+        self.capture_key(&root_name, &key_path, &registry_key, captured_values);
     }
 
-    pub async fn get_work(&self) -> Option<(isize, String)> {
+    pub async fn get_work(&self) -> Option<QueuedKey<S::Key>> {
         loop {
             let work = self.key_queue.lock().pop_front();
             if let Some(key) = work {
@@ -159,34 +474,275 @@ impl WorkerManager {
         }
     }
 
-    pub fn feed_queue(&self, keys: Vec<(isize, String)>) {
+    pub fn feed_queue(&self, keys: Vec<QueuedKey<S::Key>>) {
         let mut lock = self.key_queue.lock();
         lock.extend(keys);
     }
 
-    pub fn any_string_matches(&self, string: &str, string2: &str) -> bool {
+    fn text_terms(terms: &[SearchTerm]) -> impl Iterator<Item = &String> {
+        terms.iter().filter_map(|term| match term {
+            SearchTerm::Text(text) => Some(text),
+            _ => None,
+        })
+    }
+
+    fn patterns(terms: &[SearchTerm]) -> impl Iterator<Item = &Pattern> {
+        terms.iter().filter_map(|term| match term {
+            SearchTerm::Regex(pattern) => Some(pattern),
+            _ => None,
+        })
+    }
+
+    fn globs(terms: &[SearchTerm]) -> impl Iterator<Item = &PathGlob> {
+        terms.iter().filter_map(|term| match term {
+            SearchTerm::Path(glob) => Some(glob),
+            _ => None,
+        })
+    }
+
+    fn queries(terms: &[SearchTerm]) -> impl Iterator<Item = &Query> {
+        terms.iter().filter_map(|term| match term {
+            SearchTerm::Query(query) => Some(query.as_ref()),
+            _ => None,
+        })
+    }
+
+    /// Whether any path glob in `terms` matches a full `ROOT\path`.
+    pub fn path_matches(terms: &[SearchTerm], full_path: &str) -> bool {
+        Self::globs(terms).any(|glob| glob.matches(full_path))
+    }
+
+    /// Whether anything at or below `full_path` could be a result. Subtrees are only pruned when
+    /// every term is a path glob, other terms can match anywhere, and never while capturing or
+    /// linting, which have to walk every key.
+    fn could_match(&self, full_path: &str) -> bool {
+        let prunes = !self.search_terms.is_empty()
+            && self
+                .search_terms
+                .iter()
+                .all(|term| matches!(term, SearchTerm::Path(_)))
+            && self.snapshot.is_none()
+            && self.lint.is_none();
+        !prunes || Self::globs(&self.search_terms).any(|glob| glob.could_match(full_path))
+    }
+
+    /// Whether any text or `re:` term that applies to `field` is found in `string`.
+    pub fn string_matches(terms: &[SearchTerm], field: Field, string: &str) -> bool {
         let string_lowercase = string.to_lowercase();
-        let string2_lowercase = string2.to_lowercase();
-        for term in self.search_terms.iter() {
-            if string_lowercase.contains(term) || string2_lowercase.contains(term) {
+        for term in Self::text_terms(terms) {
+            if string_lowercase.contains(term) {
                 return true;
             }
         }
-        false
+        Self::patterns(terms).any(|pattern| pattern.applies_to(field) && pattern.is_match(string))
     }
 
-    pub fn string_matches(&self, string: &str) -> bool {
-        let string_lowercase = string.to_lowercase();
-        for term in self.search_terms.iter() {
-            if string_lowercase.contains(term) {
-                return true;
+    /// Offset of the first match of any byte pattern term in `bytes`.
+    pub fn bytes_match(terms: &[SearchTerm], bytes: &[u8]) -> Option<usize> {
+        terms.iter().find_map(|term| match term {
+            SearchTerm::Bytes(pattern) => pattern.find(bytes),
+            _ => None,
+        })
+    }
+
+    /// Whether a timestamp was found in the value's data and falls within any date term.
+    pub fn date_matches(terms: &[SearchTerm], timestamp: Option<Timestamp>) -> bool {
+        let timestamp = match timestamp {
+            Some(timestamp) => timestamp,
+            None => return false,
+        };
+        terms.iter().any(|term| match term {
+            SearchTerm::Date(range) => range.contains(timestamp.filetime),
+            _ => false,
+        })
+    }
+
+    /// Whether any numeric term holds for a REG_DWORD, REG_DWORD_BIG_ENDIAN or REG_QWORD.
+    pub fn number_matches(terms: &[SearchTerm], reg_data: &RegData) -> bool {
+        terms.iter().any(|term| match term {
+            SearchTerm::Number(predicate) => predicate.matches_data(reg_data),
+            _ => false,
+        })
+    }
+
+    /// The key's security descriptor when any ACL term holds for it, eg. ` [owner SYSTEM, allow
+    /// Everyone KEY_SET_VALUE]`.
+    fn acl_matches(
+        &self,
+        terms: &[SearchTerm],
+        key_path: &str,
+        registry_key: &S::Key,
+    ) -> Option<String> {
+        let mut predicates = terms
+            .iter()
+            .filter_map(|term| match term {
+                SearchTerm::Acl(predicate) => Some(predicate),
+                _ => None,
+            })
+            .peekable();
+        predicates.peek()?;
+        let descriptor = self
+            .source
+            .security_descriptor(registry_key)
+            .and_then(|descriptor| {
+                descriptor
+                    .map(|data| SecurityDescriptor::parse(&data))
+                    .transpose()
+            });
+        match descriptor {
+            Ok(Some(descriptor)) => predicates
+                .any(|predicate| predicate.matches(&descriptor))
+                .then(|| format!(" [{}]", descriptor)),
+            Ok(None) => None,
+            Err(err) => {
+                self.errors
+                    .lock()
+                    .insert(format!("{}, Security error: \"{}\"", key_path, err));
+                None
+            }
+        }
+    }
+
+    /// Whether a key matches any of `terms` other than queries, with its security descriptor
+    /// when an ACL term is why.
+    fn key_matches(&self, terms: &[SearchTerm], key: &KeyRecord<S::Key>) -> Option<String> {
+        if Self::string_matches(terms, Field::Path, key.key_path)
+            || Self::path_matches(terms, &key.full_path())
+        {
+            return Some(String::new());
+        }
+        key.registry_key
+            .and_then(|registry_key| self.acl_matches(terms, key.key_path, registry_key))
+    }
+
+    /// Whether a value matches any of `terms`, with a note on where in its data when that isn't
+    /// obvious from the result, eg. ` [bytes at 0x1F]`.
+    fn value_matches(
+        &self,
+        terms: &[SearchTerm],
+        key: &KeyRecord<S::Key>,
+        decoded: &DecodedValue,
+    ) -> Option<String> {
+        let (name, data, value) = (decoded.name, decoded.text.as_str(), decoded.value);
+        let display_name = match name.is_empty() {
+            true => "(Default)",
+            false => name,
+        };
+        if Self::string_matches(terms, Field::Name, name)
+            || Self::string_matches(terms, Field::Data, data)
+            || Self::number_matches(terms, &decoded.data)
+            || Self::date_matches(terms, decoded.timestamp)
+            || Self::path_matches(terms, &format!("{}\\{}", key.full_path(), display_name))
+        {
+            return Some(String::new());
+        }
+        if let Some(found) =
+            Self::queries(terms).find_map(|query| self.query_matches(query, key, Some(decoded)))
+        {
+            return Some(found);
+        }
+        if let Some(found) = self.derived_data_matches(terms, decoded) {
+            return Some(found);
+        }
+        Self::bytes_match(terms, &value.bytes).map(|offset| format!(" [bytes at {:#X}]", offset))
+    }
+
+    /// Matches terms against value data with its variables expanded and the strings pulled out
+    /// of binary data, when the options ask for them.
+    fn derived_data_matches(&self, terms: &[SearchTerm], decoded: &DecodedValue) -> Option<String> {
+        let (data, value) = (decoded.text.as_str(), decoded.value);
+        if let Some(environment) = self.options.environment.as_ref() {
+            if value.vtype == RegType::REG_EXPAND_SZ {
+                let expanded = environment.expand(data);
+                if expanded != data && Self::string_matches(terms, Field::Data, &expanded) {
+                    return Some(format!(" [expanded \"{}\"]", expanded));
+                }
+            }
+        }
+        if let Some(min_length) = self.options.string_min_length {
+            if matches!(value.vtype, RegType::REG_BINARY | RegType::REG_NONE) {
+                let found = binary_strings::extract(&value.bytes, min_length)
+                    .into_iter()
+                    .find(|string| Self::string_matches(terms, Field::Data, &string.text));
+                if let Some(string) = found {
+                    return Some(format!(" [{}]", string));
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether an exclusion or the suppression list leaves a key out, along with its values.
+    fn key_vetoed(&self, key: &KeyRecord<S::Key>) -> bool {
+        if self.exclusions.is_empty() && self.options.suppressions.is_empty() {
+            return false;
+        }
+        self.options.suppressions.suppresses_path(&key.full_path())
+            || self.key_matches(&self.exclusions, key).is_some()
+            || Self::queries(&self.exclusions)
+                .any(|query| self.query_matches(query, key, None).is_some())
+    }
+
+    /// Whether an exclusion or a suppressed value term leaves a value out.
+    fn value_vetoed(&self, key: &KeyRecord<S::Key>, decoded: &DecodedValue) -> bool {
+        [
+            self.exclusions.as_slice(),
+            self.options.suppressions.value_terms(),
+        ]
+        .into_iter()
+        .filter(|terms| !terms.is_empty())
+        .any(|terms| self.value_matches(terms, key, decoded).is_some())
+    }
+
+    /// Evaluates a query on a key, or on one of its values when `decoded` is given. Keys have
+    /// no name, data or type, so qualifiers on those never match them.
+    fn query_matches(
+        &self,
+        query: &Query,
+        key: &KeyRecord<S::Key>,
+        decoded: Option<&DecodedValue>,
+    ) -> Option<String> {
+        match query {
+            Query::Term(term) => match decoded {
+                Some(decoded) => self.value_matches(slice::from_ref(term), key, decoded),
+                None => self.key_matches(slice::from_ref(term), key),
+            },
+            Query::Field(field, term) => {
+                let terms = slice::from_ref(term);
+                let text = match (field, decoded) {
+                    (Field::Path, _) => key.full_path(),
+                    (Field::Name, Some(decoded)) => decoded.name.to_string(),
+                    (Field::Data, Some(decoded)) => {
+                        return match Self::string_matches(terms, Field::Data, &decoded.text) {
+                            true => Some(String::new()),
+                            false => self.derived_data_matches(terms, decoded),
+                        };
+                    }
+                    _ => return None,
+                };
+                Self::string_matches(terms, *field, &text).then(String::new)
             }
+            Query::Glob(glob) => glob.matches(&key.full_path()).then(String::new),
+            Query::Type(vtype) => decoded
+                .is_some_and(|decoded| decoded.value.vtype == *vtype)
+                .then(String::new),
+            Query::And(left, right) => {
+                let left = self.query_matches(left, key, decoded)?;
+                let right = self.query_matches(right, key, decoded)?;
+                Some(left + &right)
+            }
+            Query::Or(left, right) => self
+                .query_matches(left, key, decoded)
+                .or_else(|| self.query_matches(right, key, decoded)),
+            Query::Not(query) => self
+                .query_matches(query, key, decoded)
+                .is_none()
+                .then(String::new),
         }
-        false
     }
 }
 
-pub async fn run(worker_manager: Arc<WorkerManager>) {
+pub async fn run<S: RegistrySource + 'static>(worker_manager: Arc<WorkerManager<S>>) {
     for _ in 0..worker_manager.threads {
         let worker_manager = worker_manager.to_owned();
         tokio::spawn(run_thread(worker_manager));
@@ -198,7 +754,7 @@ pub async fn run(worker_manager: Arc<WorkerManager>) {
             .load(Ordering::SeqCst)
             == worker_manager.threads
         {
-            if worker_manager.key_queue.lock().len() == 0 {
+            if worker_manager.key_queue.lock().is_empty() {
                 worker_manager.no_work_left.notify_waiters();
                 break;
             } else {
@@ -208,3 +764,744 @@ pub async fn run(worker_manager: Arc<WorkerManager>) {
         tokio::time::sleep(Duration::from_millis(100)).await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        memory_source::MemorySource,
+        number_predicate::NumberPredicate,
+        registry_source::{KeyInfo, RegType, RegistryValue},
+        search_term::TermOptions,
+        timestamp::DateRange,
+    };
+
+    fn sz(text: &str) -> RegistryValue {
+        RegistryValue {
+            vtype: RegType::REG_SZ,
+            bytes: text
+                .encode_utf16()
+                .chain([0])
+                .flat_map(u16::to_le_bytes)
+                .collect(),
+        }
+    }
+
+    fn dword(number: u32) -> RegistryValue {
+        RegistryValue {
+            vtype: RegType::REG_DWORD,
+            bytes: number.to_le_bytes().to_vec(),
+        }
+    }
+
+    fn source() -> MemorySource {
+        let mut source = MemorySource::default();
+        source.set_value(
+            "HKEY_LOCAL_MACHINE",
+            "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run",
+            "Updater",
+            sz("C:\\Users\\bob\\AppData\\evil.exe"),
+        );
+        source.set_value(
+            "HKEY_LOCAL_MACHINE",
+            "SYSTEM\\CurrentControlSet\\Services\\Tcpip",
+            "Start",
+            dword(2),
+        );
+        source.set_value(
+            "HKEY_CURRENT_USER",
+            "Environment",
+            "TEMP",
+            sz("%USERPROFILE%"),
+        );
+        source
+    }
+
+    /// Runs a search over every root of `source`, returns the results and errors.
+    async fn search(source: MemorySource, terms: &[&str]) -> (Vec<String>, Vec<String>) {
+        search_with(source, terms, SearchOptions::default()).await
+    }
+
+    async fn search_with<T: RegistrySource + 'static>(
+        source: T,
+        terms: &[&str],
+        options: SearchOptions,
+    ) -> (Vec<String>, Vec<String>) {
+        let terms = terms
+            .iter()
+            .map(|term| SearchTerm::parse(term).unwrap())
+            .collect();
+        search_parsed(source, terms, options).await
+    }
+
+    async fn search_parsed<T: RegistrySource + 'static>(
+        source: T,
+        terms: Vec<SearchTerm>,
+        options: SearchOptions,
+    ) -> (Vec<String>, Vec<String>) {
+        let worker_manager = run_parsed(source, terms, options).await;
+        let results = worker_manager.results.lock().iter().cloned().collect();
+        let errors = worker_manager.errors.lock().iter().cloned().collect();
+        (results, errors)
+    }
+
+    async fn run_parsed<T: RegistrySource + 'static>(
+        source: T,
+        terms: Vec<SearchTerm>,
+        options: SearchOptions,
+    ) -> Arc<WorkerManager<T>> {
+        let roots = source.roots();
+        let worker_manager = Arc::new(
+            WorkerManager::new(
+                source,
+                terms,
+                2,
+                Arc::new(Mutex::new(BTreeSet::new())),
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(Notify::new()),
+            )
+            .with_options(options),
+        );
+        worker_manager.feed_roots(roots);
+        run(worker_manager.to_owned()).await;
+        worker_manager
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn matches_key_paths_case_insensitively() {
+        let (results, errors) = search(source(), &["currentversion\\RUN"]).await;
+        assert!(errors.is_empty());
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0],
+            "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run [0 subkeys, 1 values, max data 60 bytes]"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn matches_value_names_and_data() {
+        let (results, _) = search(source(), &["updater"]).await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].starts_with(
+            "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run\\Updater = "
+        ));
+        let (results, _) = search(source(), &["2"]).await;
+        assert_eq!(
+            results,
+            vec![
+                "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\Tcpip\\Start = \"2\" (REG_DWORD)"
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tests_numbers_with_predicates() {
+        let start = "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\Tcpip\\Start = \"2\" (REG_DWORD)";
+        for term in ["= 0x2", "in 1..=3", "& 0x6", "< 4"] {
+            let (results, errors) = search(source(), &[term]).await;
+            assert!(errors.is_empty());
+            assert_eq!(results, vec![start], "{}", term);
+        }
+        for term in ["= 0x12", ">= 4096", "& 0x1"] {
+            let (results, _) = search(source(), &[term]).await;
+            assert!(results.is_empty(), "{}", term);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn filters_timestamps_by_date_range() {
+        let stash = || {
+            let mut source = source();
+            source.set_value(
+                "HKEY_CURRENT_USER",
+                "Software\\Stash",
+                "Installed",
+                RegistryValue {
+                    vtype: RegType::REG_QWORD,
+                    bytes: 133_511_709_670_000_000u64.to_le_bytes().to_vec(),
+                },
+            );
+            source
+        };
+        let (results, errors) = search(stash(), &["date:2024-01-01..2024-01-31"]).await;
+        assert!(errors.is_empty());
+        assert_eq!(
+            results,
+            vec![
+                "HKEY_CURRENT_USER\\Software\\Stash\\Installed = \"133511709670000000\" (REG_QWORD) [FILETIME 2024-01-31 10:36:07 UTC]"
+            ]
+        );
+        let (results, _) = search(stash(), &["date:2024-02-01.."]).await;
+        assert!(results.is_empty());
+        assert!(SearchTerm::parse("date:2024-02-30..").is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn matches_utf16_string_data() {
+        let (results, _) = search(source(), &["appdata\\evil.exe"]).await;
+        assert_eq!(
+            results,
+            vec![
+                "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run\\Updater = \"C:\\Users\\bob\\AppData\\evil.exe\" (REG_SZ)"
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn matches_byte_patterns_in_raw_data() {
+        let stash = || {
+            let mut source = source();
+            let mut payload = vec![0xCC; 0x10];
+            payload.extend([0x4D, 0x5A, 0x90, 0x00, 0x03, 0x00, 0x00, 0x50, 0x45]);
+            source.set_value(
+                "HKEY_CURRENT_USER",
+                "Software\\Stash",
+                "Blob",
+                RegistryValue {
+                    vtype: RegType::REG_BINARY,
+                    bytes: payload,
+                },
+            );
+            source
+        };
+        let (results, _) = search(stash(), &["hex:4D 5A ?? ?? [2-4] 50 45"]).await;
+        assert_eq!(
+            results,
+            vec!["HKEY_CURRENT_USER\\Software\\Stash\\Blob = \"BIN_LENGTH: 25\" (REG_BINARY) [bytes at 0x10]"]
+        );
+        //the pattern isn't searched for as text and strings are matched on their UTF-16 bytes
+        let (results, _) = search(stash(), &["hex:50 45 00"]).await;
+        assert!(results.is_empty());
+        let (results, _) = search(stash(), &["hex:25 00 55 00"]).await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].starts_with("HKEY_CURRENT_USER\\Environment\\TEMP"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn matches_strings_extracted_from_binary_data() {
+        let recent = || {
+            let mut source = source();
+            let mut shell_item = vec![0x14, 0x00, 0x1F, 0x80, 0x01, 0x00];
+            shell_item.extend(crate::reg_file::to_utf16_bytes(
+                "C:\\Users\\bob\\Payroll.xlsx",
+            ));
+            source.set_value(
+                "HKEY_CURRENT_USER",
+                "Software\\RecentDocs",
+                "0",
+                RegistryValue {
+                    vtype: RegType::REG_BINARY,
+                    bytes: shell_item,
+                },
+            );
+            source.set_value(
+                "HKEY_CURRENT_USER",
+                "Software\\RecentDocs",
+                "1",
+                RegistryValue {
+                    vtype: RegType::REG_BINARY,
+                    bytes: [&[0x01, 0x02][..], b"http://payroll.example/\0"].concat(),
+                },
+            );
+            source
+        };
+        let (results, _) = search(recent(), &["payroll"]).await;
+        assert!(results.is_empty());
+
+        let options = SearchOptions {
+            string_min_length: Some(4),
+            ..Default::default()
+        };
+        let (results, _) = search_with(recent(), &["data:payroll.example"], options.clone()).await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].ends_with("[ASCII \"http://payroll.example/\" at 0x2]"));
+        let (results, _) = search_with(recent(), &["PAYROLL"], options).await;
+        assert_eq!(
+            results,
+            vec![
+                "HKEY_CURRENT_USER\\Software\\RecentDocs\\0 = \"BIN_LENGTH: 58\" (REG_BINARY) [UTF-16 \"C:\\Users\\bob\\Payroll.xlsx\" at 0x6]",
+                "HKEY_CURRENT_USER\\Software\\RecentDocs\\1 = \"BIN_LENGTH: 26\" (REG_BINARY) [ASCII \"http://payroll.example/\" at 0x2]",
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn matches_expanded_strings_with_an_environment() {
+        let dll = || {
+            let mut source = source();
+            source.set_value(
+                "HKEY_LOCAL_MACHINE",
+                "SYSTEM\\CurrentControlSet\\Services\\Evil\\Parameters",
+                "ServiceDll",
+                RegistryValue {
+                    vtype: RegType::REG_EXPAND_SZ,
+                    bytes: crate::reg_file::to_utf16_bytes("%SystemRoot%\\System32\\evil.dll"),
+                },
+            );
+            source
+        };
+        let term = "c:\\windows\\system32\\evil.dll";
+        let (results, _) = search(dll(), &[term]).await;
+        assert!(results.is_empty());
+
+        let mut environment = Environment::default();
+        environment.set("SYSTEMROOT", "C:\\Windows");
+        let options = SearchOptions {
+            environment: Some(environment),
+            ..Default::default()
+        };
+        let expected = vec!["HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\Evil\\Parameters\\ServiceDll = \"%SystemRoot%\\System32\\evil.dll\" (REG_EXPAND_SZ) [expanded \"C:\\Windows\\System32\\evil.dll\"]"];
+        let (results, _) = search_with(dll(), &[term], options.clone()).await;
+        assert_eq!(results, expected);
+        let query = format!("data:{} type:expand_sz", term);
+        let (results, _) = search_with(dll(), &[query.as_str()], options).await;
+        assert_eq!(results, expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn walks_every_root_and_depth() {
+        let (results, _) = search(source(), &["e"]).await;
+        for expected in [
+            "HKEY_CURRENT_USER\\Environment",
+            "HKEY_LOCAL_MACHINE\\SOFTWARE",
+            "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services",
+            "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\Tcpip",
+        ] {
+            assert!(
+                results
+                    .iter()
+                    .any(|result| result.starts_with(&format!("{} [", expected))),
+                "missing {}",
+                expected
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn filters_keys_on_their_metadata() {
+        let mut source = source();
+        source.set_key_info(
+            "HKEY_LOCAL_MACHINE",
+            "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run",
+            KeyInfo {
+                last_write_time: 133_511_709_670_000_000,
+                class_name: Some("Shell".into()),
+                ..Default::default()
+            },
+        );
+        let modified = |range: &str| SearchOptions {
+            key_filter: KeyFilter {
+                modified: Some(DateRange::parse(range).unwrap()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (results, _) = search_with(source, &[], modified("2024-01-31..2024-01-31")).await;
+        assert_eq!(
+            results,
+            vec!["HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run [modified 2024-01-31 10:36:07 UTC, class \"Shell\", 0 subkeys, 1 values, max data 60 bytes]"]
+        );
+
+        let (results, _) =
+            search_with(self::source(), &["updater"], modified("2024-02-01..")).await;
+        assert!(results.is_empty());
+        let options = SearchOptions {
+            key_filter: KeyFilter {
+                values: Some(NumberPredicate::parse(">= 1").unwrap()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (results, _) = search_with(self::source(), &["updater", "tcpip"], options).await;
+        assert_eq!(results.len(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn matches_key_acls() {
+        use crate::{
+            hive::Hive,
+            hive_source::HiveSource,
+            test_hive::{build, TestKey},
+        };
+        let root = TestKey::new("ROOT").key(TestKey::new("Services").key(TestKey::new("Weak")));
+        let mut hive_source = HiveSource::default();
+        hive_source.mount(
+            "HKEY_LOCAL_MACHINE\\SYSTEM".into(),
+            Hive::from_bytes(build(&root).bytes).unwrap(),
+        );
+        let (results, errors) = search_with(
+            hive_source,
+            &["acl:non-admins has KEY_SET_VALUE|KEY_CREATE_SUB_KEY"],
+            SearchOptions::default(),
+        )
+        .await;
+        assert!(errors.is_empty());
+        assert_eq!(
+            results,
+            vec![
+                "HKEY_LOCAL_MACHINE\\SYSTEM\\Services [modified 2019-04-17 18:40:00 UTC, 1 subkeys, 0 values, max data 0 bytes] [owner Administrators, group SYSTEM, allow Everyone KEY_SET_VALUE]",
+                "HKEY_LOCAL_MACHINE\\SYSTEM\\Services\\Weak [modified 2019-04-17 18:40:00 UTC, 0 subkeys, 0 values, max data 0 bytes] [owner Administrators, group SYSTEM, allow Everyone KEY_SET_VALUE]",
+            ]
+        );
+        let (results, _) = search(source(), &["acl:Everyone has KEY_ALL_ACCESS"]).await;
+        assert!(results.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn matches_regexes_against_paths_names_and_data() {
+        let (results, _) = search(source(), &[r"re:\\users\\[^\\]+\\appdata\\\w+\.EXE$"]).await;
+        assert_eq!(
+            results,
+            vec![
+                "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run\\Updater = \"C:\\Users\\bob\\AppData\\evil.exe\" (REG_SZ)"
+            ]
+        );
+        let (results, _) = search(source(), &["re:^tcp.p$", "re:Run$"]).await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].starts_with(
+            "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run ["
+        ));
+        assert!(SearchTerm::parse("re:(unclosed").is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn evaluates_queries_per_key_and_value() {
+        let start = "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\Tcpip\\Start = \"2\" (REG_DWORD)";
+        let updater = "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run\\Updater = \"C:\\Users\\bob\\AppData\\evil.exe\" (REG_SZ)";
+        let (results, _) = search(source(), &["path:Services AND name:start type:dword"]).await;
+        assert_eq!(results, vec![start]);
+        let (results, _) = search(source(), &[r#"data:appdata NOT data:"C:\Windows""#]).await;
+        assert_eq!(results, vec![updater]);
+        let (results, _) = search(
+            source(),
+            &[
+                "(name:temp OR name:re:^up) NOT path:HKEY_CURRENT_USER",
+                "\">= 2\" type:dword",
+            ],
+        )
+        .await;
+        assert_eq!(results, vec![updater, start]);
+
+        // Values aren't listed again for a query their key already matches.
+        let (results, _) = search(source(), &["path:Services"]).await;
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| !result.contains(" = ")));
+        let (results, _) = search(source(), &["path:Tcpip OR data:appdata"]).await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], updater);
+        assert!(results[1]
+            .starts_with("HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\Tcpip ["));
+        assert!(SearchTerm::parse("name:x AND (data:y").is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn path_is_text_without_wildcards_and_a_glob_with_them() {
+        let tcpip = "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\Tcpip [";
+        for term in [
+            "path:Tcpip",
+            "path:services\\tcp",
+            "path:HKLM\\*\\*\\Services\\Tcp?p",
+            "path:**\\Tcpip",
+            "name:x OR path:Tcpip",
+        ] {
+            let (results, _) = search(source(), &[term]).await;
+            assert_eq!(results.len(), 1, "{}", term);
+            assert!(results[0].starts_with(tcpip), "{}", term);
+        }
+        // A glob has to match from the root to the end of the path.
+        for term in [
+            "path:Tcpip*",
+            "path:Services\\Tcp?p",
+            "path:HKLM\\*\\Services",
+        ] {
+            let (results, _) = search(source(), &[term]).await;
+            assert!(results.is_empty(), "{}", term);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn applies_per_term_options() {
+        let updater = "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run\\Updater = \"C:\\Users\\bob\\AppData\\evil.exe\" (REG_SZ)";
+        let search = |term: &str, options: TermOptions| {
+            let term = SearchTerm::parse_with(term, &options).unwrap();
+            search_parsed(source(), vec![term], SearchOptions::default())
+        };
+        let case_sensitive = TermOptions {
+            case_sensitive: true,
+            ..Default::default()
+        };
+        assert_eq!(search("AppData", case_sensitive).await.0, vec![updater]);
+        assert!(search("appdata", case_sensitive).await.0.is_empty());
+        let whole_word = TermOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        assert_eq!(search("bob", whole_word).await.0, vec![updater]);
+        assert_eq!(search("evil.exe", whole_word).await.0, vec![updater]);
+        assert!(search("bo", whole_word).await.0.is_empty());
+        let exact = TermOptions {
+            exact: true,
+            ..Default::default()
+        };
+        assert_eq!(search("updater", exact).await.0, vec![updater]);
+        assert!(search("update", exact).await.0.is_empty());
+        assert_eq!(search("re:c:.*exe", exact).await.0, vec![updater]);
+
+        let name_only = TermOptions {
+            path: false,
+            data: false,
+            ..Default::default()
+        };
+        let (results, _) = search("run", TermOptions::default()).await;
+        assert_eq!(results.len(), 1);
+        assert!(search("run", name_only).await.0.is_empty());
+        assert!(search("appdata", name_only).await.0.is_empty());
+        assert_eq!(
+            search("path:Run AND updat", name_only).await.0,
+            vec![updater]
+        );
+        let nothing = TermOptions {
+            path: false,
+            name: false,
+            data: false,
+            ..Default::default()
+        };
+        assert!(SearchTerm::parse_with("run", &nothing).is_err());
+        assert_eq!(name_only.badges(), vec!["name".to_string()]);
+        assert!(TermOptions::default().badges().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn exclusions_and_suppressions_veto_results() {
+        let suppressing = |terms: &[&str], options| {
+            let terms = terms
+                .iter()
+                .map(|term| SearchTerm::parse(term).unwrap())
+                .collect();
+            async move {
+                let worker_manager = run_parsed(source(), terms, options).await;
+                let results = worker_manager.results.lock().iter().cloned().collect();
+                (results, worker_manager.suppressed.load(Ordering::SeqCst))
+            }
+        };
+        let (results, suppressed): (Vec<String>, usize) =
+            suppressing(&["CurrentVersion", "exclude:Run"], SearchOptions::default()).await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0]
+            .starts_with("HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion ["));
+        assert_eq!(suppressed, 1);
+        let (results, suppressed) =
+            suppressing(&["%", "exclude:name:temp"], SearchOptions::default()).await;
+        assert!(results.is_empty());
+        assert_eq!(suppressed, 1);
+
+        let everything = search(source(), &["re:."]).await.0;
+        let options = SearchOptions {
+            suppressions: SuppressionList::parse("HKLM\\SYSTEM\nvalue:updater").unwrap(),
+            ..Default::default()
+        };
+        let (results, suppressed) = suppressing(&["re:."], options).await;
+        assert_eq!(results.len(), everything.len() - 6);
+        assert!(results
+            .iter()
+            .all(|result| !result.contains("SYSTEM") && !result.contains("Updater")));
+        assert_eq!(suppressed, 6);
+        for literal in ["-", "-k netsvcs", "-Embedding"] {
+            assert_eq!(
+                SearchTerm::parse(literal),
+                Ok(SearchTerm::Text(literal.to_lowercase()))
+            );
+        }
+        assert!(SearchTerm::parse("exclude:").is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn matches_path_globs_and_prunes_the_walk() {
+        let (results, _) = search(
+            source(),
+            &["path:HKLM\\SOFTWARE\\*\\*\\CurrentVersion\\Run"],
+        )
+        .await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].starts_with(
+            "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run ["
+        ));
+        let (results, _) = search(source(), &["path:**\\Run\\*", "path:HKCU\\Env*\\t?mp"]).await;
+        assert_eq!(
+            results,
+            vec![
+                "HKEY_CURRENT_USER\\Environment\\TEMP = \"%USERPROFILE%\" (REG_SZ)",
+                "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run\\Updater = \"C:\\Users\\bob\\AppData\\evil.exe\" (REG_SZ)",
+            ]
+        );
+
+        let terms = |terms: &[&str]| {
+            terms
+                .iter()
+                .map(|term| SearchTerm::parse(term).unwrap())
+                .collect()
+        };
+        let worker_manager = |search_terms| {
+            WorkerManager::new(
+                source(),
+                search_terms,
+                1,
+                Arc::new(Mutex::new(BTreeSet::new())),
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(Notify::new()),
+            )
+        };
+        let globs = worker_manager(terms(&["path:HKLM\\SOFTWARE\\**"]));
+        assert!(globs.could_match("HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft"));
+        assert!(!globs.could_match("HKEY_LOCAL_MACHINE\\SYSTEM"));
+        assert!(!globs.could_match("HKEY_CURRENT_USER"));
+        let mixed = worker_manager(terms(&["path:HKLM\\SOFTWARE\\**", "tcpip"]));
+        assert!(mixed.could_match("HKEY_LOCAL_MACHINE\\SYSTEM"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn any_term_matches() {
+        let (results, _) = search(source(), &["tcpip", "updater"]).await;
+        assert_eq!(results.len(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn no_terms_or_no_match_finds_nothing() {
+        assert!(search(source(), &[]).await.0.is_empty());
+        assert!(search(source(), &["not in the tree"]).await.0.is_empty());
+        assert!(search(MemorySource::default(), &["e"]).await.0.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn deleted_keys_are_not_walked() {
+        let mut source = source();
+        assert!(source.delete_key("HKEY_LOCAL_MACHINE", "SYSTEM\\CurrentControlSet"));
+        let (results, _) = search(source, &["tcpip"]).await;
+        assert!(results.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn captures_every_key_walked_to_a_snapshot() {
+        let directory = crate::test_hive::temp_dir("worker_manager_capture");
+        let path = directory.join("capture.snapshot");
+        let snapshot = Arc::new(SnapshotWriter::create(&path).unwrap());
+        let worker_manager = Arc::new(
+            WorkerManager::new(
+                source(),
+                Vec::new(),
+                2,
+                Arc::new(Mutex::new(BTreeSet::new())),
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(Notify::new()),
+            )
+            .with_snapshot(snapshot.to_owned()),
+        );
+        worker_manager.feed_roots(source().roots());
+        run(worker_manager.to_owned()).await;
+        let summary = snapshot.finish().unwrap();
+        assert_eq!((summary.keys, summary.values), (12, 3));
+
+        let mut loaded = MemorySource::default();
+        assert!(crate::snapshot::load(&path, &mut loaded)
+            .unwrap()
+            .is_empty());
+        std::fs::remove_dir_all(directory).unwrap();
+        assert_eq!(loaded.roots(), source().roots());
+        let (results, _) = search(loaded, &["updater", "tcpip"]).await;
+        assert_eq!(results, search(source(), &["updater", "tcpip"]).await.0);
+        assert_eq!(results.len(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn labels_results_without_capturing_the_label() {
+        let labelled = |label: &str| {
+            let mut source = source();
+            source.set_label(label);
+            source.set_annotation("HKEY_CURRENT_USER", "Environment", "deleted");
+            source
+        };
+        let (before, _) = search(labelled("before.snapshot"), &["tcpip", "environment"]).await;
+        let (after, _) = search(labelled("after.snapshot"), &["tcpip", "environment"]).await;
+        assert_eq!(
+            before,
+            vec![
+                "HKEY_CURRENT_USER\\Environment [0 subkeys, 1 values, max data 28 bytes] [before.snapshot, deleted]",
+                "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\Tcpip [0 subkeys, 1 values, max data 4 bytes] [before.snapshot]",
+            ]
+        );
+        let results = before
+            .iter()
+            .chain(after.iter())
+            .collect::<BTreeSet<&String>>();
+        assert_eq!(results.len(), 4);
+
+        let directory = crate::test_hive::temp_dir("worker_manager_label");
+        let path = directory.join("capture.snapshot");
+        let snapshot = Arc::new(SnapshotWriter::create(&path).unwrap());
+        let worker_manager = Arc::new(
+            WorkerManager::new(
+                labelled("before.snapshot"),
+                Vec::new(),
+                2,
+                Arc::new(Mutex::new(BTreeSet::new())),
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(Notify::new()),
+            )
+            .with_snapshot(snapshot.to_owned()),
+        );
+        worker_manager.feed_roots(source().roots());
+        run(worker_manager.to_owned()).await;
+        snapshot.finish().unwrap();
+        let mut loaded = MemorySource::default();
+        crate::snapshot::load(&path, &mut loaded).unwrap();
+        std::fs::remove_dir_all(directory).unwrap();
+        loaded.set_label("capture.snapshot");
+        let (results, _) = search(loaded, &["tcpip", "environment"]).await;
+        assert_eq!(
+            results,
+            before
+                .iter()
+                .map(|result| result.replace("before.snapshot", "capture.snapshot"))
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn lints_every_value_walked() {
+        let mut source = source();
+        for (root, key_path, value_name, vtype, bytes) in [
+            ("HKEY_CURRENT_USER", "", "", RegType::REG_DWORD, vec![1, 0]),
+            (
+                "HKEY_LOCAL_MACHINE",
+                "SYSTEM\\CurrentControlSet",
+                "SymbolicLinkValue",
+                RegType::REG_LINK,
+                "\\Registry\\Machine\\SYSTEM\\ControlSet009"
+                    .encode_utf16()
+                    .flat_map(|unit| unit.to_le_bytes())
+                    .collect(),
+            ),
+        ] {
+            source.set_value(root, key_path, value_name, RegistryValue { vtype, bytes });
+        }
+        let lint = Arc::new(LintReport::default());
+        let worker_manager = Arc::new(
+            WorkerManager::new(
+                source,
+                Vec::new(),
+                2,
+                Arc::new(Mutex::new(BTreeSet::new())),
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(Notify::new()),
+            )
+            .with_lint(lint.to_owned()),
+        );
+        worker_manager.feed_roots(worker_manager.source.roots());
+        run(worker_manager.to_owned()).await;
+        assert_eq!(
+            lint.to_string(),
+            "Wrong size (1)\n    HKEY_CURRENT_USER\\(Default) (REG_DWORD): 2 bytes, expected 4\n\
+             Dangling link (1)\n    HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\SymbolicLinkValue (REG_LINK): \"\\Registry\\Machine\\SYSTEM\\ControlSet009\" doesn't exist\n"
+        );
+    }
+}
@@ -1,13 +1,27 @@
-use tracing::{debug, info};
-use winreg::RegKey;
+use tracing::{debug, error, info, warn};
 
+#[cfg(windows)]
+use crate::winreg_source::WinregSource;
 use crate::{
-    root::Root,
+    carve,
+    hive::Hive,
+    hive_integrity,
+    hive_recovery::{load_deleted, recover_deleted},
+    hive_source::HiveSource,
+    lint::LintReport,
+    memory_source::MemorySource,
+    offline_source::OfflineSource,
+    policy_file, reg_file,
+    registry_source::RegistrySource,
+    search_term::{self, SearchTerm},
+    snapshot::{self, SnapshotSummary, SnapshotWriter},
     static_selection::StaticSelection,
     worker_manager::{run, WorkerManager},
-    KEY_COUNT, VALUE_COUNT,
+    KEY_COUNT, SUPPRESSED_COUNT, VALUE_COUNT,
 };
 use std::{
+    fmt::Display,
+    path::Path,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -33,57 +47,71 @@ pub async fn worker_runtime(
         }
         KEY_COUNT.store(0, Ordering::SeqCst);
         VALUE_COUNT.store(0, Ordering::SeqCst);
-
-        let roots = static_menu_selection.selected_roots.read().export_roots();
-        let search_terms = static_menu_selection
-            .search_term_tracker
-            .read()
-            .search_terms
-            .iter()
-            .map(|value| value.to_string())
-            .collect::<Vec<String>>();
-        let worker_manager = Arc::new(WorkerManager::new(
-            search_terms,
-            num_cpus::get(),
-            static_menu_selection.results.to_owned(),
-            static_menu_selection.stop.to_owned(),
-            static_menu_selection.stop_notify.to_owned(),
-        ));
-
-        let mut work = Vec::new();
-        for root in roots {
-            for key_result in RegKey::predef(root).enum_keys() {
-                KEY_COUNT.fetch_add(1, Ordering::SeqCst);
-                match key_result {
-                    Ok(key_name) => work.push((root, key_name)),
-                    Err(err) => {
-                        let root_name = match Root::from_isize(root) {
-                            Some(root) => root.to_string(),
-                            None => "InvalidRoot".into(),
-                        };
-                        let _ = worker_manager
-                            .errors
-                            .lock()
-                            .insert(format!("{}, Subkey error: \"{}\"", root_name, err));
-                    }
-                }
-            }
+        SUPPRESSED_COUNT.store(0, Ordering::SeqCst);
+        {
+            let mut run_errors = static_menu_selection.run_errors.lock();
+            run_errors.clear();
+            run_errors.extend(static_menu_selection.setup_errors.lock().iter().cloned());
         }
-
-        worker_manager.feed_queue(work);
-        let start_time = Instant::now();
-        run(worker_manager.to_owned()).await;
-
-        /* eprintln!("Errors:");
-        for error in worker_manager.errors.lock().iter() {
-            eprintln!("{}", error);
+        //terms are parsed, and regexes compiled, once for every source searched
+        let (search_terms, term_errors) = search_term::parse_all(
+            static_menu_selection
+                .search_term_tracker
+                .read()
+                .search_terms
+                .iter(),
+        );
+        for err in term_errors {
+            warn!("{}", err);
+            static_menu_selection.run_errors.lock().insert(err);
         }
 
-        println!("\nResults:");
-        for result in worker_manager.results.lock().iter() {
-            println!("{}", result);
-        } */
-        info!("Completed in {}ms.", start_time.elapsed().as_millis());
+        let (has_offline_sources, offline_sources) = {
+            let selected_sources = static_menu_selection.selected_sources.read();
+            (
+                !selected_sources.is_empty(),
+                selected_sources.export_sources(),
+            )
+        };
+        if !has_offline_sources {
+            #[cfg(windows)]
+            {
+                let roots = static_menu_selection
+                    .selected_roots
+                    .read()
+                    .export_roots()
+                    .iter()
+                    .map(|root| root.to_string())
+                    .collect::<Vec<String>>();
+                search(
+                    WinregSource,
+                    roots,
+                    &search_terms,
+                    &static_menu_selection,
+                    None,
+                    None,
+                )
+                .await;
+            }
+            #[cfg(not(windows))]
+            {
+                let message = "No live registry on this platform, pass an offline source instead.";
+                error!("{}", message);
+                static_menu_selection
+                    .run_errors
+                    .lock()
+                    .insert(message.to_string());
+            }
+        } else {
+            search_offline(
+                offline_sources,
+                &search_terms,
+                &static_menu_selection,
+                None,
+                None,
+            )
+            .await;
+        }
 
         static_menu_selection.stop.store(false, Ordering::SeqCst);
         let mut timer_lock = static_menu_selection.timer.write();
@@ -98,3 +126,375 @@ pub async fn worker_runtime(
     }
     debug!("Worker thread closed.");
 }
+
+/// Walks the offline sources, or the live registry's default roots when there are none, and
+/// writes every key to a snapshot at `path`. Sources that fail to load are returned as errors
+/// alongside the summary rather than failing the capture.
+pub async fn capture(
+    offline_sources: Vec<OfflineSource>,
+    path: &Path,
+) -> Result<(SnapshotSummary, Vec<String>), String> {
+    #[cfg(not(windows))]
+    if offline_sources.is_empty() {
+        return Err(
+            "No live registry on this platform, pass an offline source to capture instead.".into(),
+        );
+    }
+    let snapshot = Arc::new(SnapshotWriter::create(path)?);
+    let static_menu_selection = StaticSelection::default();
+    if offline_sources.is_empty() {
+        #[cfg(windows)]
+        {
+            let roots = crate::root::SelectedRoots::default()
+                .export_roots()
+                .iter()
+                .map(|root| root.to_string())
+                .collect::<Vec<String>>();
+            search(
+                WinregSource,
+                roots,
+                &[],
+                &static_menu_selection,
+                Some(&snapshot),
+                None,
+            )
+            .await;
+        }
+    } else {
+        search_offline(
+            offline_sources,
+            &[],
+            &static_menu_selection,
+            Some(&snapshot),
+            None,
+        )
+        .await;
+    }
+    let summary = snapshot.finish()?;
+    let errors = static_menu_selection
+        .run_errors
+        .lock()
+        .iter()
+        .cloned()
+        .collect();
+    Ok((summary, errors))
+}
+
+/// Walks the offline sources, or the live registry's default roots when there are none, and
+/// checks every value for data that doesn't fit its type. Sources that fail to load are returned
+/// as errors alongside the report.
+pub async fn lint(
+    offline_sources: Vec<OfflineSource>,
+) -> Result<(Arc<LintReport>, Vec<String>), String> {
+    #[cfg(not(windows))]
+    if offline_sources.is_empty() {
+        return Err(
+            "No live registry on this platform, pass an offline source to lint instead.".into(),
+        );
+    }
+    let lint = Arc::new(LintReport::default());
+    let static_menu_selection = StaticSelection::default();
+    if offline_sources.is_empty() {
+        #[cfg(windows)]
+        {
+            let roots = crate::root::SelectedRoots::default()
+                .export_roots()
+                .iter()
+                .map(|root| root.to_string())
+                .collect::<Vec<String>>();
+            search(
+                WinregSource,
+                roots,
+                &[],
+                &static_menu_selection,
+                None,
+                Some(&lint),
+            )
+            .await;
+        }
+    } else {
+        search_offline(
+            offline_sources,
+            &[],
+            &static_menu_selection,
+            None,
+            Some(&lint),
+        )
+        .await;
+    }
+    let errors = static_menu_selection
+        .run_errors
+        .lock()
+        .iter()
+        .cloned()
+        .collect();
+    Ok((lint, errors))
+}
+
+async fn search_offline(
+    offline_sources: Vec<OfflineSource>,
+    search_terms: &[SearchTerm],
+    static_menu_selection: &StaticSelection,
+    snapshot: Option<&Arc<SnapshotWriter>>,
+    lint: Option<&Arc<LintReport>>,
+) {
+    let mut hive_source = HiveSource::default();
+    //every file gets its own source, so deletions in one can't remove keys another loaded
+    let mut memory_sources = Vec::new();
+    let mut labels = Vec::new();
+    for offline_source in offline_sources {
+        match offline_source {
+            OfflineSource::Hive {
+                path,
+                root,
+                recover_deleted: recover,
+            } => match Hive::open(&path) {
+                Ok(hive) => {
+                    if let Some(log_replay) = hive.log_replay.as_ref() {
+                        info!(
+                            "{}: dirty hive, applied {} log entries from {:?}",
+                            path.display(),
+                            log_replay.entries_applied,
+                            log_replay.logs
+                        );
+                        for warning in log_replay.warnings.iter() {
+                            warn!("{}: {}", path.display(), warning);
+                        }
+                        for key_path in hive.log_only_key_paths() {
+                            info!(
+                                "{}: key only in transaction log: {}",
+                                path.display(),
+                                key_path
+                            );
+                        }
+                    }
+                    let root = hive_source.unique_root_name(&root);
+                    report_integrity(static_menu_selection, &root, &hive);
+                    if recover {
+                        memory_sources.push(load_recovered(&hive, &path.display(), &root));
+                    }
+                    hive_source.mount(root, hive);
+                }
+                Err(err) => load_failed(
+                    static_menu_selection,
+                    format!("Failed to load hive: {}", err),
+                ),
+            },
+            OfflineSource::RegFile { path } => {
+                let mut memory_source = MemorySource::default();
+                match reg_file::load(&path, &mut memory_source) {
+                    Ok(warnings) => {
+                        for warning in warnings {
+                            warn!("{}: {}", path.display(), warning);
+                        }
+                        memory_source.set_label(&file_label(&path, &mut labels));
+                        memory_sources.push(memory_source);
+                    }
+                    Err(err) => load_failed(
+                        static_menu_selection,
+                        format!("Failed to load .reg file: {}", err),
+                    ),
+                }
+            }
+            OfflineSource::PolicyFile { path, root } => {
+                let mut memory_source = MemorySource::default();
+                match policy_file::load(&path, &root, &mut memory_source) {
+                    Ok(warnings) => {
+                        for warning in warnings {
+                            warn!("{}: {}", path.display(), warning);
+                        }
+                        memory_source.set_label(&file_label(&path, &mut labels));
+                        memory_sources.push(memory_source);
+                    }
+                    Err(err) => load_failed(
+                        static_menu_selection,
+                        format!("Failed to load policy file: {}", err),
+                    ),
+                }
+            }
+            OfflineSource::Carved {
+                path,
+                offset,
+                root,
+                recover_deleted: recover,
+            } => match carve::carve_at(&path, offset)
+                .and_then(|carved| Hive::from_bytes(carved.data))
+            {
+                Ok(hive) if hive.root_key().is_ok() => {
+                    let root = hive_source.unique_root_name(&root);
+                    report_integrity(static_menu_selection, &root, &hive);
+                    if recover {
+                        memory_sources.push(load_recovered(&hive, &root, &root));
+                    }
+                    hive_source.mount(root, hive);
+                }
+                Ok(hive) => {
+                    let mut memory_source = MemorySource::default();
+                    let keys = carve::load_fragment(&hive, &root, &mut memory_source);
+                    info!("{}: {} keys without a root", root, keys);
+                    if recover {
+                        memory_sources.push(load_recovered(&hive, &root, &root));
+                    }
+                    memory_sources.push(memory_source);
+                }
+                Err(err) => load_failed(
+                    static_menu_selection,
+                    format!("Failed to load carved hive: {}", err),
+                ),
+            },
+            OfflineSource::Snapshot { path } => {
+                let mut memory_source = MemorySource::default();
+                match snapshot::load(&path, &mut memory_source) {
+                    Ok(warnings) => {
+                        //a damaged snapshot loads up to the damage, the results are partial
+                        for warning in warnings {
+                            load_failed(
+                                static_menu_selection,
+                                format!("Snapshot partly loaded: {}: {}", path.display(), warning),
+                            );
+                        }
+                        memory_source.set_label(&file_label(&path, &mut labels));
+                        memory_sources.push(memory_source);
+                    }
+                    Err(err) => load_failed(
+                        static_menu_selection,
+                        format!("Failed to load snapshot: {}", err),
+                    ),
+                }
+            }
+        }
+    }
+    if !hive_source.is_empty() {
+        let roots = hive_source.roots();
+        search(
+            hive_source,
+            roots,
+            search_terms,
+            static_menu_selection,
+            snapshot,
+            lint,
+        )
+        .await;
+    }
+    for memory_source in memory_sources {
+        let roots = memory_source.roots();
+        if !roots.is_empty() {
+            search(
+                memory_source,
+                roots,
+                search_terms,
+                static_menu_selection,
+                snapshot,
+                lint,
+            )
+            .await;
+        }
+    }
+}
+
+/// Logs a source that couldn't be loaded and lists it in red above the results.
+fn load_failed(static_menu_selection: &StaticSelection, message: String) {
+    error!("{}", message);
+    static_menu_selection.run_errors.lock().insert(message);
+}
+
+/// Logs every bad cell the integrity check finds in a hive and lists a summary above the
+/// results, the rest of the hive is still searched.
+fn report_integrity(static_menu_selection: &StaticSelection, root: &str, hive: &Hive) {
+    let report = hive_integrity::check(hive);
+    if report.is_clean() {
+        return;
+    }
+    for bad_cell in report.bad_cells.iter() {
+        match bad_cell.key_path.as_deref() {
+            Some(key_path) => warn!(
+                "{}\\{}: bad cell at 0x{:X}: {}",
+                root, key_path, bad_cell.offset, bad_cell.problem
+            ),
+            None => warn!(
+                "{}: bad cell at 0x{:X}: {}",
+                root, bad_cell.offset, bad_cell.problem
+            ),
+        }
+    }
+    warn!("{}: {}", root, report);
+    static_menu_selection
+        .run_errors
+        .lock()
+        .insert(format!("{}: {}", root, report));
+}
+
+/// The file name the roots a .reg or policy file loads are put under, followed by " (2)", " (3)"
+/// and so on when an earlier file had the same name.
+fn file_label(path: &Path, labels: &mut Vec<String>) -> String {
+    let file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let taken = |label: &String| labels.iter().any(|taken| taken.eq_ignore_ascii_case(label));
+    let label = (1..)
+        .map(|number| match number {
+            1 => file_name.to_owned(),
+            number => format!("{} ({})", file_name, number),
+        })
+        .find(|label| !taken(label))
+        .unwrap();
+    labels.push(label.to_owned());
+    label
+}
+
+/// The deleted keys and values of one hive, under a root named after the hive's own.
+fn load_recovered(hive: &Hive, label: &dyn Display, root: &str) -> MemorySource {
+    let deleted = recover_deleted(hive);
+    info!(
+        "{}: recovered {} deleted keys and {} orphaned values",
+        label,
+        deleted.keys.len(),
+        deleted.orphaned_values.len()
+    );
+    let mut memory_source = MemorySource::default();
+    load_deleted(deleted, root, &mut memory_source);
+    memory_source
+}
+
+async fn search<S: RegistrySource + 'static>(
+    source: S,
+    roots: Vec<String>,
+    search_terms: &[SearchTerm],
+    static_menu_selection: &StaticSelection,
+    snapshot: Option<&Arc<SnapshotWriter>>,
+    lint: Option<&Arc<LintReport>>,
+) {
+    let mut worker_manager = WorkerManager::new(
+        source,
+        search_terms.to_vec(),
+        num_cpus::get(),
+        static_menu_selection.results.to_owned(),
+        static_menu_selection.stop.to_owned(),
+        static_menu_selection.stop_notify.to_owned(),
+    )
+    .with_options(static_menu_selection.search_options.read().to_owned());
+    if let Some(snapshot) = snapshot {
+        worker_manager = worker_manager.with_snapshot(snapshot.to_owned());
+    }
+    if let Some(lint) = lint {
+        worker_manager = worker_manager.with_lint(lint.to_owned());
+    }
+    let worker_manager = Arc::new(worker_manager);
+
+    worker_manager.feed_roots(roots);
+    let start_time = Instant::now();
+    run(worker_manager.to_owned()).await;
+
+    /* eprintln!("Errors:");
+    for error in worker_manager.errors.lock().iter() {
+        eprintln!("{}", error);
+    }
+
+    println!("\nResults:");
+    for result in worker_manager.results.lock().iter() {
+        println!("{}", result);
+    } */
+    info!("Completed in {}ms.", start_time.elapsed().as_millis());
+}